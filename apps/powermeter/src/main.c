@@ -3,6 +3,7 @@
 #include <zephyr/sys/reboot.h>
 #include <zephyr/kernel.h>
 #include <zephyr/settings/settings.h>
+#include <zephyr/shell/shell.h>
 
 #include "main.h"
 
@@ -17,61 +18,140 @@ void app_unrecoverable_error(void)
 	sys_reboot(SYS_REBOOT_COLD);
 }
 
-#ifdef CONFIG_SMARTMETER_RUST_LOGGER
+#ifdef CONFIG_SMARTMETER_RUST_LOGGER_DEFERRED
+/* logs deferred records in hex, they're decoded on the host using `smllog` */
 static uint32_t logger_sink(const enum smr_loglevel level, const void *const buf,
 			    const uintptr_t len)
 {
-	static uint8_t logbuf[1000];
-	static size_t logbuf_used = 0;
-	static bool overflow = false;
+	/* records are limited to 64 bytes */
+	static char hex[2 * 64 + 1];
+	static size_t hex_used = 0;
 
 	if (buf == NULL) {
-		if (overflow) {
-			LOG_WRN("**truncated**");
-		} else {
-			switch (level) {
-			case smr_loglevel_error:
-				LOG_ERR("%.*s", logbuf_used, logbuf);
-				break;
-
-			case smr_loglevel_warn:
-				LOG_WRN("%.*s", logbuf_used, logbuf);
-				break;
-
-			case smr_loglevel_info:
-				LOG_INF("%.*s", logbuf_used, logbuf);
-				break;
-
-			case smr_loglevel_debug:
-				LOG_DBG("%.*s", logbuf_used, logbuf);
-				break;
-
-			case smr_loglevel_trace:
-				LOG_DBG("TRACE - %.*s", logbuf_used, logbuf);
-				break;
-
-			default:
-				break;
-			}
+		switch (level) {
+		case smr_loglevel_error:
+			LOG_ERR("smrlog:%s", hex);
+			break;
+
+		case smr_loglevel_warn:
+			LOG_WRN("smrlog:%s", hex);
+			break;
+
+		case smr_loglevel_info:
+			LOG_INF("smrlog:%s", hex);
+			break;
+
+		default:
+			LOG_DBG("smrlog:%s", hex);
+			break;
 		}
 
-		overflow = false;
-		logbuf_used = 0;
+		hex_used = 0;
+		hex[0] = 0;
 		return 0;
 	}
 
-	if (logbuf_used + len >= sizeof(logbuf)) {
-		overflow = true;
-		return 0;
+	hex_used += bin2hex((const uint8_t *)buf, MIN(len, (sizeof(hex) - 1 - hex_used) / 2),
+			    hex + hex_used, sizeof(hex) - hex_used);
+
+	return 0;
+}
+#elif defined(CONFIG_SMARTMETER_RUST_LOGGER)
+/* the strings are NUL-terminated and only valid during the callback, deferred logging copies
+ * them using `strlen`
+ */
+#define LOG_RECORD_FMT "%s: %s%s"
+#define LOG_RECORD_ARGS(record)                                                                    \
+	(record)->target.data, (record)->message.data, (record)->truncated ? " **truncated**" : ""
+
+static void logger_record_sink(const struct smr_log_record *const record)
+{
+	switch (record->level) {
+	case smr_loglevel_error:
+		LOG_ERR(LOG_RECORD_FMT, LOG_RECORD_ARGS(record));
+		break;
+
+	case smr_loglevel_warn:
+		LOG_WRN(LOG_RECORD_FMT, LOG_RECORD_ARGS(record));
+		break;
+
+	case smr_loglevel_info:
+		LOG_INF(LOG_RECORD_FMT, LOG_RECORD_ARGS(record));
+		break;
+
+	case smr_loglevel_debug:
+		LOG_DBG(LOG_RECORD_FMT, LOG_RECORD_ARGS(record));
+		break;
+
+	case smr_loglevel_trace:
+		LOG_DBG("TRACE - " LOG_RECORD_FMT, LOG_RECORD_ARGS(record));
+		break;
+
+	default:
+		break;
 	}
+}
+#endif /* CONFIG_SMARTMETER_RUST_LOGGER_DEFERRED */
 
-	memcpy(logbuf + logbuf_used, buf, len);
-	logbuf_used += len;
-	logbuf[logbuf_used] = 0;
+#if defined(CONFIG_SMARTMETER_RUST_LOGGER) && defined(CONFIG_SHELL)
+static int cmd_smr_log_level(const struct shell *const sh, const size_t argc, char **argv)
+{
+	static const char *const names[] = {
+		[smr_loglevel_filter_off] = "off",
+		[smr_loglevel_filter_error] = "error",
+		[smr_loglevel_filter_warn] = "warn",
+		[smr_loglevel_filter_info] = "info",
+		[smr_loglevel_filter_debug] = "debug",
+		[smr_loglevel_filter_trace] = "trace",
+	};
+
+	const char *const target = argc == 3 ? argv[2] : NULL;
+	size_t level;
+
+	for (level = 0; level < ARRAY_SIZE(names); level++) {
+		if (strcmp(argv[1], names[level]) == 0) {
+			break;
+		}
+	}
+	if (level == ARRAY_SIZE(names)) {
+		shell_print(sh, "Invalid level: %s", argv[1]);
+		return -EINVAL;
+	}
+
+	const uint32_t smrrc = smr_set_log_level(target, (uint32_t)level);
+	if (smrrc) {
+		shell_print(sh, "Failed to set log level: %u", smrrc);
+		return -EINVAL;
+	}
+
+	shell_print(sh, "new log level of %s: %s", target ? target : "all targets", names[level]);
 
 	return 0;
 }
-#endif /* CONFIG_SMARTMETER_RUST_LOGGER */
+
+static int cmd_smr_log_reset(const struct shell *const sh, const size_t argc, char **argv)
+{
+	ARG_UNUSED(argc);
+	ARG_UNUSED(argv);
+
+	const uint32_t smrrc = smr_reset_log_levels();
+	if (smrrc) {
+		shell_print(sh, "Failed to reset log levels: %u", smrrc);
+		return -EINVAL;
+	}
+
+	return 0;
+}
+
+SHELL_STATIC_SUBCMD_SET_CREATE(sub_smr_log,
+		SHELL_CMD_ARG(level, NULL, "Set the level: <off|error|warn|info|debug|trace> [target]",
+			      cmd_smr_log_level, 2, 1),
+		SHELL_CMD(reset, NULL, "Reset all levels.", cmd_smr_log_reset),
+		SHELL_SUBCMD_SET_END
+		);
+
+SHELL_CMD_REGISTER(smr_log, &sub_smr_log, "Rust smartmeter logger commands", NULL);
+#endif /* CONFIG_SMARTMETER_RUST_LOGGER && CONFIG_SHELL */
 
 int main(void)
 {
@@ -87,7 +167,11 @@ int main(void)
 	mqttsndev_init();
 
 #ifdef CONFIG_SMARTMETER_RUST_LOGGER
+#ifdef CONFIG_SMARTMETER_RUST_LOGGER_DEFERRED
 	uint32_t smrrc = smr_init_logger(logger_sink);
+#else
+	uint32_t smrrc = smr_init_logger_record(logger_record_sink);
+#endif
 	if (smrrc) {
 		LOG_ERR("sml logger init failed: %u", smrrc);
 		app_unrecoverable_error();
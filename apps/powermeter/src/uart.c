@@ -9,10 +9,6 @@
 #include <zephyr/logging/log.h>
 LOG_MODULE_REGISTER(powermeter_uart, CONFIG_APP_LOG_LEVEL);
 
-struct smr_context {
-	uint8_t buf[520];
-};
-
 #define RX_BUFFER_SIZE CONFIG_APP_UART_ASYNC_RX_BUFFER_SIZE
 #define RX_BUFFER_NUM CONFIG_APP_UART_ASYNC_RX_NUM_BUFFERS
 K_MEM_SLAB_DEFINE_STATIC(uart_async_rx_slab, RX_BUFFER_SIZE, RX_BUFFER_NUM, 4);
@@ -21,11 +17,6 @@ K_MEM_SLAB_DEFINE_STATIC(uart_async_rx_slab, RX_BUFFER_SIZE, RX_BUFFER_NUM, 4);
 BUILD_ASSERT(DT_NODE_HAS_STATUS(DEFAULT_UART_NODE, okay), "No default UART specified in DT");
 static const struct device *const uart_dev = DEVICE_DT_GET(DEFAULT_UART_NODE);
 
-static K_SEM_DEFINE(uart_rx_sem, 0, 1);
-static struct k_poll_event uart_rx_event = K_POLL_EVENT_STATIC_INITIALIZER(
-	K_POLL_TYPE_SEM_AVAILABLE, K_POLL_MODE_NOTIFY_ONLY, &uart_rx_sem, 0);
-static struct k_work_poll uart_rx_work;
-
 // NOTE: `RING_BUF_DECLARE` doesn't support static linkage
 static uint8_t rx_rb_buf[CONFIG_APP_RINGBUF_SIZE];
 static struct ring_buf rx_rb;
@@ -35,6 +26,8 @@ static float active_power;
 static size_t num_samples;
 
 static void schedule_startrx_work(void);
+static void uart_rx_work_handler(struct k_work *work);
+static K_WORK_DEFINE(uart_rx_work, uart_rx_work_handler);
 
 static void startrx_work_handler(struct k_work *work)
 {
@@ -88,14 +81,16 @@ static void uart_async_callback(const struct device *const dev, struct uart_even
 		k_mem_slab_free(&uart_async_rx_slab, evt->data.rx_buf.buf);
 		break;
 	case UART_RX_RDY:
-		/* Place received data on the ring buffer */
+		/* `sml_feed` decodes the data and runs the callbacks, so it can't be called from the
+		 * ISR. Buffer the data until the work handler feeds it.
+		 */
 		written = ring_buf_put(&rx_rb, evt->data.rx.buf + evt->data.rx.offset,
 				       evt->data.rx.len);
 		if (written != evt->data.rx.len) {
 			LOG_WRN("Received bytes dropped from ring buf");
 		}
-		/* Notify upper layer that new data has arrived */
-		k_sem_give(&uart_rx_sem);
+		/* Feed the data to the SML reader outside of the ISR */
+		k_work_submit(&uart_rx_work);
 		break;
 	case UART_RX_DISABLED:
 		LOG_ERR("RX disabled: %d", evt->data.rx_stop.reason);
@@ -109,44 +104,100 @@ static void uart_async_callback(const struct device *const dev, struct uart_even
 	}
 }
 
-static struct smr_context __aligned(8) smlctx;
+static struct smr_context smlctx;
+static void sml_timeout_work_handler(struct k_work *work);
+static K_WORK_DELAYABLE_DEFINE(sml_timeout_work, sml_timeout_work_handler);
+
+/* the longest delay before polling a reset SML reader again */
+#define SML_RESET_BACKOFF_MAX_MS 60000
+/* how often the SML reader was reset in a row, for the backoff */
+static unsigned int sml_resets;
+
+/* The SML reader returns 4 if it keeps failing, e.g. because of a disturbed link. That's
+ * recovered by resetting it, the other errors are caused by invalid arguments.
+ */
+static void handle_sml_error(const char *const what, const uint32_t smlrc)
+{
+	uint32_t delay_ms;
+
+	if (smlrc != 4) {
+		LOG_ERR("%s failed: %u", what, smlrc);
+		app_unrecoverable_error();
+		return;
+	}
+
+	delay_ms = MIN((uint32_t)CONFIG_APP_SML_POLL_INTERVAL_MS << MIN(sml_resets, 10),
+		       SML_RESET_BACKOFF_MAX_MS);
+	sml_resets++;
+	LOG_ERR("%s keeps failing, resetting the SML reader and polling again in %u ms", what,
+		delay_ms);
+
+	(void)sml_reset(&smlctx);
+	(void)k_work_reschedule(&sml_timeout_work, K_MSEC(delay_ms));
+}
+
 static void uart_rx_work_handler(struct k_work *work)
 {
-	int ret;
+	uint8_t buf[64];
+	uint32_t len;
 	uint32_t smlrc;
 
 	ARG_UNUSED(work);
 
-	if (k_sem_take(&uart_rx_sem, K_NO_WAIT) != 0) {
-		// if it's NULL, it's the initial call from the main function
-		if (work) {
-			LOG_WRN("spurious uart work handler call");
+	while ((len = ring_buf_get(&rx_rb, buf, sizeof(buf))) > 0) {
+		smlrc = sml_feed(&smlctx, buf, len);
+		if (smlrc) {
+			/* the rest of the data is fed after the next RX event */
+			handle_sml_error("sml feed", smlrc);
+			return;
 		}
-		goto resubmit;
 	}
+}
 
-	smlrc = sml_poll(&smlctx);
+static void sml_timeout_work_handler(struct k_work *work)
+{
+	uint32_t smlrc;
+
+	// runs on the same workqueue as `uart_rx_work`, so they never use the context concurrently
+	do {
+		// 5: the reader was restarted and has to be polled again
+		smlrc = sml_poll(&smlctx);
+	} while (smlrc == 5);
 	if (smlrc) {
-		LOG_ERR("sml poll failed: %u", smlrc);
-		app_unrecoverable_error();
+		handle_sml_error("sml poll", smlrc);
 		return;
 	}
+	sml_resets = 0;
 
-resubmit:
-	ret = k_work_poll_submit(&uart_rx_work, &uart_rx_event, 1, K_FOREVER);
-	if (ret != 0) {
-		LOG_ERR("Failed to submit uart rx work polling: %d", ret);
-		app_unrecoverable_error();
-		return;
-	}
+	(void)k_work_schedule(k_work_delayable_from_work(work),
+			      K_MSEC(CONFIG_APP_SML_POLL_INTERVAL_MS));
 }
 
-static uint32_t sml_read_cb(void *const buf, const uintptr_t max_length,
-			    uintptr_t *const out_length)
+static uint64_t sml_uptime_cb(void)
 {
-	const uint32_t bytes_read = ring_buf_get(&rx_rb, buf, max_length);
-	*out_length = bytes_read;
-	return 0;
+	return k_uptime_get();
+}
+
+static void sml_event_cb(void *const user_data, const enum smr_event event)
+{
+	ARG_UNUSED(user_data);
+
+	switch (event) {
+	case smr_event_no_frame:
+		LOG_WRN("no SML frame received");
+		break;
+
+	case smr_event_frame_aborted:
+		LOG_WRN("SML frame aborted, no data received");
+		break;
+
+	case smr_event_restarted:
+		LOG_WRN("SML reader failed and was restarted");
+		break;
+
+	default:
+		break;
+	}
 }
 
 int app_publish_callback(struct mqtt_sn_client*const client) {
@@ -219,13 +270,13 @@ int app_setup_uart(void)
 	schedule_startrx_work();
 	LOG_INF("sml ctxsz = %lu", sml_ctxsz());
 
-	const uint32_t smlrc = sml_init(&smlctx, sizeof(smlctx), NULL, sml_read_cb, sml_data_cb);
+	const uint32_t smlrc = sml_init(smr_API_VERSION, &smlctx, sizeof(smlctx), NULL, NULL,
+					sml_data_cb, sml_uptime_cb, sml_event_cb, NULL, NULL);
 	if (smlrc) {
 		LOG_ERR("sml init failed: %u", smlrc);
 		return -1;
 	}
-	k_work_poll_init(&uart_rx_work, uart_rx_work_handler);
-	uart_rx_work_handler(NULL);
+	(void)k_work_schedule(&sml_timeout_work, K_MSEC(CONFIG_APP_SML_POLL_INTERVAL_MS));
 
 	return 0;
 }
@@ -0,0 +1,325 @@
+//! turns records back into messages using the interned strings from the ELF file
+
+/// the name of the section with the interned strings
+pub const SECTION: &str = "smr_log";
+
+#[derive(Debug)]
+pub enum Error {
+    /// the file couldn't be parsed
+    Elf(&'static str),
+    /// the file doesn't have a `smr_log` section, e.g. because it's not using deferred logs
+    NoSection,
+    EmptyRecord,
+    /// the record doesn't match the file
+    InvalidIndex {
+        index: usize,
+    },
+    InvalidTag {
+        tag: u8,
+    },
+    InvalidString,
+}
+
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Elf(e) => write!(f, "invalid ELF file: {}", e),
+            Self::NoSection => write!(f, "no `{}` section", SECTION),
+            Self::EmptyRecord => write!(f, "empty record"),
+            Self::InvalidIndex { index } => write!(f, "invalid string index {}", index),
+            Self::InvalidTag { tag } => write!(f, "invalid argument tag {}", tag),
+            Self::InvalidString => write!(f, "invalid string"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// a decoded record
+#[derive(Debug, PartialEq, Eq)]
+pub struct Record {
+    /// the module which logged it
+    pub target: String,
+    pub message: String,
+    /// the record ended before all arguments were read
+    pub truncated: bool,
+}
+
+/// the end of the record was reached
+struct Eof;
+
+/// reads a record
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl Reader<'_> {
+    fn u8(&mut self) -> Result<u8, Eof> {
+        let (&byte, rest) = self.data.split_first().ok_or(Eof)?;
+        self.data = rest;
+        Ok(byte)
+    }
+
+    fn varint(&mut self) -> Result<u64, Eof> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.u8()?;
+            value |= u64::from(byte & 0x7F) << shift;
+            if byte & 0x80 == 0 {
+                break;
+            }
+        }
+        Ok(value)
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&[u8], Eof> {
+        if self.data.len() < len {
+            return Err(Eof);
+        }
+        let (bytes, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(bytes)
+    }
+}
+
+/// `{` [`:` [`#`] [`0`] [width] [type]] `}`
+#[derive(Default)]
+struct Spec {
+    alternate: bool,
+    zero: bool,
+    width: usize,
+    ty: Option<char>,
+}
+
+impl Spec {
+    fn parse(spec: &str) -> Self {
+        let mut result = Self::default();
+        let mut chars = spec
+            .strip_prefix(':')
+            .unwrap_or_default()
+            .chars()
+            .peekable();
+        if chars.next_if_eq(&'#').is_some() {
+            result.alternate = true;
+        }
+        if chars.next_if_eq(&'0').is_some() {
+            result.zero = true;
+        }
+        while let Some(digit) = chars.peek().and_then(|c| c.to_digit(10)) {
+            result.width = result.width * 10 + digit as usize;
+            chars.next();
+        }
+        result.ty = chars.next();
+        result
+    }
+
+    fn pad(&self, prefix: &str, digits: &str) -> String {
+        let len = prefix.len() + digits.len();
+        if len >= self.width {
+            format!("{}{}", prefix, digits)
+        } else if self.zero {
+            format!("{}{}{}", prefix, "0".repeat(self.width - len), digits)
+        } else {
+            format!("{}{}{}", " ".repeat(self.width - len), prefix, digits)
+        }
+    }
+
+    fn unsigned(&self, value: u64) -> String {
+        let (prefix, digits) = match self.ty {
+            Some('x') => ("0x", format!("{:x}", value)),
+            Some('X') => ("0x", format!("{:X}", value)),
+            Some('b') => ("0b", format!("{:b}", value)),
+            _ => ("", value.to_string()),
+        };
+        self.pad(if self.alternate { prefix } else { "" }, &digits)
+    }
+
+    fn signed(&self, value: i64) -> String {
+        match (self.ty, value < 0) {
+            (Some('x' | 'X' | 'b'), _) => self.unsigned(value as u64),
+            (_, true) => self.pad("-", &value.unsigned_abs().to_string()),
+            (_, false) => self.pad("", &value.to_string()),
+        }
+    }
+
+    fn str(&self, value: &str) -> String {
+        let value = match self.ty {
+            Some('?') => format!("{:?}", value),
+            _ => value.to_owned(),
+        };
+        self.pad("", &value)
+    }
+}
+
+/// the interned strings of a firmware
+pub struct Table {
+    section: Vec<u8>,
+}
+
+impl Table {
+    /// reads the interned strings from an ELF file
+    pub fn from_elf(data: &[u8]) -> Result<Self, Error> {
+        Ok(Self::from_section(
+            crate::elf::section(data, SECTION)?.to_vec(),
+        ))
+    }
+
+    /// uses the contents of the `smr_log` section
+    pub fn from_section(section: Vec<u8>) -> Self {
+        Self { section }
+    }
+
+    /// returns the target and the format string
+    fn string(&self, index: usize) -> Result<(&str, &str), Error> {
+        let mut parts = self
+            .section
+            .get(index..)
+            .ok_or(Error::InvalidIndex { index })?
+            .split(|&b| b == 0);
+        let mut next = || {
+            let part = parts.next().ok_or(Error::InvalidIndex { index })?;
+            std::str::from_utf8(part).map_err(|_| Error::InvalidString)
+        };
+        Ok((next()?, next()?))
+    }
+
+    pub fn decode(&self, record: &[u8]) -> Result<Record, Error> {
+        let mut reader = Reader { data: record };
+        let index = reader.varint().map_err(|Eof| Error::EmptyRecord)?;
+        let (target, format) = self.string(index as usize)?;
+
+        let mut message = String::new();
+        let truncated = self.format(format, &mut reader, &mut message)?.is_err();
+        Ok(Record {
+            target: target.to_owned(),
+            message,
+            truncated,
+        })
+    }
+
+    /// appends the formatted arguments to `out`, returns `Err(Eof)` if the record ended early
+    fn format(
+        &self,
+        format: &str,
+        reader: &mut Reader,
+        out: &mut String,
+    ) -> Result<Result<(), Eof>, Error> {
+        let mut rest = format;
+        while let Some(start) = rest.find(['{', '}']) {
+            out.push_str(&rest[..start]);
+            let escaped = &rest[start..start + 1];
+            if rest[start + 1..].starts_with(escaped) {
+                out.push_str(escaped);
+                rest = &rest[start + 2..];
+                continue;
+            }
+
+            let end = match rest[start..].find('}') {
+                Some(end) => start + end,
+                None => break,
+            };
+            let spec = Spec::parse(&rest[start + 1..end]);
+            rest = &rest[end + 1..];
+
+            if let Err(Eof) = self.argument(&spec, reader, out)? {
+                out.push('…');
+                return Ok(Err(Eof));
+            }
+        }
+        out.push_str(rest);
+
+        Ok(Ok(()))
+    }
+
+    fn argument(
+        &self,
+        spec: &Spec,
+        reader: &mut Reader,
+        out: &mut String,
+    ) -> Result<Result<(), Eof>, Error> {
+        let tag = match reader.u8() {
+            Ok(tag) => tag,
+            Err(Eof) => return Ok(Err(Eof)),
+        };
+        let value = match tag {
+            crate::tag::UNSIGNED => reader.varint().map(|value| spec.unsigned(value)),
+            crate::tag::SIGNED => reader
+                .varint()
+                .map(|value| spec.signed((value >> 1) as i64 ^ -((value & 1) as i64))),
+            crate::tag::BOOL => reader.u8().map(|value| spec.str(&(value != 0).to_string())),
+            crate::tag::CHAR => reader.varint().map(|value| {
+                let value = u32::try_from(value)
+                    .ok()
+                    .and_then(char::from_u32)
+                    .unwrap_or(char::REPLACEMENT_CHARACTER);
+                spec.str(&value.to_string())
+            }),
+            crate::tag::STR => match reader.varint().and_then(|len| reader.bytes(len as usize)) {
+                Ok(bytes) => Ok(spec.str(&String::from_utf8_lossy(bytes))),
+                // show what we got
+                Err(Eof) => {
+                    out.push_str(&String::from_utf8_lossy(reader.data));
+                    reader.data = &[];
+                    Err(Eof)
+                }
+            },
+            crate::tag::NESTED => {
+                let index = match reader.varint() {
+                    Ok(index) => index as usize,
+                    Err(Eof) => return Ok(Err(Eof)),
+                };
+                let (_, format) = self.string(index)?;
+                return self.format(format, reader, out);
+            }
+            tag => return Err(Error::InvalidTag { tag }),
+        };
+
+        Ok(value.map(|value| out.push_str(&value)))
+    }
+}
+
+#[cfg(all(test, target_os = "linux", feature = "deferred"))]
+mod tests {
+    fn table() -> super::Table {
+        let exe = std::fs::read(std::env::current_exe().unwrap()).unwrap();
+        super::Table::from_elf(&exe).unwrap()
+    }
+
+    #[test]
+    fn decode() {
+        let mut encoder = crate::Encoder::new(crate::intern!(
+            module_path!(),
+            "{} {:#06x} {:X} {} {:?} {{{}}} {}"
+        ));
+        crate::Format::format(&42u8, &mut encoder);
+        crate::Format::format(&0xABu16, &mut encoder);
+        crate::Format::format(&0xABu32, &mut encoder);
+        crate::Format::format(&-3i64, &mut encoder);
+        crate::Format::format("str", &mut encoder);
+        crate::Format::format(&true, &mut encoder);
+        crate::write!(&mut encoder, "nested {}", 'c');
+        assert!(!encoder.truncated());
+
+        assert_eq!(
+            table().decode(encoder.as_bytes()).unwrap(),
+            super::Record {
+                target: module_path!().to_owned(),
+                message: "42 0x00ab AB -3 \"str\" {true} nested c".to_owned(),
+                truncated: false,
+            }
+        );
+    }
+
+    #[test]
+    fn truncated() {
+        let mut encoder = crate::Encoder::new(crate::intern!(module_path!(), "a={} b={}"));
+        crate::Format::format("x".repeat(crate::MAX_RECORD_LEN).as_str(), &mut encoder);
+        crate::Format::format(&1u8, &mut encoder);
+        assert!(encoder.truncated());
+
+        let record = table().decode(encoder.as_bytes()).unwrap();
+        assert!(record.truncated);
+        assert!(record.message.starts_with("a=xxx"));
+        assert!(record.message.ends_with('…'));
+    }
+}
@@ -0,0 +1,132 @@
+//! just enough of an ELF parser to find a section by name
+
+use crate::decoder::Error;
+
+fn read<const N: usize>(data: &[u8], offset: usize) -> Result<[u8; N], Error> {
+    offset
+        .checked_add(N)
+        .and_then(|end| data.get(offset..end))
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or(Error::Elf("file is too short"))
+}
+
+fn u16(data: &[u8], offset: usize) -> Result<usize, Error> {
+    Ok(u16::from_le_bytes(read(data, offset)?).into())
+}
+
+fn u32(data: &[u8], offset: usize) -> Result<usize, Error> {
+    Ok(u32::from_le_bytes(read(data, offset)?) as usize)
+}
+
+fn u64(data: &[u8], offset: usize) -> Result<usize, Error> {
+    usize::try_from(u64::from_le_bytes(read(data, offset)?))
+        .map_err(|_| Error::Elf("offset is too big"))
+}
+
+struct SectionHeader {
+    name: usize,
+    offset: usize,
+    size: usize,
+}
+
+/// returns the contents of the section called `name`
+pub(crate) fn section<'a>(data: &'a [u8], name: &str) -> Result<&'a [u8], Error> {
+    if data.get(..4) != Some(b"\x7fELF") {
+        return Err(Error::Elf("not an ELF file"));
+    }
+    if data.get(5) != Some(&1) {
+        return Err(Error::Elf("only little endian is supported"));
+    }
+
+    let is_64 = match data.get(4) {
+        Some(1) => false,
+        Some(2) => true,
+        _ => return Err(Error::Elf("unknown class")),
+    };
+    let (shoff, shentsize, shnum, shstrndx) = if is_64 {
+        (
+            u64(data, 0x28)?,
+            u16(data, 0x3A)?,
+            u16(data, 0x3C)?,
+            u16(data, 0x3E)?,
+        )
+    } else {
+        (
+            u32(data, 0x20)?,
+            u16(data, 0x2E)?,
+            u16(data, 0x30)?,
+            u16(data, 0x32)?,
+        )
+    };
+
+    let header = |index: usize| -> Result<SectionHeader, Error> {
+        let len = if is_64 { 0x28 } else { 0x18 };
+        let offset = index
+            .checked_mul(shentsize)
+            .and_then(|offset| offset.checked_add(shoff))
+            .filter(|offset| offset.checked_add(len).is_some_and(|end| end <= data.len()))
+            .ok_or(Error::Elf("section header is out of bounds"))?;
+        Ok(if is_64 {
+            SectionHeader {
+                name: u32(data, offset)?,
+                offset: u64(data, offset + 0x18)?,
+                size: u64(data, offset + 0x20)?,
+            }
+        } else {
+            SectionHeader {
+                name: u32(data, offset)?,
+                offset: u32(data, offset + 0x10)?,
+                size: u32(data, offset + 0x14)?,
+            }
+        })
+    };
+    let contents = |header: &SectionHeader| {
+        header
+            .offset
+            .checked_add(header.size)
+            .and_then(|end| data.get(header.offset..end))
+            .ok_or(Error::Elf("section is out of bounds"))
+    };
+
+    let names = contents(&header(shstrndx)?)?;
+    for index in 0..shnum {
+        let header = header(index)?;
+        let section_name = names
+            .get(header.name..)
+            .and_then(|names| names.split(|&b| b == 0).next())
+            .ok_or(Error::Elf("section name is out of bounds"))?;
+        if section_name == name.as_bytes() {
+            return contents(&header);
+        }
+    }
+
+    Err(Error::NoSection)
+}
+
+#[cfg(test)]
+mod tests {
+    fn elf64(shoff: u64, shentsize: u16) -> Vec<u8> {
+        let mut data = vec![0; 0x40];
+        data[..6].copy_from_slice(b"\x7fELF\x02\x01");
+        data[0x28..0x30].copy_from_slice(&shoff.to_le_bytes());
+        data[0x3A..0x3C].copy_from_slice(&shentsize.to_le_bytes());
+        data[0x3C..0x3E].copy_from_slice(&1u16.to_le_bytes());
+        data
+    }
+
+    #[test]
+    fn header_out_of_bounds() {
+        for data in [
+            elf64(u64::MAX, 0x40),
+            elf64(u64::MAX - 0x10, 0x40),
+            elf64(0x30, 0x40),
+        ] {
+            assert!(matches!(
+                super::section(&data, "smr_log"),
+                Err(crate::decoder::Error::Elf(
+                    "section header is out of bounds"
+                ))
+            ));
+        }
+    }
+}
@@ -0,0 +1,241 @@
+//! writing records on the device
+
+use crate::{tag, MAX_RECORD_LEN};
+
+/// writes a record into a bounded buffer
+pub struct Encoder {
+    buf: [u8; MAX_RECORD_LEN],
+    len: usize,
+    truncated: bool,
+}
+
+impl Encoder {
+    /// starts a record for the interned string at `index`, see [crate::intern]
+    pub fn new(index: usize) -> Self {
+        let mut encoder = Self {
+            buf: [0; MAX_RECORD_LEN],
+            len: 0,
+            truncated: false,
+        };
+        encoder.varint(index as u64);
+        encoder
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buf[..self.len]
+    }
+
+    /// whether some of the arguments didn't fit
+    pub fn truncated(&self) -> bool {
+        self.truncated
+    }
+
+    fn extend(&mut self, bytes: &[u8]) {
+        let len = bytes.len().min(self.buf.len() - self.len);
+        if len < bytes.len() {
+            self.truncated = true;
+        }
+
+        self.buf[self.len..self.len + len].copy_from_slice(&bytes[..len]);
+        self.len += len;
+    }
+
+    /// LEB128
+    fn varint(&mut self, mut value: u64) {
+        loop {
+            let byte = (value & 0x7F) as u8;
+            value >>= 7;
+            if value == 0 {
+                self.extend(&[byte]);
+                return;
+            }
+            self.extend(&[byte | 0x80]);
+        }
+    }
+
+    pub fn unsigned(&mut self, value: u64) {
+        self.extend(&[tag::UNSIGNED]);
+        self.varint(value);
+    }
+
+    /// zigzag encoded, so small negative numbers stay small
+    pub fn signed(&mut self, value: i64) {
+        self.extend(&[tag::SIGNED]);
+        self.varint(((value << 1) ^ (value >> 63)) as u64);
+    }
+
+    pub fn bool(&mut self, value: bool) {
+        self.extend(&[tag::BOOL, value.into()]);
+    }
+
+    pub fn char(&mut self, value: char) {
+        self.extend(&[tag::CHAR]);
+        self.varint(value.into());
+    }
+
+    pub fn str(&mut self, value: &str) {
+        self.extend(&[tag::STR]);
+        self.varint(value.len() as u64);
+        self.extend(value.as_bytes());
+    }
+
+    /// starts an argument formatted using another interned string, see [crate::write]
+    pub fn nested(&mut self, index: usize) {
+        self.extend(&[tag::NESTED]);
+        self.varint(index as u64);
+    }
+}
+
+/// a value which can be sent in a record
+pub trait Format {
+    fn format(&self, encoder: &mut Encoder);
+}
+
+impl<T: Format + ?Sized> Format for &T {
+    fn format(&self, encoder: &mut Encoder) {
+        (**self).format(encoder)
+    }
+}
+
+macro_rules! format_int {
+    ($method:ident, $target:ty, $($ty:ty),+) => {
+        $(
+            impl Format for $ty {
+                fn format(&self, encoder: &mut Encoder) {
+                    encoder.$method(*self as $target)
+                }
+            }
+        )+
+    };
+}
+
+format_int!(unsigned, u64, u8, u16, u32, u64, usize);
+format_int!(signed, i64, i8, i16, i32, i64, isize);
+
+impl Format for bool {
+    fn format(&self, encoder: &mut Encoder) {
+        encoder.bool(*self)
+    }
+}
+
+impl Format for char {
+    fn format(&self, encoder: &mut Encoder) {
+        encoder.char(*self)
+    }
+}
+
+impl Format for str {
+    fn format(&self, encoder: &mut Encoder) {
+        encoder.str(self)
+    }
+}
+
+#[doc(hidden)]
+pub const fn to_array<const N: usize>(s: &str) -> [u8; N] {
+    let mut array = [0; N];
+    let mut i = 0;
+    while i < N {
+        array[i] = s.as_bytes()[i];
+        i += 1;
+    }
+    array
+}
+
+/// inlined, so only call sites reference `__start_smr_log`
+#[doc(hidden)]
+#[inline(always)]
+pub fn offset(interned: *const u8) -> usize {
+    extern "C" {
+        // defined by the linker for orphan sections, and by the linker script on the device
+        static __start_smr_log: u8;
+    }
+
+    // SAFETY: only the address is used
+    interned as usize - unsafe { core::ptr::addr_of!(__start_smr_log) } as usize
+}
+
+/// stores `module\0format\0` in the `smr_log` section and returns its offset
+///
+/// `fmt` is a literal or a macro expanding to one, like `stringify!`.
+#[macro_export]
+macro_rules! intern {
+    ($module:expr, $fmt:expr) => {{
+        const INTERNED: &str = concat!($module, "\0", $fmt, "\0");
+        #[link_section = "smr_log"]
+        static ARRAY: [u8; INTERNED.len()] = $crate::to_array(INTERNED);
+        $crate::offset(ARRAY.as_ptr())
+    }};
+}
+
+/// format an argument using another format string, e.g. in a [Format] impl
+///
+/// Like with [crate::intern], the format string can come from a macro like `stringify!`.
+#[macro_export]
+macro_rules! write {
+    ($encoder:expr, $fmt:expr $(, $arg:expr)* $(,)?) => {{
+        if false {
+            // lets the compiler check the arguments
+            let _ = format_args!($fmt $(, $arg)*);
+        }
+        let encoder: &mut $crate::Encoder = $encoder;
+        encoder.nested($crate::intern!("", $fmt));
+        $( $crate::Format::format(&$arg, encoder); )*
+    }};
+}
+
+/// receives the deferred records
+pub trait Logger: Sync {
+    fn enabled(&self, level: log::Level, target: &str) -> bool;
+    fn log(&self, level: log::Level, record: &[u8]);
+}
+
+struct NopLogger;
+
+impl Logger for NopLogger {
+    fn enabled(&self, _level: log::Level, _target: &str) -> bool {
+        false
+    }
+
+    fn log(&self, _level: log::Level, _record: &[u8]) {}
+}
+
+static STATE: core::sync::atomic::AtomicBool = core::sync::atomic::AtomicBool::new(false);
+static mut LOGGER: &dyn Logger = &NopLogger;
+
+/// returned by [set_logger_racy] if it was called before
+#[derive(Debug)]
+pub struct SetLoggerError;
+
+/// sets the logger for deferred records, like [log::set_logger_racy]
+///
+/// # Safety
+///
+/// must not be called while other threads log or set the logger
+pub unsafe fn set_logger_racy(logger: &'static dyn Logger) -> Result<(), SetLoggerError> {
+    if STATE.load(core::sync::atomic::Ordering::SeqCst) {
+        return Err(SetLoggerError);
+    }
+
+    LOGGER = logger;
+    STATE.store(true, core::sync::atomic::Ordering::SeqCst);
+    Ok(())
+}
+
+fn logger() -> &'static dyn Logger {
+    if STATE.load(core::sync::atomic::Ordering::SeqCst) {
+        // SAFETY: it's only written before `STATE` is set
+        unsafe { LOGGER }
+    } else {
+        &NopLogger
+    }
+}
+
+#[doc(hidden)]
+pub fn enabled(level: log::Level, target: &str) -> bool {
+    level <= log::max_level() && logger().enabled(level, target)
+}
+
+#[doc(hidden)]
+pub fn log_record(level: log::Level, encoder: &Encoder) {
+    logger().log(level, encoder.as_bytes());
+}
@@ -0,0 +1,107 @@
+//! deferred logging, which doesn't format messages on the device
+//!
+//! Every call site stores `module_path\0format\0` in the `smr_log` link section. A record only
+//! contains the offset of that string in the section, followed by the encoded arguments. The
+//! section doesn't have to be loaded onto the device, the [decoder] reads it from the ELF file.
+//!
+//! Without the `deferred` feature, the macros forward to `log`, so call sites don't have to
+//! care which one is used. `Format` and the encoding only exist with it, so impls of it go into
+//! [deferred!].
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(feature = "std")]
+pub mod decoder;
+#[cfg(feature = "std")]
+mod elf;
+
+#[doc(hidden)]
+pub use log;
+
+/// records are cut off after this many bytes
+pub const MAX_RECORD_LEN: usize = 64;
+
+/// the first byte of every argument
+#[cfg(any(feature = "std", feature = "deferred"))]
+mod tag {
+    pub const UNSIGNED: u8 = 0;
+    pub const SIGNED: u8 = 1;
+    pub const BOOL: u8 = 2;
+    pub const CHAR: u8 = 3;
+    pub const STR: u8 = 4;
+    /// an interned format string with its own arguments
+    pub const NESTED: u8 = 5;
+}
+
+#[cfg(feature = "deferred")]
+mod encoder;
+#[cfg(feature = "deferred")]
+pub use encoder::*;
+
+/// only emits the items with the `deferred` feature, e.g. for `Format` impls
+#[cfg(feature = "deferred")]
+#[macro_export]
+macro_rules! deferred {
+    ($($item:item)*) => {
+        $($item)*
+    };
+}
+
+/// only emits the items with the `deferred` feature, e.g. for `Format` impls
+#[cfg(not(feature = "deferred"))]
+#[macro_export]
+macro_rules! deferred {
+    ($($item:item)*) => {};
+}
+
+#[cfg(feature = "deferred")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __log {
+    ($level:expr, $fmt:literal $(, $arg:expr)* $(,)?) => {{
+        let level = $level;
+        if $crate::enabled(level, module_path!()) {
+            if false {
+                // lets the compiler check the arguments
+                let _ = format_args!($fmt $(, $arg)*);
+            }
+            let mut encoder = $crate::Encoder::new($crate::intern!(module_path!(), $fmt));
+            $( $crate::Format::format(&$arg, &mut encoder); )*
+            $crate::log_record(level, &encoder);
+        }
+    }};
+}
+
+#[cfg(not(feature = "deferred"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __log {
+    ($level:expr, $($arg:tt)+) => {
+        $crate::log::log!($level, $($arg)+)
+    };
+}
+
+#[macro_export]
+macro_rules! error {
+    ($($arg:tt)+) => ($crate::__log!($crate::log::Level::Error, $($arg)+))
+}
+
+#[macro_export]
+macro_rules! warn {
+    ($($arg:tt)+) => ($crate::__log!($crate::log::Level::Warn, $($arg)+))
+}
+
+#[macro_export]
+macro_rules! info {
+    ($($arg:tt)+) => ($crate::__log!($crate::log::Level::Info, $($arg)+))
+}
+
+#[macro_export]
+macro_rules! debug {
+    ($($arg:tt)+) => ($crate::__log!($crate::log::Level::Debug, $($arg)+))
+}
+
+#[macro_export]
+macro_rules! trace {
+    ($($arg:tt)+) => ($crate::__log!($crate::log::Level::Trace, $($arg)+))
+}
@@ -0,0 +1,241 @@
+//! a buffered reader without allocations
+
+use super::*;
+
+/// buffers the data of a reader, so it can be scanned and skipped in bulk
+///
+/// Every read of the wrapped reader can be expensive, e.g. when it's a call into C. This reads
+/// up to `N` bytes at once, [BufReader::peek] looks at the data without consuming it.
+#[derive(Debug)]
+pub struct BufReader<R, const N: usize> {
+    inner: R,
+    buf: [u8; N],
+    /// the buffered data is `buf[pos..filled]`
+    pos: usize,
+    filled: usize,
+}
+
+impl<R: AsyncRead + Unpin, const N: usize> BufReader<R, N> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            buf: [0; N],
+            pos: 0,
+            filled: 0,
+        }
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// returns the wrapped reader, buffered data is lost
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// the data which was read already, but not consumed yet
+    pub fn buffer(&self) -> &[u8] {
+        &self.buf[self.pos..self.filled]
+    }
+
+    /// mark `amt` bytes of the [buffer](BufReader::buffer) as read
+    pub fn consume(&mut self, amt: usize) {
+        self.pos = (self.pos + amt).min(self.filled);
+    }
+
+    /// read into the free space of the buffer, returns the number of new bytes
+    fn poll_fill(
+        &mut self,
+        cx: &mut core::task::Context<'_>,
+    ) -> core::task::Poll<Result<usize, Error>> {
+        if self.pos == self.filled {
+            self.pos = 0;
+            self.filled = 0;
+        }
+
+        let n = futures_util::ready!(
+            core::pin::Pin::new(&mut self.inner).poll_read(cx, &mut self.buf[self.filled..])
+        )?;
+        self.filled += n;
+        core::task::Poll::Ready(Ok(n))
+    }
+
+    /// returns the buffered data, reads from the inner reader if there's none
+    ///
+    /// An empty slice means EOF.
+    pub async fn fill_buf(&mut self) -> Result<&[u8], Error> {
+        if self.pos == self.filled {
+            core::future::poll_fn(|cx| self.poll_fill(cx)).await?;
+        }
+        Ok(self.buffer())
+    }
+
+    /// returns the next `n` bytes without consuming them
+    ///
+    /// Fails with [Error::UnexpectedEof] if there are less than `n` bytes left.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `n` is larger than the buffer size `N`.
+    pub async fn peek(&mut self, n: usize) -> Result<&[u8], Error> {
+        assert!(n <= N, "can't peek {} bytes with a buffer of {}", n, N);
+
+        while self.filled - self.pos < n {
+            // make room at the end
+            if N - self.pos < n {
+                self.buf.copy_within(self.pos..self.filled, 0);
+                self.filled -= self.pos;
+                self.pos = 0;
+            }
+
+            if core::future::poll_fn(|cx| self.poll_fill(cx)).await? == 0 {
+                return Err(Error::UnexpectedEof);
+            }
+        }
+
+        Ok(&self.buf[self.pos..self.pos + n])
+    }
+
+    /// read into `out` until `delimiter` was found, including it
+    ///
+    /// Stops early if `out` is full or at EOF, returns the number of written bytes.
+    pub async fn read_until(&mut self, delimiter: u8, out: &mut [u8]) -> Result<usize, Error> {
+        let mut written = 0;
+
+        while written < out.len() {
+            let available = self.fill_buf().await?;
+            if available.is_empty() {
+                break;
+            }
+
+            let available = &available[..available.len().min(out.len() - written)];
+            let (len, found) = match available.iter().position(|b| *b == delimiter) {
+                Some(index) => (index + 1, true),
+                None => (available.len(), false),
+            };
+
+            out[written..written + len].copy_from_slice(&available[..len]);
+            written += len;
+            self.consume(len);
+
+            if found {
+                break;
+            }
+        }
+
+        Ok(written)
+    }
+
+    /// discard `n` bytes
+    ///
+    /// Fails with [Error::UnexpectedEof] if there are less than `n` bytes left.
+    pub async fn skip(&mut self, mut n: usize) -> Result<(), Error> {
+        while n > 0 {
+            let available = self.fill_buf().await?.len();
+            if available == 0 {
+                return Err(Error::UnexpectedEof);
+            }
+
+            let len = available.min(n);
+            self.consume(len);
+            n -= len;
+        }
+
+        Ok(())
+    }
+}
+
+impl<R: AsyncRead + Unpin, const N: usize> AsyncRead for BufReader<R, N> {
+    fn poll_read(
+        mut self: core::pin::Pin<&mut Self>,
+        cx: &mut core::task::Context<'_>,
+        buf: &mut [u8],
+    ) -> core::task::Poll<Result<usize, Error>> {
+        let this = &mut *self;
+
+        // don't copy large reads twice
+        if this.pos == this.filled && buf.len() >= N {
+            return core::pin::Pin::new(&mut this.inner).poll_read(cx, buf);
+        }
+
+        if this.pos == this.filled {
+            futures_util::ready!(this.poll_fill(cx))?;
+        }
+
+        let available = this.buffer();
+        let len = available.len().min(buf.len());
+        buf[..len].copy_from_slice(&available[..len]);
+        this.consume(len);
+
+        core::task::Poll::Ready(Ok(len))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::AsyncReadExt as _;
+
+    /// returns the data in chunks of the given sizes
+    fn replay(chunks: &[&[u8]]) -> Vec<u8> {
+        let mut recorder = crate::replay::Recorder::new(Vec::new()).unwrap();
+        for chunk in chunks {
+            recorder
+                .record_with_delay(std::time::Duration::ZERO, chunk)
+                .unwrap();
+        }
+        recorder.into_inner()
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn peek() {
+        let recording = replay(&[&[1, 2], &[3], &[4, 5, 6, 7]]);
+        let mut reader =
+            super::BufReader::<_, 4>::new(crate::replay::Replay::new(&recording).unwrap());
+
+        assert_eq!(reader.peek(3).await.unwrap(), [1, 2, 3]);
+        assert_eq!(reader.read_u8().await.unwrap(), 1);
+
+        // moves the data to the start of the buffer
+        assert_eq!(reader.peek(4).await.unwrap(), [2, 3, 4, 5]);
+        reader.skip(3).await.unwrap();
+        assert_eq!(reader.fill_buf().await.unwrap(), [5]);
+        reader.consume(1);
+
+        assert!(matches!(
+            reader.peek(3).await,
+            Err(crate::Error::UnexpectedEof)
+        ));
+        assert_eq!(reader.read_u16().await.unwrap(), 0x0607);
+        assert_eq!(reader.fill_buf().await.unwrap(), []);
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn read_until() {
+        let recording = replay(&[&[1, 0x1b, 2, 3], &[4, 5], &[0x1b, 6]]);
+        let mut reader =
+            super::BufReader::<_, 8>::new(crate::replay::Replay::new(&recording).unwrap());
+
+        let mut out = [0u8; 8];
+        assert_eq!(reader.read_until(0x1b, &mut out).await.unwrap(), 2);
+        assert_eq!(out[..2], [1, 0x1b]);
+
+        // until `out` is full, then across reads of the inner reader
+        assert_eq!(reader.read_until(0x1b, &mut out[..3]).await.unwrap(), 3);
+        assert_eq!(out[..3], [2, 3, 4]);
+        assert_eq!(reader.read_until(0x1b, &mut out).await.unwrap(), 2);
+        assert_eq!(out[..2], [5, 0x1b]);
+
+        assert_eq!(reader.read_until(0x1b, &mut out).await.unwrap(), 1);
+        assert_eq!(out[0], 6);
+        assert_eq!(reader.read_until(0x1b, &mut out).await.unwrap(), 0);
+        assert!(matches!(
+            reader.skip(1).await,
+            Err(crate::Error::UnexpectedEof)
+        ));
+    }
+}
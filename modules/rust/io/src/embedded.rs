@@ -0,0 +1,319 @@
+//! adapters between this crate's traits and [embedded_io_async]
+//!
+//! [EmbeddedReader] and [EmbeddedWriter] are used for e.g. driving the SML parser with an
+//! embassy UART driver, [ToEmbedded] passes this crate's readers and writers to code using
+//! [embedded_io_async].
+//!
+//! The futures of [embedded_io_async] borrow the buffer, so the adapters read and write through
+//! an internal buffer of `N` bytes. They aren't [Unpin], use e.g. [core::pin::pin!].
+
+use super::*;
+
+impl embedded_io_async::Error for Error {
+    fn kind(&self) -> embedded_io_async::ErrorKind {
+        match self {
+            Error::Embedded { kind } => *kind,
+            Error::WriteZero => embedded_io_async::ErrorKind::WriteZero,
+            Error::TimedOut => embedded_io_async::ErrorKind::TimedOut,
+            Error::Framing => embedded_io_async::ErrorKind::InvalidData,
+            // there's no kind for lost data, HALs report their overruns as `Other` as well
+            Error::Overrun => embedded_io_async::ErrorKind::Other,
+            Error::Unimplemented => embedded_io_async::ErrorKind::Unsupported,
+            _ => embedded_io_async::ErrorKind::Other,
+        }
+    }
+}
+
+fn error(e: impl embedded_io_async::Error) -> Error {
+    match e.kind() {
+        embedded_io_async::ErrorKind::TimedOut => Error::TimedOut,
+        embedded_io_async::ErrorKind::WriteZero => Error::WriteZero,
+        embedded_io_async::ErrorKind::InvalidData => Error::Framing,
+        kind => Error::Embedded { kind },
+    }
+}
+
+/// use this crate's reader or writer as [embedded_io_async::Read] or [embedded_io_async::Write]
+pub struct ToEmbedded<T>(pub T);
+
+impl<T> embedded_io_async::ErrorType for ToEmbedded<T> {
+    type Error = Error;
+}
+
+impl<T: AsyncRead + Unpin> embedded_io_async::Read for ToEmbedded<T> {
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        self.0.read(buf).await
+    }
+}
+
+impl<T: AsyncWrite + Unpin> embedded_io_async::Write for ToEmbedded<T> {
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Error> {
+        self.0.write(buf).await
+    }
+
+    async fn flush(&mut self) -> Result<(), Error> {
+        self.0.flush().await
+    }
+}
+
+// The futures own the inner reader or writer and the buffer while they're running, and return
+// them when they're done.
+
+type ReadFuture<T: embedded_io_async::Read, const N: usize> =
+    impl core::future::Future<Output = (T, [u8; N], Result<usize, T::Error>)>;
+
+fn read<T: embedded_io_async::Read, const N: usize>(
+    mut inner: T,
+    mut data: [u8; N],
+) -> ReadFuture<T, N> {
+    async move {
+        let res = inner.read(&mut data).await;
+        (inner, data, res)
+    }
+}
+
+type WriteFuture<T: embedded_io_async::Write, const N: usize> =
+    impl core::future::Future<Output = (T, [u8; N], Result<usize, T::Error>)>;
+
+fn write<T: embedded_io_async::Write, const N: usize>(
+    mut inner: T,
+    data: [u8; N],
+    len: usize,
+) -> WriteFuture<T, N> {
+    async move {
+        let res = inner.write(&data[..len]).await;
+        (inner, data, res)
+    }
+}
+
+type FlushFuture<T: embedded_io_async::Write, const N: usize> =
+    impl core::future::Future<Output = (T, [u8; N], Result<(), T::Error>)>;
+
+fn flush<T: embedded_io_async::Write, const N: usize>(
+    mut inner: T,
+    data: [u8; N],
+) -> FlushFuture<T, N> {
+    async move {
+        let res = inner.flush().await;
+        (inner, data, res)
+    }
+}
+
+#[pin_project::pin_project(project = ReadStateProj, project_replace = ReadStateOwn)]
+enum ReadState<T: embedded_io_async::Read, const N: usize> {
+    Idle {
+        inner: T,
+        data: [u8; N],
+    },
+    Reading(#[pin] ReadFuture<T, N>),
+    /// only while switching between the other states
+    Empty,
+}
+
+/// use an [embedded_io_async::Read] reader with this crate's reader
+#[pin_project::pin_project]
+pub struct EmbeddedReader<T: embedded_io_async::Read, const N: usize> {
+    #[pin]
+    state: ReadState<T, N>,
+    /// the buffered data is `data[pos..filled]`
+    pos: usize,
+    filled: usize,
+}
+
+impl<T: embedded_io_async::Read, const N: usize> EmbeddedReader<T, N> {
+    pub fn new(inner: T) -> Self {
+        Self {
+            state: ReadState::Idle {
+                inner,
+                data: [0; N],
+            },
+            pos: 0,
+            filled: 0,
+        }
+    }
+}
+
+impl<T: embedded_io_async::Read, const N: usize> AsyncRead for EmbeddedReader<T, N> {
+    fn poll_read(
+        self: core::pin::Pin<&mut Self>,
+        cx: &mut core::task::Context<'_>,
+        buf: &mut [u8],
+    ) -> core::task::Poll<Result<usize, Error>> {
+        let mut this = self.project();
+        if buf.is_empty() {
+            return core::task::Poll::Ready(Ok(0));
+        }
+
+        loop {
+            match this.state.as_mut().project() {
+                ReadStateProj::Idle { data, .. } if *this.pos < *this.filled => {
+                    let available = &data[*this.pos..*this.filled];
+                    let len = available.len().min(buf.len());
+                    buf[..len].copy_from_slice(&available[..len]);
+                    *this.pos += len;
+                    return core::task::Poll::Ready(Ok(len));
+                }
+                ReadStateProj::Idle { .. } => {
+                    if let ReadStateOwn::Idle { inner, data } =
+                        this.state.as_mut().project_replace(ReadState::Empty)
+                    {
+                        this.state.set(ReadState::Reading(read(inner, data)));
+                    }
+                }
+                ReadStateProj::Reading(future) => {
+                    let (inner, data, res) =
+                        futures_util::ready!(core::future::Future::poll(future, cx));
+                    this.state.set(ReadState::Idle { inner, data });
+                    *this.pos = 0;
+                    *this.filled = res.map_err(error)?;
+
+                    if *this.filled == 0 {
+                        return core::task::Poll::Ready(Ok(0));
+                    }
+                }
+                ReadStateProj::Empty => unreachable!(),
+            }
+        }
+    }
+}
+
+#[pin_project::pin_project(project = WriteStateProj, project_replace = WriteStateOwn)]
+enum WriteState<T: embedded_io_async::Write, const N: usize> {
+    Idle {
+        inner: T,
+        data: [u8; N],
+    },
+    Writing(#[pin] WriteFuture<T, N>),
+    Flushing(#[pin] FlushFuture<T, N>),
+    /// only while switching between the other states
+    Empty,
+}
+
+/// use an [embedded_io_async::Write] writer with this crate's writer
+///
+/// Writes are copied into the buffer first. If a write is pending, the next call to
+/// [AsyncWrite::poll_write] continues it and ignores its own data, like when called with the same
+/// data again.
+#[pin_project::pin_project]
+pub struct EmbeddedWriter<T: embedded_io_async::Write, const N: usize> {
+    #[pin]
+    state: WriteState<T, N>,
+}
+
+impl<T: embedded_io_async::Write, const N: usize> EmbeddedWriter<T, N> {
+    pub fn new(inner: T) -> Self {
+        Self {
+            state: WriteState::Idle {
+                inner,
+                data: [0; N],
+            },
+        }
+    }
+}
+
+impl<T: embedded_io_async::Write, const N: usize> AsyncWrite for EmbeddedWriter<T, N> {
+    fn poll_write(
+        self: core::pin::Pin<&mut Self>,
+        cx: &mut core::task::Context<'_>,
+        buf: &[u8],
+    ) -> core::task::Poll<Result<usize, Error>> {
+        let mut state = self.project().state;
+        if buf.is_empty() {
+            return core::task::Poll::Ready(Ok(0));
+        }
+
+        loop {
+            match state.as_mut().project() {
+                WriteStateProj::Idle { .. } => {
+                    if let WriteStateOwn::Idle { inner, mut data } =
+                        state.as_mut().project_replace(WriteState::Empty)
+                    {
+                        let len = buf.len().min(N);
+                        data[..len].copy_from_slice(&buf[..len]);
+                        state.set(WriteState::Writing(write(inner, data, len)));
+                    }
+                }
+                WriteStateProj::Writing(future) => {
+                    let (inner, data, res) =
+                        futures_util::ready!(core::future::Future::poll(future, cx));
+                    state.set(WriteState::Idle { inner, data });
+                    return core::task::Poll::Ready(res.map_err(error));
+                }
+                WriteStateProj::Flushing(future) => {
+                    let (inner, data, res) =
+                        futures_util::ready!(core::future::Future::poll(future, cx));
+                    state.set(WriteState::Idle { inner, data });
+                    res.map_err(error)?;
+                }
+                WriteStateProj::Empty => unreachable!(),
+            }
+        }
+    }
+
+    fn poll_flush(
+        self: core::pin::Pin<&mut Self>,
+        cx: &mut core::task::Context<'_>,
+    ) -> core::task::Poll<Result<(), Error>> {
+        let mut state = self.project().state;
+
+        loop {
+            match state.as_mut().project() {
+                WriteStateProj::Idle { .. } => {
+                    if let WriteStateOwn::Idle { inner, data } =
+                        state.as_mut().project_replace(WriteState::Empty)
+                    {
+                        state.set(WriteState::Flushing(flush(inner, data)));
+                    }
+                }
+                // a pending write is finished first
+                WriteStateProj::Writing(future) => {
+                    let (inner, data, res) =
+                        futures_util::ready!(core::future::Future::poll(future, cx));
+                    state.set(WriteState::Idle { inner, data });
+                    res.map_err(error)?;
+                }
+                WriteStateProj::Flushing(future) => {
+                    let (inner, data, res) =
+                        futures_util::ready!(core::future::Future::poll(future, cx));
+                    state.set(WriteState::Idle { inner, data });
+                    return core::task::Poll::Ready(res.map_err(error));
+                }
+                WriteStateProj::Empty => unreachable!(),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{AsyncReadExt as _, AsyncWriteExt as _};
+
+    #[test_log::test(tokio::test)]
+    async fn read() {
+        let sampledata = [0xaau8, 0xbb, 0xcc, 0xdd, 0xee, 0xff];
+        let inner = super::ToEmbedded(crate::FuturesUtilReader(&sampledata[..]));
+        let mut reader = core::pin::pin!(super::EmbeddedReader::<_, 4>::new(inner));
+
+        assert_eq!(reader.read_u8().await.unwrap(), 0xaa);
+        assert_eq!(reader.read_u32().await.unwrap(), 0xbbccddee);
+        assert_eq!(reader.read_u8().await.unwrap(), 0xff);
+        assert!(matches!(
+            reader.read_u8().await,
+            Err(crate::Error::UnexpectedEof)
+        ));
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn write() {
+        let mut out = Vec::new();
+        {
+            let inner = super::ToEmbedded(crate::FuturesUtilWriter(&mut out));
+            let mut writer = core::pin::pin!(super::EmbeddedWriter::<_, 2>::new(inner));
+
+            writer.write_u32(0xaabbccdd).await.unwrap();
+            writer.write_all(&[1, 2, 3]).await.unwrap();
+            writer.flush().await.unwrap();
+        }
+        assert_eq!(out, [0xaa, 0xbb, 0xcc, 0xdd, 1, 2, 3]);
+    }
+}
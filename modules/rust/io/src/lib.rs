@@ -1,4 +1,17 @@
 #![cfg_attr(not(feature = "std"), no_std)]
+#![cfg_attr(
+    feature = "embedded-io-async",
+    feature(async_fn_in_trait, type_alias_impl_trait)
+)]
+
+mod buf_reader;
+#[cfg(feature = "embedded-io-async")]
+pub mod embedded;
+#[cfg(feature = "std")]
+pub mod replay;
+pub mod time;
+
+pub use buf_reader::BufReader;
 
 #[derive(Debug, snafu::Snafu)]
 pub enum Error {
@@ -6,6 +19,19 @@ pub enum Error {
     NativeUnsigned { ret: u32 },
     #[snafu(display("unexpected EOF"))]
     UnexpectedEof,
+    #[snafu(display("failed to write the whole buffer"))]
+    WriteZero,
+    #[snafu(display("timed out"))]
+    TimedOut,
+    /// data was lost because it wasn't read fast enough, e.g. from a UART FIFO
+    #[snafu(display("receive overrun"))]
+    Overrun,
+    /// a UART received an invalid stop bit, e.g. because of a wrong baud rate
+    #[snafu(display("framing error"))]
+    Framing,
+    /// a non-blocking reader has no data, it doesn't wake the task when there is
+    #[snafu(display("operation would block"))]
+    WouldBlock,
     #[snafu(display("unimplemented feature"))]
     Unimplemented,
     #[snafu(display("unknown error"))]
@@ -13,7 +39,14 @@ pub enum Error {
 
     #[cfg(feature = "std")]
     #[snafu(display("std io error: {inner}"))]
-    StdIoError { inner: std::io::Error },
+    StdIoError {
+        #[snafu(source)]
+        inner: std::io::Error,
+    },
+
+    #[cfg(feature = "embedded-io-async")]
+    #[snafu(display("embedded io error: {kind:?}"))]
+    Embedded { kind: embedded_io_async::ErrorKind },
 }
 
 pub trait AsyncRead {
@@ -61,6 +94,20 @@ impl<R: AsyncRead + ?Sized + Unpin> core::future::Future for Read<'_, R> {
     }
 }
 
+impl<P> AsyncRead for core::pin::Pin<P>
+where
+    P: core::ops::DerefMut + Unpin,
+    P::Target: AsyncRead,
+{
+    fn poll_read(
+        self: core::pin::Pin<&mut Self>,
+        cx: &mut core::task::Context<'_>,
+        buf: &mut [u8],
+    ) -> core::task::Poll<Result<usize, Error>> {
+        self.get_mut().as_mut().poll_read(cx, buf)
+    }
+}
+
 /// Future for the [`read_exact`](AsyncReadExt::read_exact) method.
 #[derive(Debug)]
 #[must_use = "futures do nothing unless you `.await` or poll them"]
@@ -101,10 +148,166 @@ impl<R: AsyncRead + ?Sized + Unpin> core::future::Future for ReadExact<'_, R> {
     }
 }
 
+pub trait AsyncWrite {
+    fn poll_write(
+        self: core::pin::Pin<&mut Self>,
+        cx: &mut core::task::Context<'_>,
+        buf: &[u8],
+    ) -> core::task::Poll<Result<usize, Error>>;
+
+    fn poll_flush(
+        self: core::pin::Pin<&mut Self>,
+        cx: &mut core::task::Context<'_>,
+    ) -> core::task::Poll<Result<(), Error>>;
+}
+
+impl<T: ?Sized + AsyncWrite + Unpin> AsyncWrite for &mut T {
+    fn poll_write(
+        mut self: core::pin::Pin<&mut Self>,
+        cx: &mut core::task::Context<'_>,
+        buf: &[u8],
+    ) -> core::task::Poll<Result<usize, Error>> {
+        core::pin::Pin::new(&mut **self).poll_write(cx, buf)
+    }
+
+    fn poll_flush(
+        mut self: core::pin::Pin<&mut Self>,
+        cx: &mut core::task::Context<'_>,
+    ) -> core::task::Poll<Result<(), Error>> {
+        core::pin::Pin::new(&mut **self).poll_flush(cx)
+    }
+}
+
+impl<P> AsyncWrite for core::pin::Pin<P>
+where
+    P: core::ops::DerefMut + Unpin,
+    P::Target: AsyncWrite,
+{
+    fn poll_write(
+        self: core::pin::Pin<&mut Self>,
+        cx: &mut core::task::Context<'_>,
+        buf: &[u8],
+    ) -> core::task::Poll<Result<usize, Error>> {
+        self.get_mut().as_mut().poll_write(cx, buf)
+    }
+
+    fn poll_flush(
+        self: core::pin::Pin<&mut Self>,
+        cx: &mut core::task::Context<'_>,
+    ) -> core::task::Poll<Result<(), Error>> {
+        self.get_mut().as_mut().poll_flush(cx)
+    }
+}
+
+/// Future for the [`write`](AsyncWriteExt::write) method.
+#[derive(Debug)]
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct Write<'a, W: ?Sized> {
+    writer: &'a mut W,
+    buf: &'a [u8],
+}
+
+impl<W: ?Sized + Unpin> Unpin for Write<'_, W> {}
+
+impl<'a, W: AsyncWrite + ?Sized + Unpin> Write<'a, W> {
+    fn new(writer: &'a mut W, buf: &'a [u8]) -> Self {
+        Self { writer, buf }
+    }
+}
+
+impl<W: AsyncWrite + ?Sized + Unpin> core::future::Future for Write<'_, W> {
+    type Output = Result<usize, Error>;
+
+    fn poll(
+        mut self: core::pin::Pin<&mut Self>,
+        cx: &mut core::task::Context<'_>,
+    ) -> core::task::Poll<Self::Output> {
+        let this = &mut *self;
+        core::pin::Pin::new(&mut this.writer).poll_write(cx, this.buf)
+    }
+}
+
+/// Future for the [`write_all`](AsyncWriteExt::write_all) method.
+#[derive(Debug)]
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct WriteAll<'a, W: ?Sized> {
+    writer: &'a mut W,
+    buf: &'a [u8],
+}
+
+impl<W: ?Sized + Unpin> Unpin for WriteAll<'_, W> {}
+
+impl<'a, W: AsyncWrite + ?Sized + Unpin> WriteAll<'a, W> {
+    fn new(writer: &'a mut W, buf: &'a [u8]) -> Self {
+        Self { writer, buf }
+    }
+}
+
+impl<W: AsyncWrite + ?Sized + Unpin> core::future::Future for WriteAll<'_, W> {
+    type Output = Result<(), Error>;
+
+    fn poll(
+        mut self: core::pin::Pin<&mut Self>,
+        cx: &mut core::task::Context<'_>,
+    ) -> core::task::Poll<Self::Output> {
+        let this = &mut *self;
+        while !this.buf.is_empty() {
+            let n = futures_util::ready!(
+                core::pin::Pin::new(&mut this.writer).poll_write(cx, this.buf)
+            )?;
+            if n == 0 {
+                return core::task::Poll::Ready(Err(Error::WriteZero));
+            }
+            this.buf = &this.buf[n..];
+        }
+        core::task::Poll::Ready(Ok(()))
+    }
+}
+
+/// Future for the [`flush`](AsyncWriteExt::flush) method.
+#[derive(Debug)]
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct Flush<'a, W: ?Sized> {
+    writer: &'a mut W,
+}
+
+impl<W: ?Sized + Unpin> Unpin for Flush<'_, W> {}
+
+impl<'a, W: AsyncWrite + ?Sized + Unpin> Flush<'a, W> {
+    fn new(writer: &'a mut W) -> Self {
+        Self { writer }
+    }
+}
+
+impl<W: AsyncWrite + ?Sized + Unpin> core::future::Future for Flush<'_, W> {
+    type Output = Result<(), Error>;
+
+    fn poll(
+        mut self: core::pin::Pin<&mut Self>,
+        cx: &mut core::task::Context<'_>,
+    ) -> core::task::Poll<Self::Output> {
+        core::pin::Pin::new(&mut *self.writer).poll_flush(cx)
+    }
+}
+
 #[cfg(feature = "std")]
 mod std_impl {
     use super::*;
 
+    impl From<std::io::Error> for Error {
+        fn from(inner: std::io::Error) -> Self {
+            match inner.kind() {
+                std::io::ErrorKind::UnexpectedEof => Error::UnexpectedEof,
+                std::io::ErrorKind::WriteZero => Error::WriteZero,
+                std::io::ErrorKind::TimedOut => Error::TimedOut,
+                std::io::ErrorKind::WouldBlock => Error::WouldBlock,
+                // std has no kinds for UART errors, corrupted input is reported as invalid data
+                std::io::ErrorKind::InvalidData => Error::Framing,
+                _ => Error::StdIoError { inner },
+            }
+        }
+    }
+
     /// use a [futures_util::io::AsyncRead] reader with this crate's reader
     pub struct FuturesUtilReader<R>(pub R);
 
@@ -116,7 +319,31 @@ mod std_impl {
         ) -> core::task::Poll<Result<usize, Error>> {
             core::pin::Pin::new(&mut self.0)
                 .poll_read(cx, buf)
-                .map_err(|inner| Error::StdIoError { inner })
+                .map_err(Error::from)
+        }
+    }
+
+    /// use a [futures_util::io::AsyncWrite] writer with this crate's writer
+    pub struct FuturesUtilWriter<W>(pub W);
+
+    impl<W: futures_util::io::AsyncWrite + Unpin> AsyncWrite for FuturesUtilWriter<W> {
+        fn poll_write(
+            mut self: core::pin::Pin<&mut Self>,
+            cx: &mut core::task::Context<'_>,
+            buf: &[u8],
+        ) -> core::task::Poll<Result<usize, Error>> {
+            core::pin::Pin::new(&mut self.0)
+                .poll_write(cx, buf)
+                .map_err(Error::from)
+        }
+
+        fn poll_flush(
+            mut self: core::pin::Pin<&mut Self>,
+            cx: &mut core::task::Context<'_>,
+        ) -> core::task::Poll<Result<(), Error>> {
+            core::pin::Pin::new(&mut self.0)
+                .poll_flush(cx)
+                .map_err(Error::from)
         }
     }
 }
@@ -124,6 +351,104 @@ mod std_impl {
 #[cfg(feature = "std")]
 pub use std_impl::*;
 
+#[cfg(feature = "tokio")]
+mod tokio_impl {
+    use super::*;
+
+    /// use a [tokio::io::AsyncRead] reader with this crate's reader
+    pub struct TokioReader<R>(pub R);
+
+    impl<R: tokio::io::AsyncRead + Unpin> AsyncRead for TokioReader<R> {
+        fn poll_read(
+            mut self: core::pin::Pin<&mut Self>,
+            cx: &mut core::task::Context<'_>,
+            buf: &mut [u8],
+        ) -> core::task::Poll<Result<usize, Error>> {
+            let mut buf = tokio::io::ReadBuf::new(buf);
+            futures_util::ready!(core::pin::Pin::new(&mut self.0).poll_read(cx, &mut buf))
+                .map_err(Error::from)?;
+            core::task::Poll::Ready(Ok(buf.filled().len()))
+        }
+    }
+
+    /// use a [tokio::io::AsyncWrite] writer with this crate's writer
+    pub struct TokioWriter<W>(pub W);
+
+    impl<W: tokio::io::AsyncWrite + Unpin> AsyncWrite for TokioWriter<W> {
+        fn poll_write(
+            mut self: core::pin::Pin<&mut Self>,
+            cx: &mut core::task::Context<'_>,
+            buf: &[u8],
+        ) -> core::task::Poll<Result<usize, Error>> {
+            core::pin::Pin::new(&mut self.0)
+                .poll_write(cx, buf)
+                .map_err(Error::from)
+        }
+
+        fn poll_flush(
+            mut self: core::pin::Pin<&mut Self>,
+            cx: &mut core::task::Context<'_>,
+        ) -> core::task::Poll<Result<(), Error>> {
+            core::pin::Pin::new(&mut self.0)
+                .poll_flush(cx)
+                .map_err(Error::from)
+        }
+    }
+}
+
+#[cfg(feature = "tokio")]
+pub use tokio_impl::*;
+
+#[cfg(feature = "std-io")]
+mod std_io_impl {
+    use super::*;
+
+    /// use a blocking [std::io::Read] reader with this crate's reader
+    ///
+    /// Reads never return pending, so this can be polled using [block_on] instead of a runtime.
+    pub struct StdReader<R>(pub R);
+
+    impl<R: std::io::Read + Unpin> AsyncRead for StdReader<R> {
+        fn poll_read(
+            mut self: core::pin::Pin<&mut Self>,
+            _cx: &mut core::task::Context<'_>,
+            buf: &mut [u8],
+        ) -> core::task::Poll<Result<usize, Error>> {
+            loop {
+                match self.0.read(buf) {
+                    Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                    res => return core::task::Poll::Ready(res.map_err(Error::from)),
+                }
+            }
+        }
+    }
+
+    struct Unparker(std::thread::Thread);
+
+    impl std::task::Wake for Unparker {
+        fn wake(self: std::sync::Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    /// run a future on the current thread, e.g. one reading from a [StdReader]
+    pub fn block_on<F: core::future::Future>(future: F) -> F::Output {
+        let waker = std::sync::Arc::new(Unparker(std::thread::current())).into();
+        let mut cx = core::task::Context::from_waker(&waker);
+        let mut future = core::pin::pin!(future);
+
+        loop {
+            if let core::task::Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return output;
+            }
+            std::thread::park();
+        }
+    }
+}
+
+#[cfg(feature = "std-io")]
+pub use std_io_impl::*;
+
 macro_rules! reader {
     ($name:ident, $ty:ty, $reader:ident) => {
         reader!($name, $ty, $reader, core::mem::size_of::<$ty>());
@@ -256,6 +581,135 @@ pub trait AsyncReadExt: AsyncRead {
 
 impl<R: AsyncRead + ?Sized> AsyncReadExt for R {}
 
+macro_rules! writer {
+    ($name:ident, $ty:ty, $writer:ident) => {
+        writer!($name, $ty, $writer, core::mem::size_of::<$ty>());
+    };
+    ($name:ident, $ty:ty, $writer:ident, $bytes:expr) => {
+        #[pin_project::pin_project]
+        #[must_use = "futures do nothing unless you `.await` or poll them"]
+        pub struct $name<W> {
+            #[pin]
+            dst: W,
+            buf: [u8; $bytes],
+            written: usize,
+        }
+
+        impl<W> $name<W> {
+            pub(crate) fn new(dst: W, num: $ty) -> Self {
+                $name {
+                    dst,
+                    buf: <$ty>::$writer(num),
+                    written: 0,
+                }
+            }
+        }
+
+        impl<W> core::future::Future for $name<W>
+        where
+            W: AsyncWrite,
+        {
+            type Output = Result<(), Error>;
+
+            fn poll(
+                self: core::pin::Pin<&mut Self>,
+                cx: &mut core::task::Context<'_>,
+            ) -> core::task::Poll<Self::Output> {
+                let mut me = self.project();
+
+                while *me.written < $bytes {
+                    *me.written += match me.dst.as_mut().poll_write(cx, &me.buf[*me.written..]) {
+                        core::task::Poll::Pending => return core::task::Poll::Pending,
+                        core::task::Poll::Ready(Err(e)) => return core::task::Poll::Ready(Err(e)),
+                        core::task::Poll::Ready(Ok(0)) => {
+                            return core::task::Poll::Ready(Err(Error::WriteZero));
+                        }
+                        core::task::Poll::Ready(Ok(n)) => n,
+                    };
+                }
+
+                core::task::Poll::Ready(Ok(()))
+            }
+        }
+    };
+}
+
+macro_rules! write_impl {
+    ($name:ident, $fut:ident, $ty:ty) => {
+        fn $name(&mut self, n: $ty) -> $fut<&mut Self>
+        where
+            Self: Unpin,
+        {
+            $fut::new(self, n)
+        }
+    };
+}
+
+writer!(WriteU8, u8, to_be_bytes);
+writer!(WriteU16, u16, to_be_bytes);
+writer!(WriteU32, u32, to_be_bytes);
+writer!(WriteU64, u64, to_be_bytes);
+
+writer!(WriteU8Le, u8, to_le_bytes);
+writer!(WriteU16Le, u16, to_le_bytes);
+writer!(WriteU32Le, u32, to_le_bytes);
+writer!(WriteU64Le, u64, to_le_bytes);
+
+writer!(WriteI8, i8, to_be_bytes);
+writer!(WriteI16, i16, to_be_bytes);
+writer!(WriteI32, i32, to_be_bytes);
+writer!(WriteI64, i64, to_be_bytes);
+
+writer!(WriteI8Le, i8, to_le_bytes);
+writer!(WriteI16Le, i16, to_le_bytes);
+writer!(WriteI32Le, i32, to_le_bytes);
+writer!(WriteI64Le, i64, to_le_bytes);
+
+pub trait AsyncWriteExt: AsyncWrite {
+    write_impl!(write_u8, WriteU8, u8);
+    write_impl!(write_u16, WriteU16, u16);
+    write_impl!(write_u32, WriteU32, u32);
+    write_impl!(write_u64, WriteU64, u64);
+
+    write_impl!(write_u8_le, WriteU8Le, u8);
+    write_impl!(write_u16_le, WriteU16Le, u16);
+    write_impl!(write_u32_le, WriteU32Le, u32);
+    write_impl!(write_u64_le, WriteU64Le, u64);
+
+    write_impl!(write_i8, WriteI8, i8);
+    write_impl!(write_i16, WriteI16, i16);
+    write_impl!(write_i32, WriteI32, i32);
+    write_impl!(write_i64, WriteI64, i64);
+
+    write_impl!(write_i8_le, WriteI8Le, i8);
+    write_impl!(write_i16_le, WriteI16Le, i16);
+    write_impl!(write_i32_le, WriteI32Le, i32);
+    write_impl!(write_i64_le, WriteI64Le, i64);
+
+    fn write<'a>(&'a mut self, buf: &'a [u8]) -> Write<'a, Self>
+    where
+        Self: Unpin,
+    {
+        Write::new(self, buf)
+    }
+
+    fn write_all<'a>(&'a mut self, buf: &'a [u8]) -> WriteAll<'a, Self>
+    where
+        Self: Unpin,
+    {
+        WriteAll::new(self, buf)
+    }
+
+    fn flush(&mut self) -> Flush<'_, Self>
+    where
+        Self: Unpin,
+    {
+        Flush::new(self)
+    }
+}
+
+impl<W: AsyncWrite + ?Sized> AsyncWriteExt for W {}
+
 #[cfg(test)]
 mod tests {
     use super::AsyncReadExt as _;
@@ -269,4 +723,77 @@ mod tests {
         let mut reader = super::FuturesUtilReader(&sampledata[..]);
         assert_eq!(reader.read_u32_le().await.unwrap(), 0xddccbbaa);
     }
+
+    #[test_log::test(tokio::test)]
+    async fn write() {
+        use super::AsyncWriteExt as _;
+
+        let mut writer = super::FuturesUtilWriter(Vec::new());
+        writer.write_u32(0xaabbccdd).await.unwrap();
+        writer.write_u16_le(0xeeff).await.unwrap();
+        writer.write_i8(-1).await.unwrap();
+        writer.write_all(&[1, 2]).await.unwrap();
+        writer.flush().await.unwrap();
+        assert_eq!(
+            writer.0,
+            [0xaa, 0xbb, 0xcc, 0xdd, 0xff, 0xee, 0xff, 0x01, 0x02]
+        );
+
+        // a full buffer
+        let mut buf = [0u8; 3];
+        let mut writer = super::FuturesUtilWriter(futures_util::io::Cursor::new(&mut buf[..]));
+        assert!(matches!(
+            writer.write_u32(0).await,
+            Err(super::Error::WriteZero)
+        ));
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test_log::test(tokio::test)]
+    async fn tokio() {
+        use super::AsyncWriteExt as _;
+
+        let sampledata = [0xaau8, 0xbb, 0xcc, 0xdd];
+        let mut reader = super::TokioReader(&sampledata[..]);
+        assert_eq!(reader.read_u32_le().await.unwrap(), 0xddccbbaa);
+
+        let mut writer = super::TokioWriter(Vec::new());
+        writer.write_u16(0xaabb).await.unwrap();
+        writer.flush().await.unwrap();
+        assert_eq!(writer.0, [0xaa, 0xbb]);
+    }
+
+    #[cfg(feature = "std-io")]
+    #[test]
+    fn std_io() {
+        let sampledata = [0xaau8, 0xbb, 0xcc, 0xdd];
+        let mut reader = super::StdReader(std::io::Cursor::new(&sampledata[..]));
+        assert_eq!(super::block_on(reader.read_u16()).unwrap(), 0xaabb);
+        assert_eq!(super::block_on(reader.read_u16_le()).unwrap(), 0xddcc);
+        assert!(matches!(
+            super::block_on(reader.read_u8()),
+            Err(super::Error::UnexpectedEof)
+        ));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn std_error_kinds() {
+        let error = |kind| super::Error::from(std::io::Error::from(kind));
+        assert!(matches!(
+            error(std::io::ErrorKind::TimedOut),
+            super::Error::TimedOut
+        ));
+        assert!(matches!(
+            error(std::io::ErrorKind::InvalidData),
+            super::Error::Framing
+        ));
+        assert!(matches!(
+            error(std::io::ErrorKind::Other),
+            super::Error::StdIoError { .. }
+        ));
+
+        assert_eq!(super::Error::Overrun.to_string(), "receive overrun");
+        assert_eq!(super::Error::Framing.to_string(), "framing error");
+    }
 }
@@ -0,0 +1,300 @@
+//! recording and replaying byte streams with their timing
+//!
+//! A recording starts with [MAGIC], followed by one record per chunk of data as it was
+//! returned by the reader:
+//!
+//! ```text
+//! u32 LE  microseconds since the previous record
+//! u16 LE  length
+//! [u8]    data
+//! ```
+//!
+//! [Replay] returns the same chunks again, so bugs depending on how a UART driver splits the
+//! data can be reproduced.
+
+use super::*;
+
+/// the first bytes of every recording, including the format version
+pub const MAGIC: [u8; 8] = *b"SMLREC\x00\x01";
+
+#[derive(Debug, snafu::Snafu)]
+pub enum FormatError {
+    #[snafu(display("not a recording"))]
+    BadMagic,
+    #[snafu(display("record at offset {offset} is truncated"))]
+    Truncated { offset: usize },
+}
+
+/// a chunk of data, see the [module documentation](self)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Record<'a> {
+    /// the time since the previous record
+    pub delay: std::time::Duration,
+    pub data: &'a [u8],
+}
+
+/// parse a whole recording
+pub fn parse(recording: &[u8]) -> Result<Vec<Record<'_>>, FormatError> {
+    let mut rest = recording
+        .strip_prefix(&MAGIC)
+        .ok_or(FormatError::BadMagic)?;
+    let mut records = Vec::new();
+
+    while !rest.is_empty() {
+        let offset = recording.len() - rest.len();
+        let truncated = FormatError::Truncated { offset };
+
+        if rest.len() < 6 {
+            return Err(truncated);
+        }
+        let delay = u32::from_le_bytes(rest[0..4].try_into().unwrap());
+        let len = usize::from(u16::from_le_bytes(rest[4..6].try_into().unwrap()));
+        let data = rest.get(6..6 + len).ok_or(truncated)?;
+
+        records.push(Record {
+            delay: std::time::Duration::from_micros(delay.into()),
+            data,
+        });
+        rest = &rest[6 + len..];
+    }
+
+    Ok(records)
+}
+
+/// writes a recording, measuring the time between calls to [Recorder::record]
+pub struct Recorder<W> {
+    out: W,
+    last: Option<std::time::Instant>,
+}
+
+impl<W: std::io::Write> Recorder<W> {
+    pub fn new(mut out: W) -> std::io::Result<Self> {
+        out.write_all(&MAGIC)?;
+        Ok(Self { out, last: None })
+    }
+
+    /// add data which was received just now
+    pub fn record(&mut self, data: &[u8]) -> std::io::Result<()> {
+        let now = std::time::Instant::now();
+        let delay = self
+            .last
+            .map_or(std::time::Duration::ZERO, |last| now - last);
+        self.last = Some(now);
+
+        self.record_with_delay(delay, data)
+    }
+
+    /// add data with a given delay, e.g. for creating recordings in tests
+    pub fn record_with_delay(
+        &mut self,
+        delay: std::time::Duration,
+        data: &[u8],
+    ) -> std::io::Result<()> {
+        let mut delay = u32::try_from(delay.as_micros()).unwrap_or(u32::MAX);
+
+        // longer chunks are split, only the first one is delayed
+        for chunk in data.chunks(u16::MAX.into()) {
+            self.out.write_all(&delay.to_le_bytes())?;
+            self.out.write_all(&(chunk.len() as u16).to_le_bytes())?;
+            self.out.write_all(chunk)?;
+            delay = 0;
+        }
+
+        Ok(())
+    }
+
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.out
+    }
+
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+}
+
+/// wakes the reader when the next record is due
+///
+/// A thread is used, so this works with any executor.
+struct Timer {
+    waker: std::sync::Arc<std::sync::Mutex<core::task::Waker>>,
+}
+
+impl Timer {
+    fn start(deadline: std::time::Instant, waker: &core::task::Waker) -> Self {
+        let waker = std::sync::Arc::new(std::sync::Mutex::new(waker.clone()));
+
+        let thread_waker = waker.clone();
+        std::thread::spawn(move || {
+            std::thread::sleep(deadline.saturating_duration_since(std::time::Instant::now()));
+            thread_waker.lock().unwrap().wake_by_ref();
+        });
+
+        Self { waker }
+    }
+
+    fn update(&self, waker: &core::task::Waker) {
+        let mut current = self.waker.lock().unwrap();
+        if !current.will_wake(waker) {
+            *current = waker.clone();
+        }
+    }
+}
+
+/// an [AsyncRead] which returns the records of a recording
+///
+/// Every read returns data of a single record only, just like the original reader did. At the
+/// end of the recording, reads return 0 bytes.
+pub struct Replay<'a> {
+    records: std::vec::IntoIter<Record<'a>>,
+    /// the rest of the current record
+    current: &'a [u8],
+    realtime: bool,
+    /// when the previous record was due, starts with the first read
+    last_deadline: Option<std::time::Instant>,
+    /// the next record, waiting for its deadline
+    waiting: Option<(Record<'a>, Timer)>,
+}
+
+impl<'a> Replay<'a> {
+    /// replay as fast as possible
+    pub fn new(recording: &'a [u8]) -> Result<Self, FormatError> {
+        Ok(Self {
+            records: parse(recording)?.into_iter(),
+            current: &[],
+            realtime: false,
+            last_deadline: None,
+            waiting: None,
+        })
+    }
+
+    /// replay with the recorded delays between the records
+    pub fn with_timing(recording: &'a [u8]) -> Result<Self, FormatError> {
+        Ok(Self {
+            realtime: true,
+            ..Self::new(recording)?
+        })
+    }
+
+    /// returns the next record as soon as it's due
+    fn poll_record(
+        &mut self,
+        cx: &mut core::task::Context<'_>,
+    ) -> core::task::Poll<Option<Record<'a>>> {
+        if !self.realtime {
+            return core::task::Poll::Ready(self.records.next());
+        }
+
+        let now = std::time::Instant::now();
+        let last_deadline = *self.last_deadline.get_or_insert(now);
+
+        if let Some((record, timer)) = &self.waiting {
+            let deadline = last_deadline + record.delay;
+            if now < deadline {
+                timer.update(cx.waker());
+                return core::task::Poll::Pending;
+            }
+
+            let record = *record;
+            self.waiting = None;
+            self.last_deadline = Some(deadline);
+            return core::task::Poll::Ready(Some(record));
+        }
+
+        let record = match self.records.next() {
+            None => return core::task::Poll::Ready(None),
+            Some(record) => record,
+        };
+
+        let deadline = last_deadline + record.delay;
+        if now < deadline {
+            self.waiting = Some((record, Timer::start(deadline, cx.waker())));
+            return core::task::Poll::Pending;
+        }
+
+        self.last_deadline = Some(deadline);
+        core::task::Poll::Ready(Some(record))
+    }
+}
+
+impl<'a> AsyncRead for Replay<'a> {
+    fn poll_read(
+        mut self: core::pin::Pin<&mut Self>,
+        cx: &mut core::task::Context<'_>,
+        buf: &mut [u8],
+    ) -> core::task::Poll<Result<usize, Error>> {
+        while self.current.is_empty() {
+            match futures_util::ready!(self.poll_record(cx)) {
+                None => return core::task::Poll::Ready(Ok(0)),
+                Some(record) => self.current = record.data,
+            }
+        }
+
+        let len = buf.len().min(self.current.len());
+        buf[..len].copy_from_slice(&self.current[..len]);
+        self.current = &self.current[len..];
+
+        core::task::Poll::Ready(Ok(len))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::AsyncReadExt as _;
+
+    fn recording(records: &[(u64, &[u8])]) -> Vec<u8> {
+        let mut recorder = super::Recorder::new(Vec::new()).unwrap();
+        for (delay, data) in records {
+            recorder
+                .record_with_delay(std::time::Duration::from_millis(*delay), data)
+                .unwrap();
+        }
+        recorder.into_inner()
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn chunks() {
+        let recording = recording(&[(0, &[1, 2, 3]), (5, &[4]), (5, &[5, 6])]);
+        let mut replay = super::Replay::new(&recording).unwrap();
+
+        let mut buf = [0u8; 8];
+        assert_eq!(replay.read(&mut buf).await.unwrap(), 3);
+        assert_eq!(buf[..3], [1, 2, 3]);
+
+        // smaller reads continue with the same record
+        assert_eq!(replay.read(&mut buf[..1]).await.unwrap(), 1);
+        assert_eq!(replay.read(&mut buf[..1]).await.unwrap(), 1);
+        assert_eq!(buf[0], 5);
+        assert_eq!(replay.read(&mut buf).await.unwrap(), 1);
+        assert_eq!(buf[0], 6);
+
+        assert_eq!(replay.read(&mut buf).await.unwrap(), 0);
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn timing() {
+        let recording = recording(&[(0, &[1]), (30, &[2]), (30, &[3])]);
+        let mut replay = super::Replay::with_timing(&recording).unwrap();
+
+        let start = std::time::Instant::now();
+        let mut buf = [0u8; 3];
+        replay.read_exact(&mut buf).await.unwrap();
+
+        assert_eq!(buf, [1, 2, 3]);
+        assert!(start.elapsed() >= std::time::Duration::from_millis(60));
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(matches!(
+            super::parse(b"something else"),
+            Err(super::FormatError::BadMagic)
+        ));
+
+        let mut recording = recording(&[(0, &[1, 2, 3])]);
+        recording.pop();
+        assert!(matches!(
+            super::parse(&recording),
+            Err(super::FormatError::Truncated { offset: 8 })
+        ));
+    }
+}
@@ -0,0 +1,199 @@
+//! time sources and read timeouts
+
+use super::*;
+
+/// a monotonic clock, e.g. Zephyr's `k_uptime_get`
+pub trait TimeSource {
+    /// milliseconds since an arbitrary start
+    fn now_ms(&self) -> u64;
+}
+
+impl<T: TimeSource + ?Sized> TimeSource for &T {
+    fn now_ms(&self) -> u64 {
+        (**self).now_ms()
+    }
+}
+
+/// a [TimeSource] which is advanced manually, e.g. for tests
+#[derive(Debug, Default)]
+pub struct ManualTime(core::cell::Cell<u64>);
+
+impl ManualTime {
+    pub fn set(&self, now_ms: u64) {
+        self.0.set(now_ms);
+    }
+
+    pub fn advance(&self, ms: u64) {
+        self.0.set(self.0.get() + ms);
+    }
+}
+
+impl TimeSource for ManualTime {
+    fn now_ms(&self) -> u64 {
+        self.0.get()
+    }
+}
+
+/// a [TimeSource] measuring the time since its creation
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy)]
+pub struct StdTimeSource(std::time::Instant);
+
+#[cfg(feature = "std")]
+impl StdTimeSource {
+    pub fn new() -> Self {
+        Self(std::time::Instant::now())
+    }
+}
+
+#[cfg(feature = "std")]
+impl Default for StdTimeSource {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "std")]
+impl TimeSource for StdTimeSource {
+    fn now_ms(&self) -> u64 {
+        self.0.elapsed().as_millis().try_into().unwrap_or(u64::MAX)
+    }
+}
+
+/// fails reads with [Error::TimedOut] when no data was received in time
+///
+/// The deadline is only checked when the reader is polled, it doesn't wake the task by itself.
+/// The task has to be polled periodically, e.g. by the C glue calling `sml_poll` on a timer.
+pub struct TimeoutReader<R, T> {
+    inner: R,
+    time: T,
+    deadline: Option<u64>,
+    /// moves the deadline after every successful read
+    idle_timeout: Option<u64>,
+}
+
+impl<R, T: TimeSource> TimeoutReader<R, T> {
+    /// a reader without a timeout
+    pub fn new(inner: R, time: T) -> Self {
+        Self {
+            inner,
+            time,
+            deadline: None,
+            idle_timeout: None,
+        }
+    }
+
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    pub fn time(&self) -> &T {
+        &self.time
+    }
+
+    /// time out at a fixed point in time of the [TimeSource], or never
+    pub fn set_deadline(&mut self, deadline: Option<u64>) {
+        self.deadline = deadline;
+        self.idle_timeout = None;
+    }
+
+    /// time out if there's no data for `timeout_ms`, starting now
+    pub fn set_idle_timeout(&mut self, timeout_ms: u64) {
+        self.deadline = Some(self.time.now_ms().saturating_add(timeout_ms));
+        self.idle_timeout = Some(timeout_ms);
+    }
+}
+
+impl<R: AsyncRead + Unpin, T: TimeSource + Unpin> AsyncRead for TimeoutReader<R, T> {
+    fn poll_read(
+        mut self: core::pin::Pin<&mut Self>,
+        cx: &mut core::task::Context<'_>,
+        buf: &mut [u8],
+    ) -> core::task::Poll<Result<usize, Error>> {
+        let this = &mut *self;
+
+        match core::pin::Pin::new(&mut this.inner).poll_read(cx, buf) {
+            core::task::Poll::Ready(res) => {
+                if let (Ok(_), Some(timeout)) = (&res, this.idle_timeout) {
+                    this.deadline = Some(this.time.now_ms().saturating_add(timeout));
+                }
+                core::task::Poll::Ready(res)
+            }
+            core::task::Poll::Pending => match this.deadline {
+                Some(deadline) if this.time.now_ms() >= deadline => {
+                    core::task::Poll::Ready(Err(Error::TimedOut))
+                }
+                _ => core::task::Poll::Pending,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::AsyncRead as _;
+
+    /// returns the data of `chunks`, with a pending read after each
+    struct PendingReader<'a> {
+        chunks: std::collections::VecDeque<&'a [u8]>,
+        pending: bool,
+    }
+
+    impl crate::AsyncRead for PendingReader<'_> {
+        fn poll_read(
+            mut self: core::pin::Pin<&mut Self>,
+            _cx: &mut core::task::Context<'_>,
+            buf: &mut [u8],
+        ) -> core::task::Poll<Result<usize, crate::Error>> {
+            if core::mem::replace(&mut self.pending, false) {
+                return core::task::Poll::Pending;
+            }
+            self.pending = true;
+
+            match self.chunks.pop_front() {
+                None => core::task::Poll::Pending,
+                Some(chunk) => {
+                    buf[..chunk.len()].copy_from_slice(chunk);
+                    core::task::Poll::Ready(Ok(chunk.len()))
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn timeout() {
+        let time = super::ManualTime::default();
+        let inner = PendingReader {
+            chunks: [&[1u8][..], &[2]].into(),
+            pending: false,
+        };
+        let mut reader = super::TimeoutReader::new(inner, &time);
+        reader.set_idle_timeout(100);
+
+        let waker = futures_util::task::noop_waker();
+        let mut cx = core::task::Context::from_waker(&waker);
+        let mut poll = |reader: &mut super::TimeoutReader<_, _>| {
+            let mut buf = [0u8; 1];
+            core::pin::Pin::new(reader).poll_read(&mut cx, &mut buf)
+        };
+
+        assert!(matches!(poll(&mut reader), core::task::Poll::Ready(Ok(1))));
+
+        // reads move the deadline
+        time.set(90);
+        assert!(poll(&mut reader).is_pending());
+        assert!(matches!(poll(&mut reader), core::task::Poll::Ready(Ok(1))));
+        time.set(180);
+        assert!(poll(&mut reader).is_pending());
+
+        time.set(190);
+        assert!(matches!(
+            poll(&mut reader),
+            core::task::Poll::Ready(Err(crate::Error::TimedOut))
+        ));
+
+        reader.set_deadline(None);
+        time.set(1000);
+        assert!(poll(&mut reader).is_pending());
+    }
+}
@@ -7,43 +7,148 @@ use core::future::Future as _;
 pub type ReadFn =
     extern "C" fn(buf: *mut core::ffi::c_void, max_length: usize, out_length: *mut usize) -> u32;
 
-/// asynchronously write data to a C callback
-pub struct Reader {
-    function: ReadFn,
+/// errors a read callback can return, so they're reported as what they are
+///
+/// Any other non-zero value is reported as a native error with that number.
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReadError {
+    /// received data was lost, e.g. because the UART FIFO overflowed
+    Overrun = 0x100,
+    /// the UART received an invalid stop bit, e.g. because of a wrong baud rate
+    Framing = 0x101,
+}
+
+/// the error for the non-zero return value of a read callback
+fn read_error(ret: u32) -> io::Error {
+    match ret {
+        ret if ret == ReadError::Overrun as u32 => io::Error::Overrun,
+        ret if ret == ReadError::Framing as u32 => io::Error::Framing,
+        ret => io::Error::NativeUnsigned { ret },
+    }
+}
+
+/// the data passed to [sml_feed], it's only valid during that call
+pub struct Input {
+    data: core::cell::Cell<*const u8>,
+    len: core::cell::Cell<usize>,
+}
+
+impl Input {
+    fn new() -> Self {
+        Self {
+            data: core::cell::Cell::new(core::ptr::null()),
+            len: core::cell::Cell::new(0),
+        }
+    }
+}
+
+/// asynchronously read data from a C callback or from the data pushed with [sml_feed]
+#[derive(Clone, Copy)]
+pub enum Reader {
+    Callback(ReadFn),
+    /// points to the [Input] stored in the same [Context]
+    Feed(*const Input),
 }
 
 impl io::AsyncRead for Reader {
     fn poll_read(
         self: core::pin::Pin<&mut Self>,
-        _cx: &mut core::task::Context<'_>,
+        cx: &mut core::task::Context<'_>,
         buf: &mut [u8],
     ) -> core::task::Poll<Result<usize, io::Error>> {
-        let mut length = 0;
+        match *self {
+            Self::Callback(function) => {
+                let mut length = 0;
 
-        let ret = (self.function)(
-            buf.as_mut_ptr() as *mut core::ffi::c_void,
-            buf.len(),
-            &mut length,
-        );
-        if ret != 0 {
-            return core::task::Poll::Ready(Err(io::Error::NativeUnsigned { ret }));
+                let ret = function(
+                    buf.as_mut_ptr() as *mut core::ffi::c_void,
+                    buf.len(),
+                    &mut length,
+                );
+                if ret != 0 {
+                    return core::task::Poll::Ready(Err(read_error(ret)));
+                }
+
+                if length > 0 {
+                    core::task::Poll::Ready(Ok(length))
+                } else {
+                    // tells C that we need data, see `sml_init`
+                    cx.waker().wake_by_ref();
+                    core::task::Poll::Pending
+                }
+            }
+            Self::Feed(input) => {
+                // SAFETY: the input is stored next to the future, so it outlives us
+                let input = unsafe { &*input };
+                let available = input.len.get();
+                let length = available.min(buf.len());
+                if length == 0 {
+                    // there's nothing to wake, C calls `sml_feed` with the next data
+                    return core::task::Poll::Pending;
+                }
+
+                // SAFETY: `sml_feed` only sets a non-zero length for the duration of the call
+                let data = unsafe { core::slice::from_raw_parts(input.data.get(), available) };
+                buf[..length].copy_from_slice(&data[..length]);
+                input.data.set(data[length..].as_ptr());
+                input.len.set(available - length);
+
+                core::task::Poll::Ready(Ok(length))
+            }
         }
+    }
+}
+
+pub type UptimeFn = extern "C" fn() -> u64;
+
+/// the uptime in milliseconds from a C callback, without one the time stands still
+#[derive(Clone, Copy)]
+pub struct Uptime {
+    function: Option<UptimeFn>,
+}
 
-        if length > 0 {
-            core::task::Poll::Ready(Ok(length))
-        } else {
-            core::task::Poll::Pending
+impl io::time::TimeSource for Uptime {
+    fn now_ms(&self) -> u64 {
+        self.function.map_or(0, |function| function())
+    }
+}
+
+/// everything needed to start the task again after it failed
+#[derive(Clone, Copy)]
+struct Task {
+    reader: Reader,
+    uptime: Uptime,
+    timeouts: sml::Timeouts,
+    message_function: crate::MessageFn,
+    event_function: Option<crate::EventFn>,
+    user_context: *mut core::ffi::c_void,
+}
+
+impl Task {
+    fn event(&self, event: crate::Event) {
+        if let Some(function) = self.event_function {
+            function(self.user_context, event);
         }
     }
 }
 
+/// the number of bytes requested from the C reader at once
+const READ_BUFFER_SIZE: usize = 64;
+
 type SmlTaskFuture = impl core::future::Future<Output = Result<(), sml::Error>>;
-fn sml_task_sized(
-    mut reader: Reader,
-    mut message_callback: crate::MessageCallback,
-) -> SmlTaskFuture {
+fn sml_task_sized(task: Task) -> SmlTaskFuture {
     async move {
-        sml::task(&mut reader, &mut message_callback).await?;
+        let mut reader = io::BufReader::<_, READ_BUFFER_SIZE>::new(io::time::TimeoutReader::new(
+            task.reader,
+            task.uptime,
+        ));
+        let mut message_callback = crate::MessageCallback::new(
+            task.message_function,
+            task.event_function,
+            task.user_context,
+        );
+        sml::task_with_timeouts(&mut reader, task.timeouts, &mut message_callback).await?;
 
         Ok(())
     }
@@ -51,63 +156,201 @@ fn sml_task_sized(
 
 /// the main context pointer passed to us by C
 pub struct Context {
-    waker: core::task::Waker,
+    task: Task,
+    /// the waker is created when polling, it points into the context
+    wake: Option<crate::waker::CWaker>,
+    /// the data of the current [sml_feed] call, only used without a read callback
+    input: Input,
+    /// how often the task failed since it last waited for data
+    failures: u32,
+    /// limits the logs while this context is polled, see [sml_set_log_level]
+    #[cfg(feature = "logger")]
+    log_level: log::LevelFilter,
     f: SmlTaskFuture,
 }
 
 impl Context {
-    fn new(reader: Reader, message_callback: crate::MessageCallback) -> Self {
+    fn new(task: Task, wake: Option<crate::waker::CWaker>) -> Self {
         Self {
-            waker: crate::waker::stub(),
-            f: sml_task_sized(reader, message_callback),
+            task,
+            wake,
+            input: Input::new(),
+            failures: 0,
+            #[cfg(feature = "logger")]
+            log_level: log::LevelFilter::Trace,
+            f: sml_task_sized(task),
         }
     }
 }
 
+/// the number of failures in a row after which [sml_poll] reports an error instead of asking to
+/// be called again right away
+const MAX_FAILURES: u32 = 3;
+
+/// incremented on every incompatible change of the C API, checked by [sml_init]
+pub const API_VERSION: u32 = 2;
+
+/// the size of [ContextStorage], at least the size of the context on that architecture
+#[cfg(target_pointer_width = "64")]
+pub const CONTEXT_SIZE: usize = 1536;
+/// the size of [ContextStorage], at least the size of the context on that architecture
+#[cfg(not(target_pointer_width = "64"))]
+pub const CONTEXT_SIZE: usize = 1216;
+/// the alignment of [ContextStorage]
+pub const CONTEXT_ALIGN: usize = 8;
+
+/// the memory for a [Context], allocated by C
+///
+/// This is exported to C, so its size is known at compile time there.
+#[repr(C, align(8))]
+pub struct ContextStorage {
+    buf: [u8; CONTEXT_SIZE],
+}
+
+// fail to compile instead of overflowing the C allocation
+const _: () = assert!(core::mem::size_of::<Context>() <= CONTEXT_SIZE);
+const _: () = assert!(core::mem::align_of::<Context>() <= CONTEXT_ALIGN);
+const _: () = assert!(core::mem::align_of::<ContextStorage>() == CONTEXT_ALIGN);
+
 // workaround for cbindgen limitations: https://github.com/eqrion/cbindgen/issues/326#issuecomment-584288686
 pub type ReadFnOpt = Option<
     extern "C" fn(buf: *mut core::ffi::c_void, max_length: usize, out_length: *mut usize) -> u32,
 >;
 pub type MessageFnOpt =
     Option<extern "C" fn(user: *mut core::ffi::c_void, *const crate::CallbackData)>;
+pub type UptimeFnOpt = Option<extern "C" fn() -> u64>;
+pub type EventFnOpt = Option<extern "C" fn(user: *mut core::ffi::c_void, event: crate::Event)>;
+pub type WakeFnOpt = Option<extern "C" fn(user: *mut core::ffi::c_void)>;
 
 /// initialize SML reader
 ///
-/// none of the arguments must be NULL.
+/// All state lives in the context, so multiple meters can be read using one context each. Only
+/// the logger and its levels are shared, see `smr_init_logger`. Use [sml_deinit] to drop the
+/// context again. With the logger, all contexts have to be polled and fed from one thread or
+/// work queue, see [sml_set_log_level].
+///
+/// `uptime_callback` returns the uptime in milliseconds, e.g. using `k_uptime_get`. Without it,
+/// no timeouts are detected. If it's given, [sml_poll] has to be called periodically and not
+/// only when data was received, so timeouts are reported to `event_callback`. They can be
+/// changed using [sml_set_timeouts].
+///
+/// `wake_callback` is called with `wake_user` from within [sml_poll] when the read callback
+/// returned no data. It only means that [sml_poll] has to be called again once the read callback
+/// can return data, never that it has to be called right away. Without it, [sml_poll] has to be
+/// called whenever data was received. E.g. with a `k_poll` signal, which the UART ISR raises
+/// after putting data into the ring buffer the read callback takes it from:
+///
+/// ```c
+/// static atomic_t sml_waiting;
+/// static struct k_poll_signal sml_signal = K_POLL_SIGNAL_INITIALIZER(sml_signal);
+///
+/// static void sml_wake_cb(void *user)
+/// {
+///     // called while polling, so it must not raise the signal itself
+///     atomic_set(&sml_waiting, 1);
+/// }
+///
+/// static void uart_isr_rx_ready(void)
+/// {
+///     // ring_buf_put(...)
+///     if (atomic_cas(&sml_waiting, 1, 0)) {
+///         k_poll_signal_raise(&sml_signal, 0);
+///     }
+/// }
+///
+/// static void sml_thread(void)
+/// {
+///     struct k_poll_event event = K_POLL_EVENT_INITIALIZER(
+///         K_POLL_TYPE_SIGNAL, K_POLL_MODE_NOTIFY_ONLY, &sml_signal);
+///
+///     for (;;) {
+///         uint32_t ret;
+///         do {
+///             ret = sml_poll(&ctx);
+///         } while (ret == 5);
+///
+///         // wake up periodically for the timeouts
+///         k_poll(&event, 1, K_MSEC(100));
+///         event.state = K_POLL_STATE_NOT_READY;
+///         k_poll_signal_reset(&sml_signal);
+///     }
+/// }
+/// ```
+///
+/// `read_callback` returns 0 on success, even if there's no data yet. Errors of the UART are
+/// returned as a `smr_read_error`, any other non-zero value is logged as a native error. Either
+/// way, reading starts over with the next frame, see [sml_poll].
+///
+/// Without `read_callback`, the data has to be pushed using [sml_feed] instead. The wake
+/// callback isn't needed then.
+///
+/// `api_version` has to be `smr_API_VERSION`, so a header that doesn't match the library is
+/// detected.
+///
+/// `read_callback`, `uptime_callback`, `event_callback` and `wake_callback` may be NULL, none
+/// of the other arguments must be NULL.
 #[no_mangle]
+#[allow(clippy::too_many_arguments)]
 pub extern "C" fn sml_init(
-    out_context: *mut Context,
+    api_version: u32,
+    out_context: *mut ContextStorage,
     context_len: usize,
     user_context: *mut core::ffi::c_void,
     read_callback: ReadFnOpt,
     message_callback: MessageFnOpt,
+    uptime_callback: UptimeFnOpt,
+    event_callback: EventFnOpt,
+    wake_callback: WakeFnOpt,
+    wake_user: *mut core::ffi::c_void,
 ) -> u32 {
+    if api_version != API_VERSION {
+        dlog::error!(
+            "API version mismatch, header: `{}`, library: `{}`",
+            api_version,
+            API_VERSION
+        );
+        return 3;
+    }
     if out_context.is_null() {
-        log::error!("out context is null");
+        dlog::error!("out context is null");
         return 1;
     }
+    if out_context as usize % CONTEXT_ALIGN != 0 {
+        dlog::error!("out context isn't aligned to `{}` bytes", CONTEXT_ALIGN);
+        return 2;
+    }
+    let out_context = out_context.cast::<Context>();
     let reader = match read_callback {
-        Some(function) => Reader { function },
-        None => {
-            log::error!("read callback is null");
-            return 2;
-        }
+        Some(function) => Reader::Callback(function),
+        // SAFETY: only computes the address, the input gets initialized together with the context
+        None => Reader::Feed(unsafe { core::ptr::addr_of!((*out_context).input) }),
     };
-    let message_callback = match message_callback {
-        Some(function) => crate::MessageCallback::new(function, user_context),
+    let message_function = match message_callback {
+        Some(function) => function,
         None => {
-            log::error!("message callback is null");
+            dlog::error!("message callback is null");
             return 2;
         }
     };
 
     let min_size = core::mem::size_of::<Context>();
     if context_len < min_size {
-        log::error!("callback is too small, min size: `{}`", min_size);
+        dlog::error!("callback is too small, min size: `{}`", min_size);
         return 2;
     }
 
-    let context = Context::new(reader, message_callback);
+    let task = Task {
+        reader,
+        uptime: Uptime {
+            function: uptime_callback,
+        },
+        timeouts: sml::Timeouts::default(),
+        message_function,
+        event_function: event_callback,
+        user_context,
+    };
+    let wake = wake_callback.map(|function| crate::waker::CWaker::new(function, wake_user));
+    let context = Context::new(task, wake);
 
     // SAFETY: we verified the validity of the pointer and C also doens't ever move data
     unsafe { out_context.write(context) }
@@ -118,22 +361,233 @@ pub extern "C" fn sml_init(
 /// process pending data
 ///
 /// must be called as soon as the provided read callback can return data again.
+///
+/// If reading fails, the error is logged, `smr_event_restarted` is sent to the event callback
+/// and reading starts over with the next frame. This returns 5 then, which means that this has
+/// to be called again right away. The wake callback isn't called for that.
+///
+/// Returns 4 if the task failed 3 times in a row without waiting for data, e.g. because the read
+/// callback keeps failing. It's restarted as well, but C decides when to try again by calling
+/// this or [sml_reset].
 #[no_mangle]
-pub extern "C" fn sml_poll(mut context: core::ptr::NonNull<Context>) -> u32 {
+pub extern "C" fn sml_poll(context: core::ptr::NonNull<ContextStorage>) -> u32 {
+    let context = context.cast::<Context>();
+    match poll(context) {
+        Polled::Pending => 0,
+        Polled::Failed => 4,
+        Polled::Restarted => 5,
+    }
+}
+
+/// decode received data
+///
+/// Only usable if no read callback was passed to [sml_init]. The data is decoded before this
+/// returns, so the message callback is called from here and the data doesn't have to stay valid
+/// afterwards. [sml_poll] still has to be called periodically to detect timeouts. Like it, this
+/// returns 4 if the task keeps failing.
+///
+/// There's no queue for the data, it's consumed before this returns. Since decoding and the
+/// callbacks take a while, this must not be called from an interrupt handler. Data received
+/// there has to be buffered by C, e.g. in a `ring_buf`, and fed from a thread or work queue.
+#[no_mangle]
+pub extern "C" fn sml_feed(
+    context: core::ptr::NonNull<ContextStorage>,
+    data: *const core::ffi::c_void,
+    len: usize,
+) -> u32 {
+    let context = context.cast::<Context>();
     // SAFETY: We expect our callers to only pass initialized non-null pointers
-    let context = unsafe { context.as_mut() };
-    // SAFETY: C usually doesn't move memory around and we expect our callers
-    //         to not do that
-    let f = unsafe { core::pin::Pin::new_unchecked(&mut context.f) };
+    let (input, reader) = unsafe {
+        let context = context.as_ptr();
+        (&(*context).input, (*context).task.reader)
+    };
+    if let Reader::Callback(_) = reader {
+        dlog::error!("can't feed data when using a read callback");
+        return 2;
+    }
+    if len == 0 {
+        return 0;
+    }
+    if data.is_null() {
+        dlog::error!("data is null");
+        return 2;
+    }
 
-    let mut task_context = core::task::Context::from_waker(&context.waker);
-    match f.poll(&mut task_context) {
-        core::task::Poll::Ready(res) => {
-            log::error!("task ended with: {:?}", res);
-            1
+    input.data.set(data as *const u8);
+    input.len.set(len);
+    // the reader only returns pending once everything was consumed, a restarted task
+    // continues with the remaining data
+    let ret = loop {
+        match poll(context) {
+            Polled::Pending => break 0,
+            Polled::Restarted if input.len.get() > 0 => (),
+            Polled::Restarted => break 0,
+            Polled::Failed => break 4,
         }
-        core::task::Poll::Pending => 0,
+    };
+
+    input.data.set(core::ptr::null());
+    input.len.set(0);
+
+    ret
+}
+
+/// drop the context
+///
+/// Afterwards, the context must not be used anymore until it's initialized again using
+/// [sml_init]. Must not be called from within a callback.
+#[no_mangle]
+pub extern "C" fn sml_deinit(context: core::ptr::NonNull<ContextStorage>) -> u32 {
+    let context = context.cast::<Context>();
+    // SAFETY: We expect our callers to only pass initialized non-null pointers and to not use
+    //         the context afterwards
+    unsafe { core::ptr::drop_in_place(context.as_ptr()) };
+    0
+}
+
+/// start reading from scratch
+///
+/// Drops the partially received frame and forgets about previous failures, see [sml_poll].
+/// Call [sml_poll] afterwards to start reading again. Must not be called from within a callback.
+#[no_mangle]
+pub extern "C" fn sml_reset(context: core::ptr::NonNull<ContextStorage>) -> u32 {
+    let context = context.cast::<Context>();
+    // SAFETY: see `poll`
+    unsafe {
+        restart(context);
+        (*context.as_ptr()).failures = 0;
     }
+    0
+}
+
+/// set the timeouts for detecting a stalled link, in milliseconds
+///
+/// `smr_event_no_frame` is sent if no frame started for `frame_timeout_ms`, 10 seconds by
+/// default. Meters send a frame every 1 to 4 seconds, so this should span a few of them.
+/// `smr_event_frame_aborted` is sent if a frame stalls for `byte_timeout_ms`, 500 milliseconds
+/// by default. Slow baud rates may need more.
+///
+/// Both have to be non-zero, fails with 2 otherwise. Reading starts over with the next frame,
+/// like after [sml_reset], so [sml_poll] has to be called afterwards. Must not be called from
+/// within a callback.
+#[no_mangle]
+pub extern "C" fn sml_set_timeouts(
+    context: core::ptr::NonNull<ContextStorage>,
+    frame_timeout_ms: u32,
+    byte_timeout_ms: u32,
+) -> u32 {
+    if frame_timeout_ms == 0 || byte_timeout_ms == 0 {
+        dlog::error!("timeouts must not be 0");
+        return 2;
+    }
+
+    let context = context.cast::<Context>();
+    // SAFETY: see `poll`
+    unsafe {
+        (*context.as_ptr()).task.timeouts = sml::Timeouts {
+            frame_ms: frame_timeout_ms.into(),
+            byte_ms: byte_timeout_ms.into(),
+        };
+        restart(context);
+    }
+    0
+}
+
+/// the result of [poll]
+enum Polled {
+    /// the task waits for data
+    Pending,
+    /// the task failed and was restarted, it has to be polled again right away
+    Restarted,
+    /// the task failed [MAX_FAILURES] times in a row and was restarted
+    Failed,
+}
+
+fn poll(context: core::ptr::NonNull<Context>) -> Polled {
+    let context_ptr = context.as_ptr();
+    // SAFETY: We expect our callers to only pass initialized non-null pointers.
+    //         C usually doesn't move memory around and we expect our callers
+    //         to not do that. The reader accesses `input`, so we only borrow `f`.
+    let f = unsafe { core::pin::Pin::new_unchecked(&mut (*context_ptr).f) };
+
+    // SAFETY: see above
+    let waker = match unsafe { &(*context_ptr).wake } {
+        // SAFETY: the future is stored next to `wake`, so it can't outlive it
+        Some(wake) => unsafe { wake.waker() },
+        None => crate::waker::stub(),
+    };
+    let mut task_context = core::task::Context::from_waker(&waker);
+    #[cfg(feature = "logger")]
+    // SAFETY: see above
+    let res = crate::logger::with_instance_level(unsafe { (*context_ptr).log_level }, || {
+        f.poll(&mut task_context)
+    });
+    #[cfg(not(feature = "logger"))]
+    let res = f.poll(&mut task_context);
+    let res = match res {
+        core::task::Poll::Ready(res) => res,
+        core::task::Poll::Pending => {
+            // SAFETY: see above
+            unsafe { (*context_ptr).failures = 0 };
+            return Polled::Pending;
+        }
+    };
+
+    match res {
+        Ok(()) => dlog::error!("task ended, restarting"),
+        Err(e) => dlog::error!("task failed: {}, restarting", e),
+    }
+    // SAFETY: see above, the future isn't borrowed anymore
+    let failures = unsafe {
+        restart(context);
+        (*context_ptr).task.event(crate::Event::Restarted);
+        (*context_ptr).failures = (*context_ptr).failures.saturating_add(1);
+        (*context_ptr).failures
+    };
+    if failures >= MAX_FAILURES {
+        dlog::error!("task failed {} times in a row", failures);
+        return Polled::Failed;
+    }
+
+    Polled::Restarted
+}
+
+/// replaces the future in place
+///
+/// # Safety
+///
+/// `context` must be initialized and the future must not be borrowed
+unsafe fn restart(context: core::ptr::NonNull<Context>) {
+    let context = context.as_ptr();
+    let mut f = core::pin::Pin::new_unchecked(&mut (*context).f);
+    f.set(sml_task_sized((*context).task));
+}
+
+/// limit the logs of this context to `level`
+///
+/// The levels set using `smr_set_log_level` still apply, so this can only lower them, e.g. to
+/// only see the trace logs of one of two meters. `level` is a `smr_loglevel_filter`, fails with
+/// 4 for other values. The context starts with `smr_loglevel_filter_trace`.
+///
+/// The logger can't tell which context a log belongs to, so the level applies to all logs while
+/// this context is polled or fed. All contexts have to be polled and fed from one thread or
+/// work queue for that. Otherwise, the level of a context which is polled at the same time is
+/// used instead.
+#[cfg(feature = "logger")]
+#[no_mangle]
+pub extern "C" fn sml_set_log_level(
+    context: core::ptr::NonNull<ContextStorage>,
+    level: u32,
+) -> u32 {
+    let level = match crate::logger::level_filter(level) {
+        Some(level) => level,
+        None => return 4,
+    };
+
+    let context = context.cast::<Context>();
+    // SAFETY: We expect our callers to only pass initialized non-null pointers
+    unsafe { (*context.as_ptr()).log_level = level };
+    0
 }
 
 /// return the buffer size required for the sml context
@@ -162,19 +616,344 @@ mod tests {
         ) {
         }
 
-        let mut context = core::mem::MaybeUninit::<super::Context>::uninit();
+        extern "C" fn wake_callback(user: *mut core::ffi::c_void) {
+            let wakes = unsafe { &*(user as *const core::sync::atomic::AtomicUsize) };
+            wakes.fetch_add(1, core::sync::atomic::Ordering::SeqCst);
+        }
+
+        let wakes = core::sync::atomic::AtomicUsize::new(0);
+        let mut context = core::mem::MaybeUninit::<super::ContextStorage>::uninit();
+        assert_eq!(
+            super::sml_init(
+                super::API_VERSION + 1,
+                context.as_mut_ptr(),
+                core::mem::size_of::<super::ContextStorage>(),
+                core::ptr::null_mut(),
+                Some(read_callback),
+                Some(message_callback),
+                None,
+                None,
+                None,
+                core::ptr::null_mut(),
+            ),
+            3
+        );
+        assert_eq!(
+            super::sml_init(
+                super::API_VERSION,
+                context.as_mut_ptr(),
+                core::mem::size_of::<super::ContextStorage>(),
+                core::ptr::null_mut(),
+                Some(read_callback),
+                Some(message_callback),
+                None,
+                None,
+                Some(wake_callback),
+                &wakes as *const _ as *mut core::ffi::c_void,
+            ),
+            0
+        );
+        let context = unsafe { context.assume_init_mut() };
+
+        // the reader didn't get any data, so it asks to be woken up
+        assert_eq!(super::sml_poll(context.into()), 0);
+        assert_eq!(wakes.load(core::sync::atomic::Ordering::SeqCst), 1);
+
+        #[cfg(feature = "logger")]
+        {
+            assert_eq!(super::sml_set_log_level(context.into(), 6), 4);
+            assert_eq!(super::sml_set_log_level(context.into(), 2), 0);
+            assert_eq!(super::sml_poll(context.into()), 0);
+        }
+    }
+
+    #[test_log::test]
+    fn restart() {
+        static READS: core::sync::atomic::AtomicUsize = core::sync::atomic::AtomicUsize::new(0);
+
+        // fails on the first read only
+        extern "C" fn read_callback(
+            _buf: *mut core::ffi::c_void,
+            _max_length: usize,
+            _out_length: *mut usize,
+        ) -> u32 {
+            match READS.fetch_add(1, core::sync::atomic::Ordering::SeqCst) {
+                0 => 5,
+                _ => 0,
+            }
+        }
+
+        extern "C" fn message_callback(
+            _user: *mut core::ffi::c_void,
+            _data: *const crate::CallbackData,
+        ) {
+        }
+
+        extern "C" fn event_callback(user: *mut core::ffi::c_void, event: crate::Event) {
+            assert_eq!(event, crate::Event::Restarted);
+            let restarts = unsafe { &*(user as *const core::sync::atomic::AtomicUsize) };
+            restarts.fetch_add(1, core::sync::atomic::Ordering::SeqCst);
+        }
+
+        let restarts = core::sync::atomic::AtomicUsize::new(0);
+        let mut context = core::mem::MaybeUninit::<super::ContextStorage>::uninit();
+        assert_eq!(
+            super::sml_init(
+                super::API_VERSION,
+                context.as_mut_ptr(),
+                core::mem::size_of::<super::ContextStorage>(),
+                &restarts as *const _ as *mut core::ffi::c_void,
+                Some(read_callback),
+                Some(message_callback),
+                None,
+                Some(event_callback),
+                None,
+                core::ptr::null_mut(),
+            ),
+            0
+        );
+        let context = unsafe { context.assume_init_mut() };
+
+        // asks to be polled again right away instead of waking
+        assert_eq!(super::sml_poll(context.into()), 5);
+        assert_eq!(restarts.load(core::sync::atomic::Ordering::SeqCst), 1);
+
+        // the restarted task reads again
+        assert_eq!(super::sml_poll(context.into()), 0);
+        assert_eq!(READS.load(core::sync::atomic::Ordering::SeqCst), 2);
+        assert_eq!(super::sml_reset(context.into()), 0);
+        assert_eq!(super::sml_poll(context.into()), 0);
+        assert_eq!(READS.load(core::sync::atomic::Ordering::SeqCst), 3);
+        assert_eq!(restarts.load(core::sync::atomic::Ordering::SeqCst), 1);
+    }
+
+    #[test_log::test]
+    fn persistent_failure() {
+        extern "C" fn read_callback(
+            _buf: *mut core::ffi::c_void,
+            _max_length: usize,
+            _out_length: *mut usize,
+        ) -> u32 {
+            5
+        }
+
+        extern "C" fn message_callback(
+            _user: *mut core::ffi::c_void,
+            _data: *const crate::CallbackData,
+        ) {
+        }
+
+        extern "C" fn wake_callback(user: *mut core::ffi::c_void) {
+            let wakes = unsafe { &*(user as *const core::sync::atomic::AtomicUsize) };
+            wakes.fetch_add(1, core::sync::atomic::Ordering::SeqCst);
+        }
+
+        let wakes = core::sync::atomic::AtomicUsize::new(0);
+        let mut context = core::mem::MaybeUninit::<super::ContextStorage>::uninit();
         assert_eq!(
             super::sml_init(
+                super::API_VERSION,
                 context.as_mut_ptr(),
-                core::mem::size_of::<super::Context>(),
+                core::mem::size_of::<super::ContextStorage>(),
                 core::ptr::null_mut(),
                 Some(read_callback),
                 Some(message_callback),
+                None,
+                None,
+                Some(wake_callback),
+                &wakes as *const _ as *mut core::ffi::c_void,
+            ),
+            0
+        );
+        let context = unsafe { context.assume_init_mut() };
+
+        assert_eq!(super::sml_poll(context.into()), 5);
+        assert_eq!(super::sml_poll(context.into()), 5);
+
+        // reported instead of asking to be polled again
+        assert_eq!(super::sml_poll(context.into()), 4);
+        assert_eq!(super::sml_poll(context.into()), 4);
+
+        assert_eq!(super::sml_reset(context.into()), 0);
+        assert_eq!(super::sml_poll(context.into()), 5);
+
+        // the wake callback only means that the reader needs data
+        assert_eq!(wakes.load(core::sync::atomic::Ordering::SeqCst), 0);
+    }
+
+    #[test_log::test]
+    fn read_errors() {
+        assert!(matches!(
+            super::read_error(super::ReadError::Overrun as u32),
+            io::Error::Overrun
+        ));
+        assert!(matches!(
+            super::read_error(super::ReadError::Framing as u32),
+            io::Error::Framing
+        ));
+        assert!(matches!(
+            super::read_error(5),
+            io::Error::NativeUnsigned { ret: 5 }
+        ));
+    }
+
+    #[test_log::test]
+    fn timeouts() {
+        static NOW: core::sync::atomic::AtomicU64 = core::sync::atomic::AtomicU64::new(0);
+        static EVENTS: std::sync::Mutex<Vec<crate::Event>> = std::sync::Mutex::new(Vec::new());
+
+        extern "C" fn uptime_callback() -> u64 {
+            NOW.load(core::sync::atomic::Ordering::SeqCst)
+        }
+
+        extern "C" fn message_callback(
+            _user: *mut core::ffi::c_void,
+            _data: *const crate::CallbackData,
+        ) {
+        }
+
+        extern "C" fn event_callback(_user: *mut core::ffi::c_void, event: crate::Event) {
+            EVENTS.lock().unwrap().push(event);
+        }
+
+        fn poll_at(context: &mut super::ContextStorage, now: u64) -> Vec<crate::Event> {
+            NOW.store(now, core::sync::atomic::Ordering::SeqCst);
+            assert_eq!(super::sml_poll(context.into()), 0);
+            EVENTS.lock().unwrap().clone()
+        }
+
+        let mut context = core::mem::MaybeUninit::<super::ContextStorage>::uninit();
+        assert_eq!(
+            super::sml_init(
+                super::API_VERSION,
+                context.as_mut_ptr(),
+                core::mem::size_of::<super::ContextStorage>(),
+                core::ptr::null_mut(),
+                None,
+                Some(message_callback),
+                Some(uptime_callback),
+                Some(event_callback),
+                None,
+                core::ptr::null_mut(),
             ),
             0
         );
         let context = unsafe { context.assume_init_mut() };
 
-        super::sml_poll(context.into());
+        assert_eq!(super::sml_set_timeouts(context.into(), 0, 100), 2);
+        assert_eq!(super::sml_set_timeouts(context.into(), 1000, 100), 0);
+        assert_eq!(poll_at(context, 0), []);
+        assert_eq!(poll_at(context, 999), []);
+        assert_eq!(poll_at(context, 1000), [crate::Event::NoFrame]);
+
+        // a frame starts, but stalls
+        NOW.store(1500, core::sync::atomic::Ordering::SeqCst);
+        feed(context, &[0x1b, 0x1b, 0x1b, 0x1b, 0x01, 0x01, 0x01, 0x01]);
+        assert_eq!(poll_at(context, 1599), [crate::Event::NoFrame]);
+        assert_eq!(
+            poll_at(context, 1600),
+            [crate::Event::NoFrame, crate::Event::FrameAborted]
+        );
+
+        assert_eq!(super::sml_deinit(context.into()), 0);
+    }
+
+    const LONG_TAGS: &[u8] = include_bytes!("../../sml/testdata/long_tags.bin");
+    const NO_SENSOR_TIME: &[u8] = include_bytes!("../../sml/testdata/no_sensor_time.bin");
+
+    /// initializes a context without a read callback, which collects the received energy values
+    fn init_feed<'a>(
+        context: &'a mut core::mem::MaybeUninit<super::ContextStorage>,
+        energies: &mut Vec<u64>,
+    ) -> &'a mut super::ContextStorage {
+        extern "C" fn message_callback(
+            user: *mut core::ffi::c_void,
+            data: *const crate::CallbackData,
+        ) {
+            let energies = unsafe { &mut *(user as *mut Vec<u64>) };
+            energies.push(unsafe { &*data }.active_energy.value);
+        }
+
+        assert_eq!(
+            super::sml_init(
+                super::API_VERSION,
+                context.as_mut_ptr(),
+                core::mem::size_of::<super::ContextStorage>(),
+                energies as *mut Vec<u64> as *mut core::ffi::c_void,
+                None,
+                Some(message_callback),
+                None,
+                None,
+                None,
+                core::ptr::null_mut(),
+            ),
+            0
+        );
+        unsafe { context.assume_init_mut() }
+    }
+
+    fn feed(context: &mut super::ContextStorage, data: &[u8]) {
+        assert_eq!(
+            super::sml_feed(context.into(), data.as_ptr() as *const _, data.len()),
+            0
+        );
+    }
+
+    fn feed_capture(capture: &[u8], chunk_size: usize) -> Vec<u64> {
+        let mut energies = Vec::new();
+        let mut context = core::mem::MaybeUninit::uninit();
+        let context = init_feed(&mut context, &mut energies);
+
+        for chunk in capture.chunks(chunk_size) {
+            feed(context, chunk);
+        }
+        assert_eq!(super::sml_poll(context.into()), 0);
+        assert_eq!(super::sml_deinit(context.into()), 0);
+
+        energies
+    }
+
+    #[test_log::test]
+    fn feed_chunks() {
+        let energies = feed_capture(LONG_TAGS, 4096);
+        assert!(!energies.is_empty());
+        assert_eq!(feed_capture(LONG_TAGS, 7), energies);
+        assert_eq!(feed_capture(LONG_TAGS, 1), energies);
+    }
+
+    #[test_log::test]
+    fn instances() {
+        let expected_a = feed_capture(LONG_TAGS, 4096);
+        let expected_b = feed_capture(NO_SENSOR_TIME, 4096);
+        assert!(!expected_b.is_empty());
+        assert_ne!(expected_a, expected_b);
+
+        let mut energies_a = Vec::new();
+        let mut energies_b = Vec::new();
+        let mut context_a = core::mem::MaybeUninit::uninit();
+        let mut context_b = core::mem::MaybeUninit::uninit();
+        let context_a = init_feed(&mut context_a, &mut energies_a);
+        let context_b = init_feed(&mut context_b, &mut energies_b);
+
+        let mut chunks_a = LONG_TAGS.chunks(5);
+        let mut chunks_b = NO_SENSOR_TIME.chunks(3);
+        loop {
+            let chunk_a = chunks_a.next();
+            let chunk_b = chunks_b.next();
+            if chunk_a.is_none() && chunk_b.is_none() {
+                break;
+            }
+            if let Some(chunk) = chunk_a {
+                feed(context_a, chunk);
+            }
+            if let Some(chunk) = chunk_b {
+                feed(context_b, chunk);
+            }
+        }
+
+        assert_eq!(super::sml_deinit(context_a.into()), 0);
+        assert_eq!(super::sml_deinit(context_b.into()), 0);
+        assert_eq!(energies_a, expected_a);
+        assert_eq!(energies_b, expected_b);
     }
 }
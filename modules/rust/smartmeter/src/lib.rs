@@ -37,18 +37,48 @@ pub struct CallbackData {
 
 pub type MessageFn = extern "C" fn(*mut core::ffi::c_void, *const CallbackData);
 
+/// something went wrong with the connection to the meter
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    /// no frame started within the frame timeout, reported again after every timeout, see
+    /// `sml_set_timeouts`
+    NoFrame,
+    /// a frame was aborted because no data was received within the byte timeout
+    FrameAborted,
+    /// reading failed, e.g. because the read callback returned an error, and was restarted
+    Restarted,
+}
+
+impl From<sml::Timeout> for Event {
+    fn from(timeout: sml::Timeout) -> Self {
+        match timeout {
+            sml::Timeout::NoFrame => Self::NoFrame,
+            sml::Timeout::FrameAborted => Self::FrameAborted,
+        }
+    }
+}
+
+pub type EventFn = extern "C" fn(*mut core::ffi::c_void, Event);
+
 /// handle SML messages and forward data to C
-struct MessageCallback {
+pub(crate) struct MessageCallback {
     function: MessageFn,
+    event_function: Option<EventFn>,
     user_context: *mut core::ffi::c_void,
     active_power: Option<Value>,
     active_energy: Option<Value>,
 }
 
 impl MessageCallback {
-    pub fn new(function: MessageFn, user_context: *mut core::ffi::c_void) -> Self {
+    pub fn new(
+        function: MessageFn,
+        event_function: Option<EventFn>,
+        user_context: *mut core::ffi::c_void,
+    ) -> Self {
         Self {
             function,
+            event_function,
             user_context,
             active_power: None,
             active_energy: None,
@@ -66,66 +96,58 @@ impl<R: io::AsyncRead + Unpin> sml::Callback<R> for MessageCallback {
         &'a mut self,
         mut body: sml::types::MessageBody<'a, R>,
     ) -> Result<(), sml::Error> {
-        const OBIS_ACTIVE_ENERGY: &[u8] = &[0x01, 0x00, 0x01, 0x08, 0x00, 0xFF];
-        const OBIS_ACTIVE_POWER: &[u8] = &[0x01, 0x00, 0x10, 0x07, 0x00, 0xFF];
-
-        match body.read().await? {
-            sml::types::MessageBodyEnum::GetListResponse(r) => {
-                let mut field = r.val_list().await?;
-                let mut list = field.parse().await?;
+        const OBIS_ACTIVE_ENERGY: sml::reading::Obis =
+            sml::reading::Obis([0x01, 0x00, 0x01, 0x08, 0x00, 0xFF]);
+        const OBIS_ACTIVE_POWER: sml::reading::Obis =
+            sml::reading::Obis([0x01, 0x00, 0x10, 0x07, 0x00, 0xFF]);
 
-                let mut buf = [0; 7];
-                while let Some(entry) = list.next().await? {
-                    let mut field = entry.obj_name().await?;
-                    let mut obj_name = field.parse().await?;
-
-                    if obj_name.len() > buf.len() {
-                        continue;
-                    }
-
-                    let buf = &mut buf[..obj_name.len()];
-                    obj_name.read(buf).await?;
-                    drop(obj_name);
-                    let entry = field.finish().await?;
-
-                    let (entry, scaler) = entry.scaler().await?;
-                    let scaler = scaler.unwrap_or(0);
-                    log::debug!("scaler={}", scaler);
+        let r = match body.read().await? {
+            sml::types::MessageBodyEnum::GetListResponse(r) => r,
+            _ => return Ok(()),
+        };
 
-                    let mut field = entry.value().await?;
-                    let value = field.parse().await?.read().await?;
-                    match &*buf {
-                        OBIS_ACTIVE_POWER => {
-                            let value: u64 = value.into_i128_relaxed()?.try_into()?;
-                            log::debug!("active_power={}", value);
+        let mut entries = r.entries().await?;
+        while let Some(reading) = entries.next().await? {
+            let scaler = reading.scaler.unwrap_or(0);
+            log::debug!("scaler={}", scaler);
 
-                            if self.active_power.is_some() {
-                                return Err(sml::Error::UnexpectedValue);
-                            }
+            match reading.obis {
+                OBIS_ACTIVE_POWER => {
+                    let value = reading.value.to_i128().ok_or(sml::Error::UnexpectedValue)?;
+                    let value: u64 = value.try_into()?;
+                    log::debug!("active_power={}", value);
 
-                            self.active_power = Some(Value::new(scaler, value));
-                        }
+                    if self.active_power.is_some() {
+                        return Err(sml::Error::UnexpectedValue);
+                    }
 
-                        OBIS_ACTIVE_ENERGY => {
-                            let value: u64 = value.into_i128_relaxed()?.try_into()?;
-                            log::debug!("active_energy={}", value);
+                    self.active_power = Some(Value::new(scaler, value));
+                }
 
-                            if self.active_energy.is_some() {
-                                return Err(sml::Error::UnexpectedValue);
-                            }
+                OBIS_ACTIVE_ENERGY => {
+                    let value = reading.value.to_i128().ok_or(sml::Error::UnexpectedValue)?;
+                    let value: u64 = value.try_into()?;
+                    log::debug!("active_energy={}", value);
 
-                            self.active_energy = Some(Value::new(scaler, value));
-                        }
-                        _ => continue,
+                    if self.active_energy.is_some() {
+                        return Err(sml::Error::UnexpectedValue);
                     }
+
+                    self.active_energy = Some(Value::new(scaler, value));
                 }
+                _ => continue,
             }
-            _ => return Ok(()),
         }
 
         Ok(())
     }
 
+    fn timeout(&mut self, timeout: sml::Timeout) {
+        if let Some(function) = self.event_function {
+            function(self.user_context, timeout.into());
+        }
+    }
+
     fn frame_finished(&mut self, valid: bool) {
         log::info!(
             "finished, valid: {}, power={:?}, energy={:?}",
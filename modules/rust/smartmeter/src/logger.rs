@@ -1,14 +1,42 @@
 //! a logger that writes data via a C callback
+//!
+//! The sink, the levels and the target filters are process-wide on purpose and shared by all
+//! contexts. The `log` crate and `dlog` only support one global logger, and the `sml` crate
+//! logs using their macros, which don't know about contexts. So a level can't be stored in a
+//! context and there's only one sink to send the records to.
+//!
+//! A context can only lower the levels while it's polled, see [with_instance_level].
 
 use core::fmt::Write as _;
 
 static STATE: core::sync::atomic::AtomicUsize = core::sync::atomic::AtomicUsize::new(0);
 static mut SINK_CALLBACK: SinkCallback = nop_sink;
+static mut RECORD_SINK_CALLBACK: Option<RecordSinkCallback> = None;
 static LOGGER: SimpleLogger = SimpleLogger {};
 
+/// the level for targets without a filter, as [log::LevelFilter]
+static LEVEL: core::sync::atomic::AtomicUsize =
+    core::sync::atomic::AtomicUsize::new(DEFAULT_LEVEL as usize);
+/// protects [FILTERS], logging falls back to [LEVEL] while it's taken
+static FILTERS_LOCKED: core::sync::atomic::AtomicBool = core::sync::atomic::AtomicBool::new(false);
+static mut FILTERS: [Option<TargetFilter>; MAX_FILTERS] = [None; MAX_FILTERS];
+/// the level of the context which is polled right now as [log::LevelFilter], or [NO_INSTANCE]
+static INSTANCE_LEVEL: core::sync::atomic::AtomicUsize =
+    core::sync::atomic::AtomicUsize::new(NO_INSTANCE);
+
 const UNINITIALIZED: usize = 0;
 const INITIALIZED: usize = 1;
 
+/// [INSTANCE_LEVEL] while no context is polled
+const NO_INSTANCE: usize = usize::MAX;
+
+const DEFAULT_LEVEL: log::LevelFilter = log::LevelFilter::Debug;
+const MAX_FILTERS: usize = 8;
+const MAX_TARGET_LEN: usize = 32;
+const MAX_MESSAGE_LEN: usize = 256;
+/// the maximum length of the target, module path and file of a [CLogRecord]
+const MAX_LOCATION_LEN: usize = 64;
+
 #[repr(C)]
 #[derive(Copy, Clone)]
 pub enum CLogLevel {
@@ -19,11 +47,208 @@ pub enum CLogLevel {
     Trace,
 }
 
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub enum CLogLevelFilter {
+    Off,
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl From<log::Level> for CLogLevel {
+    fn from(level: log::Level) -> Self {
+        match level {
+            log::Level::Error => Self::Error,
+            log::Level::Warn => Self::Warn,
+            log::Level::Info => Self::Info,
+            log::Level::Debug => Self::Debug,
+            log::Level::Trace => Self::Trace,
+        }
+    }
+}
+
+/// converts a [CLogLevelFilter] passed by C, which can be any number
+pub(crate) fn level_filter(level: u32) -> Option<log::LevelFilter> {
+    [
+        (CLogLevelFilter::Off, log::LevelFilter::Off),
+        (CLogLevelFilter::Error, log::LevelFilter::Error),
+        (CLogLevelFilter::Warn, log::LevelFilter::Warn),
+        (CLogLevelFilter::Info, log::LevelFilter::Info),
+        (CLogLevelFilter::Debug, log::LevelFilter::Debug),
+        (CLogLevelFilter::Trace, log::LevelFilter::Trace),
+    ]
+    .into_iter()
+    .find(|(filter, _)| *filter as u32 == level)
+    .map(|(_, level)| level)
+}
+
+/// the level for a target and all of its submodules
+#[derive(Copy, Clone)]
+struct TargetFilter {
+    target: [u8; MAX_TARGET_LEN],
+    target_len: usize,
+    level: log::LevelFilter,
+}
+
+impl TargetFilter {
+    fn target(&self) -> &[u8] {
+        &self.target[..self.target_len]
+    }
+
+    fn matches(&self, target: &str) -> bool {
+        match target.as_bytes().strip_prefix(self.target()) {
+            Some(rest) => rest.is_empty() || rest.starts_with(b"::"),
+            None => false,
+        }
+    }
+}
+
+/// runs `f` with the target filters, returns `None` if they're in use
+fn with_filters<R>(f: impl FnOnce(&mut [Option<TargetFilter>; MAX_FILTERS]) -> R) -> Option<R> {
+    if FILTERS_LOCKED
+        .compare_exchange(
+            false,
+            true,
+            core::sync::atomic::Ordering::Acquire,
+            core::sync::atomic::Ordering::Relaxed,
+        )
+        .is_err()
+    {
+        return None;
+    }
+
+    // SAFETY: we hold the lock
+    let ret = f(unsafe { &mut *core::ptr::addr_of_mut!(FILTERS) });
+    FILTERS_LOCKED.store(false, core::sync::atomic::Ordering::Release);
+    Some(ret)
+}
+
+fn global_level() -> log::LevelFilter {
+    let level = LEVEL.load(core::sync::atomic::Ordering::Relaxed);
+    log::LevelFilter::iter().nth(level).unwrap_or(DEFAULT_LEVEL)
+}
+
+/// runs `f` with all logs limited to `level`
+///
+/// Used while polling a context, so its level applies to the logs of its task. The logging
+/// macros can't tell which thread or context they're called from, so the level applies to every
+/// log during that time. That's why all contexts have to be polled from one thread or work
+/// queue.
+///
+/// If another context is polled in parallel anyway, its level is kept and `level` isn't applied.
+/// Either way, no level is left behind once both are done.
+pub(crate) fn with_instance_level<R>(level: log::LevelFilter, f: impl FnOnce() -> R) -> R {
+    let applied = INSTANCE_LEVEL
+        .compare_exchange(
+            NO_INSTANCE,
+            level as usize,
+            core::sync::atomic::Ordering::Acquire,
+            core::sync::atomic::Ordering::Relaxed,
+        )
+        .is_ok();
+    let ret = f();
+    if applied {
+        INSTANCE_LEVEL.store(NO_INSTANCE, core::sync::atomic::Ordering::Release);
+    }
+    ret
+}
+
+/// without a polled context, nothing is limited
+fn instance_level() -> log::LevelFilter {
+    let level = INSTANCE_LEVEL.load(core::sync::atomic::Ordering::Relaxed);
+    log::LevelFilter::iter()
+        .nth(level)
+        .unwrap_or(log::LevelFilter::Trace)
+}
+
+/// whether `level` is logged for `target` by the context which is polled right now
+fn enabled(level: log::Level, target: &str) -> bool {
+    level <= self::level(target).min(instance_level())
+}
+
+/// the level of the longest matching target filter or the global one
+fn level(target: &str) -> log::LevelFilter {
+    with_filters(|filters| {
+        filters
+            .iter()
+            .flatten()
+            .filter(|filter| filter.matches(target))
+            .max_by_key(|filter| filter.target_len)
+            .map(|filter| filter.level)
+    })
+    .flatten()
+    .unwrap_or_else(global_level)
+}
+
+/// lets the `log` macros skip everything that's disabled for all targets
+fn update_max_level() {
+    let max = with_filters(|filters| {
+        filters
+            .iter()
+            .flatten()
+            .map(|filter| filter.level)
+            .max()
+            .unwrap_or(log::LevelFilter::Off)
+    })
+    // the filters are only in use while they're changed, which updates the level afterwards
+    .unwrap_or(log::LevelFilter::Trace);
+
+    let max = max.max(global_level());
+    // deferred records are only sent for errors and warnings, their sink gets nothing else
+    #[cfg(feature = "deferred-log")]
+    let max = max.min(log::LevelFilter::Warn);
+    log::set_max_level(max);
+}
+
 type SinkCallback =
     extern "C" fn(level: CLogLevel, buf: *const core::ffi::c_void, len: usize) -> u32;
 type SinkCallbackOpt =
     Option<extern "C" fn(level: CLogLevel, buf: *const core::ffi::c_void, len: usize) -> u32>;
 
+/// a NUL-terminated string, `len` doesn't include the NUL
+///
+/// Zephyr's deferred logging copies `%s` arguments using `strlen`, so the NUL is needed even
+/// though the length is known.
+#[repr(C)]
+pub struct CStrRef {
+    data: *const core::ffi::c_char,
+    len: usize,
+}
+
+impl<const N: usize> From<&StrBuffer<N>> for CStrRef {
+    fn from(buffer: &StrBuffer<N>) -> Self {
+        Self {
+            data: buffer.buf.as_ptr().cast(),
+            len: buffer.len,
+        }
+    }
+}
+
+/// a log record, the strings are only valid during the callback
+#[repr(C)]
+pub struct CLogRecord {
+    level: CLogLevel,
+    /// usually the module path, e.g. `sml::frame`
+    target: CStrRef,
+    /// empty if unknown
+    module_path: CStrRef,
+    /// empty if unknown
+    file: CStrRef,
+    /// 0 if unknown
+    line: u32,
+    message: CStrRef,
+    /// the message was cut off, it's limited to 255 bytes
+    ///
+    /// The other strings are cut off at 63 bytes without a notice.
+    truncated: bool,
+}
+
+type RecordSinkCallback = extern "C" fn(record: *const CLogRecord);
+type RecordSinkCallbackOpt = Option<extern "C" fn(record: *const CLogRecord)>;
+
 /// returns the current sink or an empty one if there is none
 pub fn sink() -> SinkCallback {
     if STATE.load(core::sync::atomic::Ordering::SeqCst) != INITIALIZED {
@@ -41,16 +266,8 @@ struct LogWriter {
 
 impl LogWriter {
     pub fn new(level: log::Level) -> Self {
-        let level = match level {
-            log::Level::Error => CLogLevel::Error,
-            log::Level::Warn => CLogLevel::Warn,
-            log::Level::Info => CLogLevel::Info,
-            log::Level::Debug => CLogLevel::Debug,
-            log::Level::Trace => CLogLevel::Trace,
-        };
-
         Self {
-            level,
+            level: level.into(),
             sink: sink(),
         }
     }
@@ -70,16 +287,113 @@ impl core::fmt::Write for LogWriter {
     }
 }
 
-/// implements [log::Log] with a [LogWriter]
+/// sends deferred records through the sink
+#[cfg(feature = "deferred-log")]
+struct DeferredLogger;
+
+#[cfg(feature = "deferred-log")]
+static DEFERRED_LOGGER: DeferredLogger = DeferredLogger;
+
+#[cfg(feature = "deferred-log")]
+impl dlog::Logger for DeferredLogger {
+    fn enabled(&self, level: log::Level, target: &str) -> bool {
+        self::enabled(level, target)
+    }
+
+    fn log(&self, level: log::Level, record: &[u8]) {
+        let sink = sink();
+        let level = level.into();
+        let _ = sink(
+            level,
+            record.as_ptr() as *const core::ffi::c_void,
+            record.len(),
+        );
+        let _ = sink(level, core::ptr::null(), 0);
+    }
+}
+
+/// formats a NUL-terminated string into a bounded buffer, cutting it off at a char boundary
+struct StrBuffer<const N: usize> {
+    /// the last byte is reserved for the NUL
+    buf: [u8; N],
+    len: usize,
+    truncated: bool,
+}
+
+impl<const N: usize> StrBuffer<N> {
+    pub fn new() -> Self {
+        Self {
+            buf: [0; N],
+            len: 0,
+            truncated: false,
+        }
+    }
+
+    /// copies `s`, cutting it off if it's too long
+    pub fn from_str(s: &str) -> Self {
+        let mut buffer = Self::new();
+        let _ = buffer.write_str(s);
+        buffer
+    }
+}
+
+impl<const N: usize> core::fmt::Write for StrBuffer<N> {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        let mut len = s.len().min(self.buf.len() - 1 - self.len);
+        while !s.is_char_boundary(len) {
+            len -= 1;
+        }
+        if len < s.len() {
+            self.truncated = true;
+        }
+
+        self.buf[self.len..self.len + len].copy_from_slice(&s.as_bytes()[..len]);
+        self.len += len;
+        // the rest of the buffer is still zeroed, so this stays NUL-terminated
+        Ok(())
+    }
+}
+
+/// returns the record sink if it was initialized
+fn record_sink() -> Option<RecordSinkCallback> {
+    if STATE.load(core::sync::atomic::Ordering::SeqCst) != INITIALIZED {
+        None
+    } else {
+        unsafe { RECORD_SINK_CALLBACK }
+    }
+}
+
+/// implements [log::Log] with a [LogWriter] or a record sink
 struct SimpleLogger;
 
 impl log::Log for SimpleLogger {
     fn enabled(&self, metadata: &log::Metadata) -> bool {
-        metadata.level() <= log::Level::Debug
+        self::enabled(metadata.level(), metadata.target())
     }
 
     fn log(&self, record: &log::Record) {
-        if self.enabled(record.metadata()) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        if let Some(sink) = record_sink() {
+            let mut message = StrBuffer::<MAX_MESSAGE_LEN>::new();
+            let _ = write!(&mut message, "{}", record.args());
+            let target = StrBuffer::<MAX_LOCATION_LEN>::from_str(record.target());
+            let module_path =
+                StrBuffer::<MAX_LOCATION_LEN>::from_str(record.module_path().unwrap_or_default());
+            let file = StrBuffer::<MAX_LOCATION_LEN>::from_str(record.file().unwrap_or_default());
+
+            sink(&CLogRecord {
+                level: record.level().into(),
+                target: (&target).into(),
+                module_path: (&module_path).into(),
+                file: (&file).into(),
+                line: record.line().unwrap_or(0),
+                message: (&message).into(),
+                truncated: message.truncated,
+            });
+        } else {
             let mut writer = LogWriter::new(record.level());
             let _ = write!(&mut writer, "{}: {}", record.target(), record.args());
             writer.finish_record();
@@ -99,8 +413,12 @@ extern "C" fn nop_sink(_level: CLogLevel, _s: *const core::ffi::c_void, _len: us
 
 /// Initialize SML logger
 ///
+/// The logger is global and receives the logs of all contexts, see [smr_set_log_level].
+///
 /// - should only be called once
 /// - `sink` must not be NULL
+/// - with deferred logs, `sink` only receives binary records for errors and
+///   warnings, which are decoded using `smllog`
 /// - must not be called while running other functions of this library in
 ///   parallel - e.g. on threads or during interrupts.
 #[no_mangle]
@@ -120,8 +438,253 @@ pub unsafe extern "C" fn smr_init_logger(sink: SinkCallbackOpt) -> u32 {
         _ => return 2,
     }
 
-    match log::set_logger_racy(&LOGGER).map(|()| log::set_max_level(log::LevelFilter::Debug)) {
+    // the sink can't tell formatted messages from deferred records, so there's only one of them
+    #[cfg(feature = "deferred-log")]
+    let ret = dlog::set_logger_racy(&DEFERRED_LOGGER)
+        .map(|()| update_max_level())
+        .map_err(|_| ());
+    #[cfg(not(feature = "deferred-log"))]
+    let ret = log::set_logger_racy(&LOGGER)
+        .map(|()| update_max_level())
+        .map_err(|_| ());
+
+    match ret {
+        Err(_) => 3,
+        Ok(_) => 0,
+    }
+}
+
+/// Initialize SML logger with a sink that receives whole records
+///
+/// - like [smr_init_logger], only one of them should be called once
+/// - `sink` must not be NULL
+/// - deferred logs aren't supported
+/// - must not be called while running other functions of this library in
+///   parallel - e.g. on threads or during interrupts.
+#[no_mangle]
+pub unsafe extern "C" fn smr_init_logger_record(sink: RecordSinkCallbackOpt) -> u32 {
+    let sink = match sink {
+        Some(v) => v,
+        None => {
+            return 1;
+        }
+    };
+
+    match STATE.load(core::sync::atomic::Ordering::SeqCst) {
+        UNINITIALIZED => {
+            RECORD_SINK_CALLBACK = Some(sink);
+            STATE.store(INITIALIZED, core::sync::atomic::Ordering::SeqCst);
+        }
+        _ => return 2,
+    }
+
+    match log::set_logger_racy(&LOGGER).map(|()| update_max_level()) {
         Err(_) => 3,
         Ok(_) => 0,
     }
 }
+
+/// Set the log level
+///
+/// The levels are global and apply to the logs of all contexts, since the Rust logging macros
+/// don't know which context they're running for.
+///
+/// - `target` selects a module and its submodules, e.g. `sml::tlv`. The longest
+///   matching target wins. Without one, the level of all other targets is set.
+/// - `level` is a [CLogLevelFilter], fails with 4 for other values
+/// - up to 8 targets with up to 32 bytes each are supported
+/// - may be called at any time, also before [smr_init_logger]
+/// - `sml_set_log_level` lowers the level of a single context
+/// - release builds contain no formatted logs unless
+///   `CONFIG_SMARTMETER_RUST_LOGGER_RUNTIME_LEVELS` is set
+#[no_mangle]
+pub unsafe extern "C" fn smr_set_log_level(target: *const core::ffi::c_char, level: u32) -> u32 {
+    let level = match level_filter(level) {
+        Some(level) => level,
+        None => return 4,
+    };
+    if target.is_null() {
+        LEVEL.store(level as usize, core::sync::atomic::Ordering::Relaxed);
+        update_max_level();
+        return 0;
+    }
+
+    let target = core::ffi::CStr::from_ptr(target).to_bytes();
+    if target.len() > MAX_TARGET_LEN {
+        return 1;
+    }
+    let mut filter = TargetFilter {
+        target: [0; MAX_TARGET_LEN],
+        target_len: target.len(),
+        level,
+    };
+    filter.target[..target.len()].copy_from_slice(target);
+
+    let ret = with_filters(|filters| {
+        let slot = match filters
+            .iter()
+            .position(|slot| matches!(slot, Some(existing) if existing.target() == target))
+            .or_else(|| filters.iter().position(Option::is_none))
+        {
+            Some(slot) => slot,
+            None => return 2,
+        };
+        filters[slot] = Some(filter);
+        0
+    })
+    .unwrap_or(3);
+
+    update_max_level();
+    ret
+}
+
+/// Remove all target filters and reset the level to the default
+#[no_mangle]
+pub extern "C" fn smr_reset_log_levels() -> u32 {
+    LEVEL.store(
+        DEFAULT_LEVEL as usize,
+        core::sync::atomic::Ordering::Relaxed,
+    );
+    let ret = with_filters(|filters| *filters = [None; MAX_FILTERS]).map_or(3, |()| 0);
+
+    update_max_level();
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn levels() {
+        use super::CLogLevelFilter;
+
+        unsafe {
+            assert_eq!(
+                super::smr_set_log_level(core::ptr::null(), CLogLevelFilter::Warn as u32),
+                0
+            );
+            assert_eq!(
+                super::smr_set_log_level(b"sml\0".as_ptr().cast(), CLogLevelFilter::Info as u32),
+                0
+            );
+            assert_eq!(
+                super::smr_set_log_level(
+                    b"sml::tlv\0".as_ptr().cast(),
+                    CLogLevelFilter::Off as u32
+                ),
+                0
+            );
+            assert_eq!(
+                super::smr_set_log_level(
+                    b"sml::tl\0".as_ptr().cast(),
+                    CLogLevelFilter::Trace as u32
+                ),
+                0
+            );
+        }
+
+        assert_eq!(super::level("smartmeter::capi"), log::LevelFilter::Warn);
+        assert_eq!(super::level("sml"), log::LevelFilter::Info);
+        assert_eq!(super::level("sml::frame"), log::LevelFilter::Info);
+        assert_eq!(super::level("sml::tlv"), log::LevelFilter::Off);
+        assert_eq!(super::level("sml::tlv::inner"), log::LevelFilter::Off);
+        assert_eq!(super::level("smlx"), log::LevelFilter::Warn);
+        assert_eq!(unsafe { super::smr_set_log_level(core::ptr::null(), 6) }, 4);
+        assert_eq!(super::level("smlx"), log::LevelFilter::Warn);
+
+        // a context can only lower the levels
+        assert!(super::enabled(log::Level::Info, "sml"));
+        super::with_instance_level(log::LevelFilter::Warn, || {
+            assert!(!super::enabled(log::Level::Info, "sml"));
+            assert!(super::enabled(log::Level::Warn, "sml"));
+            assert!(!super::enabled(log::Level::Warn, "sml::tlv"));
+
+            // a context polled in parallel keeps the level and doesn't restore another one
+            super::with_instance_level(log::LevelFilter::Error, || {
+                assert!(super::enabled(log::Level::Warn, "sml"));
+            });
+            assert!(!super::enabled(log::Level::Info, "sml"));
+        });
+        assert!(super::enabled(log::Level::Info, "sml"));
+
+        assert_eq!(super::smr_reset_log_levels(), 0);
+        assert_eq!(super::level("sml::tlv"), super::DEFAULT_LEVEL);
+    }
+
+    #[test]
+    fn str_buffer() {
+        use core::fmt::Write as _;
+
+        let mut message = super::StrBuffer::<{ super::MAX_MESSAGE_LEN }>::new();
+        write!(&mut message, "{}", "a".repeat(super::MAX_MESSAGE_LEN - 2)).unwrap();
+        assert!(!message.truncated);
+
+        // doesn't split the two-byte char
+        write!(&mut message, "ä").unwrap();
+        assert!(message.truncated);
+
+        // the NUL is always there for C
+        let c = super::CStrRef::from(&message);
+        assert_eq!(c.len, super::MAX_MESSAGE_LEN - 2);
+        let c = unsafe { core::ffi::CStr::from_ptr(c.data) };
+        assert_eq!(c.to_str().unwrap(), "a".repeat(super::MAX_MESSAGE_LEN - 2));
+    }
+
+    #[cfg(feature = "deferred-log")]
+    #[test]
+    fn deferred() {
+        static RECORDS: std::sync::Mutex<Vec<Vec<u8>>> = std::sync::Mutex::new(Vec::new());
+
+        extern "C" fn sink(
+            _level: super::CLogLevel,
+            buf: *const core::ffi::c_void,
+            len: usize,
+        ) -> u32 {
+            let mut records = RECORDS.lock().unwrap();
+            if buf.is_null() {
+                records.push(Vec::new());
+            } else {
+                let data = unsafe { core::slice::from_raw_parts(buf as *const u8, len) };
+                records.last_mut().unwrap().extend_from_slice(data);
+            }
+            0
+        }
+
+        RECORDS.lock().unwrap().push(Vec::new());
+        assert_eq!(unsafe { super::smr_init_logger(Some(sink)) }, 0);
+
+        // other levels aren't logged, even if they're enabled
+        let trace = super::CLogLevelFilter::Trace as u32;
+        assert_eq!(
+            unsafe { super::smr_set_log_level(core::ptr::null(), trace) },
+            0
+        );
+        assert_eq!(log::max_level(), log::LevelFilter::Warn);
+
+        let mut context = core::mem::MaybeUninit::<crate::capi::ContextStorage>::uninit();
+        assert_eq!(
+            crate::capi::sml_init(
+                crate::capi::API_VERSION,
+                context.as_mut_ptr(),
+                0,
+                core::ptr::null_mut(),
+                None,
+                None,
+                None,
+                None,
+                None,
+                core::ptr::null_mut(),
+            ),
+            2
+        );
+
+        let exe = std::fs::read(std::env::current_exe().unwrap()).unwrap();
+        let table = dlog::decoder::Table::from_elf(&exe).unwrap();
+        let records = RECORDS.lock().unwrap();
+        assert!(records
+            .iter()
+            .filter(|record| !record.is_empty())
+            .map(|record| table.decode(record).unwrap())
+            .any(|record| record.target == "smartmeter::capi"
+                && record.message == "message callback is null"));
+    }
+}
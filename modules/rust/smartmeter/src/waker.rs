@@ -19,3 +19,46 @@ pub fn stub() -> core::task::Waker {
     let raw_waker = core::task::RawWaker::new(core::ptr::null(), &VTABLE_STUB);
     unsafe { core::task::Waker::from_raw(raw_waker) }
 }
+
+pub type WakeFn = extern "C" fn(user: *mut core::ffi::c_void);
+
+/// a C callback which is called when waking
+pub struct CWaker {
+    function: WakeFn,
+    user: *mut core::ffi::c_void,
+}
+
+static VTABLE_C: core::task::RawWakerVTable =
+    core::task::RawWakerVTable::new(c_clone, c_wake, c_wake_by_ref, c_drop);
+
+unsafe fn c_clone(data: *const ()) -> core::task::RawWaker {
+    core::task::RawWaker::new(data, &VTABLE_C)
+}
+
+unsafe fn c_wake(data: *const ()) {
+    c_wake_by_ref(data);
+}
+
+unsafe fn c_wake_by_ref(data: *const ()) {
+    let waker = &*(data as *const CWaker);
+    (waker.function)(waker.user);
+}
+
+unsafe fn c_drop(_: *const ()) {}
+
+impl CWaker {
+    pub fn new(function: WakeFn, user: *mut core::ffi::c_void) -> Self {
+        Self { function, user }
+    }
+
+    /// create a waker calling the C callback
+    ///
+    /// # Safety
+    ///
+    /// The waker only points to `self`, so neither the waker nor its clones must be used after
+    /// `self` was moved or dropped.
+    pub unsafe fn waker(&self) -> core::task::Waker {
+        let raw_waker = core::task::RawWaker::new(self as *const Self as *const (), &VTABLE_C);
+        core::task::Waker::from_raw(raw_waker)
+    }
+}
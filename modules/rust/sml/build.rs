@@ -6,9 +6,15 @@ use askama::Template as _;
 use convert_case::{Case, Casing};
 use lazy_static::lazy_static;
 use std::borrow::Borrow as _;
-use std::io::BufRead as _;
 use std::io::Write as _;
 
+#[path = "build/pdf.rs"]
+mod pdf;
+#[path = "build/schema.rs"]
+mod schema;
+
+use schema::{Field, ImplicitChoice, Schema, Sequence, SequenceOf, Type};
+
 /// turns any string into a valid rust identifier
 fn str2ident(string: &str, case: Case) -> String {
     lazy_static! {
@@ -83,47 +89,12 @@ fn type2opt(name: &str, optional: bool) -> String {
     }
 }
 
-#[derive(Debug, Default, serde::Serialize)]
-struct Variant {
-    ty: String,
-    value: u64,
-}
-
-impl AsRef<str> for Variant {
-    fn as_ref(&self) -> &str {
-        &self.ty
-    }
-}
-
-#[derive(Debug, Default, serde::Serialize)]
-struct Choice {
-    variants: std::collections::HashMap<String, Variant>,
-}
-
-#[derive(Debug, Default, serde::Serialize)]
-struct ImplicitChoice {
-    /// name, type
-    types: std::collections::HashMap<String, String>,
-}
-
-#[derive(Debug, Default, serde::Serialize)]
-struct Field {
-    name: String,
-    ty: String,
-    optional: bool,
-}
-
 impl Field {
     pub fn name(&self) -> String {
         str2ident(&self.name, Case::Snake)
     }
 }
 
-#[derive(Debug, Default, serde::Serialize)]
-struct Sequence {
-    fields: Vec<Field>,
-}
-
 impl Sequence {
     fn fieldstruct_ident(&self, name: &str, id: usize) -> String {
         if id == 0 {
@@ -134,25 +105,70 @@ impl Sequence {
     }
 }
 
-#[derive(Debug, Default, serde::Serialize)]
-struct SequenceOf {
-    /// name, type
-    types: std::collections::HashMap<String, String>,
+#[derive(askama::Template)]
+#[template(path = "visitor.rs", escape = "none")]
+struct VisitorTemplate<'a> {
+    base: &'a Template<'a>,
+    /// types with a visitor function which doesn't walk into them by default
+    recursive: &'a std::collections::BTreeSet<String>,
 }
 
-#[derive(Debug, serde::Serialize)]
-enum Type {
-    Choice(Choice),
-    ImplicitChoice(ImplicitChoice),
-    Sequence(Sequence),
-    SequenceOf(SequenceOf),
+impl<'a> VisitorTemplate<'a> {
+    pub fn visit_fn(&self, name: &str) -> String {
+        match self.base.type2rust(name).as_str() {
+            "crate::tlv::String" => "visit_octet_string".to_string(),
+            other => format!("visit_{}", other.to_case(Case::Snake)),
+        }
+    }
+
+    pub fn walk_fn(&self, name: &str) -> String {
+        format!("walk_{}", self.base.type2rust(name).to_case(Case::Snake))
+    }
+
+    /// return a statement which passes `value` of type `name` to the visitor
+    pub fn visit_call(&self, name: &str, parent: &str, fieldname: &str, value: &str) -> String {
+        let field = format!("Field {{ ty: {:?}, name: {:?} }}", parent, fieldname);
+
+        match self.base.type2rust(name).as_str() {
+            "u64" => format!("visitor.visit_unsigned({}, {}).await?", field, value),
+            "u8" | "u16" | "u32" => {
+                format!("visitor.visit_unsigned({}, {}.into()).await?", field, value)
+            }
+            "i64" => format!("visitor.visit_integer({}, {}).await?", field, value),
+            "i8" | "i16" | "i32" => {
+                format!("visitor.visit_integer({}, {}.into()).await?", field, value)
+            }
+            "bool" => format!("visitor.visit_boolean({}, {}).await?", field, value),
+            _ => format!(
+                "visitor.{}({}, {}).await?",
+                self.visit_fn(name),
+                field,
+                value
+            ),
+        }
+    }
+
+    pub fn visit_none_call(&self, parent: &str, fieldname: &str) -> String {
+        format!(
+            "visitor.visit_none(Field {{ ty: {:?}, name: {:?} }}).await?",
+            parent, fieldname
+        )
+    }
+}
+
+/// tables describing the schema at runtime, e.g. for annotating dumps
+#[derive(askama::Template)]
+#[template(path = "schema.rs", escape = "none")]
+struct SchemaTemplate<'a> {
+    types: &'a std::collections::BTreeMap<String, Type>,
+    typedefs: &'a std::collections::BTreeMap<String, String>,
 }
 
 #[derive(askama::Template)]
 #[template(path = "messages.rs", escape = "none")]
 struct Template<'a> {
-    types: &'a std::collections::HashMap<String, Type>,
-    typedefs: &'a std::collections::HashMap<String, String>,
+    types: &'a std::collections::BTreeMap<String, Type>,
+    typedefs: &'a std::collections::BTreeMap<String, String>,
 }
 
 impl<'a> Template<'a> {
@@ -176,6 +192,11 @@ impl<'a> Template<'a> {
         .to_string()
     }
 
+    pub fn is_implicit_choice(&self, name: &str) -> bool {
+        let name = self.typedefs.get(name).map(|s| s.as_str()).unwrap_or(name);
+        matches!(self.types.get(name), Some(Type::ImplicitChoice(_)))
+    }
+
     pub fn has_complexs<I: std::iter::Iterator<Item = Item>, Item: AsRef<str>>(
         &self,
         mut iter: I,
@@ -230,241 +251,123 @@ impl<'a> Template<'a> {
     }
 }
 
-fn is_cosem(name: &str) -> bool {
-    lazy_static! {
-        static ref RE: regex::Regex = regex::Regex::new(r"^SML_[a-zA-Z]*Cosem.*$").unwrap();
-    }
-
-    RE.is_match(name)
-}
-
-fn main() {
-    env_logger::init();
-
-    lazy_static! {
-        static ref RE_START: regex::Regex = regex::Regex::new(r"([a-zA-Z0-9_\.]+)\s*::=\s*([a-zA-Z0-9_\. ]+)").unwrap();
-        static ref RE_OPEN: regex::Regex = regex::Regex::new(r"^\s*\{\s*$").unwrap();
-        static ref RE_CLOSE: regex::Regex = regex::Regex::new(r"^\s*\}\s*$").unwrap();
-        static ref RE_CLOSE_WITH_COMMENT: regex::Regex = regex::Regex::new(r"^\s*\}\s+.*\)$").unwrap();
-        static ref RE_FIELD: regex::Regex = regex::Regex::new(r"^\s*([a-zA-Z0-9_\-\.]+)\s+(\[0x([0-9a-fA-F]+)\]\s+)?([a-zA-Z0-9_\.\? ]+)\s*,?\s*(\(.*)?$").unwrap();
+impl Schema {
+    fn resolve<'s>(&'s self, name: &'s str) -> &'s str {
+        self.typedefs.get(name).map(|s| s.as_str()).unwrap_or(name)
     }
 
-    let doc_pdf_path = std::path::Path::new("specification/TR-03109-1_Anlage_Feinspezifikation_Drahtgebundene_LMN-Schnittstelle_Teilb.pdf");
-    let out_path = std::path::PathBuf::from(std::env::var("OUT_DIR").unwrap());
-    let doc_txt_path = out_path.join("spec-sml2pdf.txt");
-
-    println!("cargo:rerun-if-changed={}", doc_pdf_path.to_str().unwrap());
-    println!("cargo:rerun-if-changed=templates");
-
-    std::process::Command::new("pdftotext")
-        .arg("-layout")
-        .arg(doc_pdf_path)
-        .arg(&doc_txt_path)
-        .spawn()
-        .expect("failed to spawn pdftotext")
-        .wait()
-        .expect("failed to wait for pdftotext")
-        .exit_ok()
-        .expect("pdftotext failed");
-
-    let f = std::fs::File::open(&doc_txt_path).expect("can't open file");
-    let mut lines = std::io::BufReader::new(f).lines().map(|l| l.unwrap());
-
-    let mut types = std::collections::HashMap::<String, Type>::new();
-    let mut typedefs = std::collections::HashMap::<String, String>::new();
-
-    while let Some(line_start) = lines.next() {
-        let (name, ty) = match RE_START.captures(&line_start) {
-            None => continue,
-            Some(caps) => (
-                caps.get(1).unwrap().as_str().trim(),
-                caps.get(2).unwrap().as_str().trim(),
-            ),
-        };
-
-        let line = lines.next().unwrap();
-        if !RE_OPEN.is_match(&line) {
-            if name != "EndOfSmlMsg" {
-                typedefs.insert(name.to_string(), ty.to_string());
+    /// the names of all types which are used by `ty`
+    fn referenced_types<'s>(&'s self, ty: &'s Type) -> Vec<&'s str> {
+        let names: Vec<&str> = match ty {
+            Type::Choice(c) => c.variants.values().map(|v| v.ty.as_str()).collect(),
+            Type::ImplicitChoice(ImplicitChoice { types })
+            | Type::SequenceOf(SequenceOf { types }) => {
+                types.values().map(|ty| ty.as_str()).collect()
             }
-            continue;
-        }
-
-        log::trace!("MATCH: {}", line_start);
-
-        let mut parsed_type = match ty {
-            "CHOICE" => Type::Choice(Choice::default()),
-            "IMPLICIT CHOICE" => Type::ImplicitChoice(ImplicitChoice::default()),
-            "SEQUENCE" => Type::Sequence(Sequence::default()),
-            "SEQUENCE OF" => Type::SequenceOf(SequenceOf::default()),
-            other => panic!("unsupported type: {}", other),
+            Type::Sequence(seq) => seq.fields.iter().map(|f| f.ty.as_str()).collect(),
         };
 
-        'mainloop: loop {
-            let line = lines.next().unwrap();
-            if RE_CLOSE.is_match(&line) {
-                break;
-            }
-            if line.is_empty() || line.trim() == "alle Datentyp aus GreenBook Seite 210 übernehmen!"
-            {
-                continue;
-            }
-            log::trace!("LINE: {}", line);
+        names
+            .into_iter()
+            .map(|name| self.resolve(name))
+            .filter(|name| self.types.contains_key(*name))
+            .collect()
+    }
 
-            let caps = RE_FIELD.captures(&line).unwrap();
+    /// types which contain themselves, directly or indirectly
+    fn recursive_types(&self) -> std::collections::BTreeSet<String> {
+        let mut recursive = std::collections::BTreeSet::new();
 
-            let name = caps.get(1).unwrap().as_str().trim();
-            let value = caps.get(3);
-            let (ty, optional) = {
-                let s: Vec<_> = caps.get(4).unwrap().as_str().trim().split(' ').collect();
+        for (name, ty) in &self.types {
+            let mut stack = self.referenced_types(ty);
+            let mut seen = std::collections::BTreeSet::new();
 
-                if s.len() > 1 && s.last().unwrap() == &"OPTIONAL" {
-                    (s[0..s.len() - 1].join(" ").trim().to_string(), true)
-                } else {
-                    (s.join(" "), false)
+            while let Some(other) = stack.pop() {
+                if other == name {
+                    recursive.insert(name.clone());
+                    break;
                 }
-            };
-            let ty = match ty.as_ref() {
-                "SML_Value10" => "SML_Value".to_string(),
-                "Octet String9" => "Octet String".to_string(),
-                "boolean" => "Boolean".to_string(),
-                _ => ty,
-            };
-            let comment = caps.get(5).map(|v| v.as_str());
-
-            if let Some(comment) = comment {
-                if !comment.ends_with(')') {
-                    loop {
-                        let line = lines.next().unwrap();
-                        if line.ends_with(')') {
-                            if RE_CLOSE_WITH_COMMENT.is_match(&line) {
-                                break 'mainloop;
-                            } else {
-                                break;
-                            }
-                        }
-                    }
+                if seen.insert(other) {
+                    stack.append(&mut self.referenced_types(&self.types[other]));
                 }
             }
+        }
 
-            match &mut parsed_type {
-                Type::Sequence(seq) => {
-                    if value.is_some() {
-                        panic!("sequence fields can't have values");
-                    }
-                    seq.fields.push(Field {
-                        name: name.to_string(),
-                        ty,
-                        optional,
-                    });
-                }
-                Type::Choice(c) => {
-                    if optional {
-                        panic!("choice variants can't be optional");
-                    }
-
-                    let value: u64 = u64::from_str_radix(value.unwrap().as_str(), 16).unwrap();
+        recursive
+    }
+}
 
-                    if name != "SetProcParameterResponse" && !is_cosem(&ty) {
-                        c.variants.insert(name.to_string(), Variant { ty, value });
-                    }
-                }
-                Type::ImplicitChoice(c) => {
-                    if value.is_some() {
-                        panic!("implicit choices can't have values");
-                    }
-                    if optional {
-                        panic!("implicit choice variants can't be optional");
-                    }
-                    c.types.insert(name.to_string(), ty);
-                }
-                Type::SequenceOf(seq) => {
-                    if value.is_some() {
-                        panic!("sequence-ofs can't have values");
-                    }
-                    if optional {
-                        panic!("sequence-ofs can't be optional");
-                    }
-                    seq.types.insert(name.to_string(), ty);
-                }
-            }
-        }
+fn main() {
+    env_logger::init();
 
-        if name == "..."
-            || name == "Boolean"
-            || name.starts_with("Unsigned")
-            || name.starts_with("Integer")
-            // this one has an incomplete specification
-            || is_cosem(name)
-        {
-            continue;
-        }
+    let doc_pdf_path = std::path::Path::new("specification/TR-03109-1_Anlage_Feinspezifikation_Drahtgebundene_LMN-Schnittstelle_Teilb.pdf");
+    let schema_path = std::path::Path::new("specification/sml.asn1");
+    let out_path = std::path::PathBuf::from(std::env::var("OUT_DIR").unwrap());
 
-        types.insert(name.to_string(), parsed_type);
-    }
+    println!("cargo:rerun-if-changed={}", schema_path.to_str().unwrap());
+    println!("cargo:rerun-if-changed=templates");
+    println!("cargo:rerun-if-env-changed=SML_REGENERATE_SCHEMA");
+    println!("cargo:rerun-if-env-changed=SML_EXTRA_SCHEMAS");
+
+    let mut schema = if std::env::var_os("SML_REGENERATE_SCHEMA").is_some() {
+        println!("cargo:rerun-if-changed={}", doc_pdf_path.to_str().unwrap());
+
+        // the source tree may be read-only, `scripts/sml-regenerate-schema` copies it from here
+        let schema = pdf::parse(doc_pdf_path, &out_path);
+        let regenerated_path = out_path.join("sml.asn1");
+        std::fs::write(&regenerated_path, schema.to_text()).expect("can't write schema");
+        println!(
+            "cargo:warning=regenerated schema: {}",
+            regenerated_path.display()
+        );
+        schema
+    } else {
+        let text = std::fs::read_to_string(schema_path).expect("can't read schema");
+        Schema::parse(&text, schema_path.to_str().unwrap())
+    };
 
-    // validate
-    for (name, parsed_type) in &types {
-        match parsed_type {
-            Type::Sequence(_) => {}
-            Type::Choice(_) => {}
-            Type::ImplicitChoice(c) => {
-                let count = c
-                    .types
-                    .values()
-                    .filter(|&variantty| types.get(variantty).is_some())
-                    .count();
-                if count > 1 {
-                    panic!("choice {} has {} list types", name, count);
-                }
+    // additional schemas for meters which deviate from the specification
+    if let Some(paths) = std::env::var_os("SML_EXTRA_SCHEMAS") {
+        // relative to this crate, no matter where cargo was started
+        let manifest_dir = std::path::PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").unwrap());
 
-                for variantty in c.types.values() {
-                    let count = c
-                        .types
-                        .values()
-                        .filter(|&variantty2| variantty2 == variantty)
-                        .count();
-                    if count > 1 {
-                        panic!(
-                            "choice {} has {} variants of type {}",
-                            name, count, variantty
-                        );
-                    }
-                }
-            }
-            Type::SequenceOf(seq) => {
-                let count = seq
-                    .types
-                    .values()
-                    .filter(|&variantty| types.get(variantty).is_some())
-                    .count();
-                if count > 1 {
-                    panic!("sequenceof {} has {} list types", name, count);
-                }
+        for path in std::env::split_paths(&paths) {
+            let path = manifest_dir.join(path);
+            println!("cargo:rerun-if-changed={}", path.display());
 
-                for variantty in seq.types.values() {
-                    let count = seq
-                        .types
-                        .values()
-                        .filter(|&variantty2| variantty2 == variantty)
-                        .count();
-                    if count > 1 {
-                        panic!(
-                            "sequenceof {} has {} variants of type {}",
-                            name, count, variantty
-                        );
-                    }
-                }
-            }
+            let text = std::fs::read_to_string(&path)
+                .unwrap_or_else(|e| panic!("can't read schema {}: {}", path.display(), e));
+            schema.merge(Schema::parse(&text, &path.display().to_string()));
         }
     }
 
+    schema::validate(&schema);
+
     let template = Template {
-        types: &types,
-        typedefs: &typedefs,
+        types: &schema.types,
+        typedefs: &schema.typedefs,
     };
     let code = template.render().unwrap();
 
     let mut f = std::fs::File::create(out_path.join("messages.rs")).unwrap();
     f.write_all(code.as_bytes()).unwrap();
+
+    let recursive = schema.recursive_types();
+    let visitor = VisitorTemplate {
+        base: &template,
+        recursive: &recursive,
+    };
+    let code = visitor.render().unwrap();
+
+    let mut f = std::fs::File::create(out_path.join("visitor.rs")).unwrap();
+    f.write_all(code.as_bytes()).unwrap();
+
+    let tables = SchemaTemplate {
+        types: &schema.types,
+        typedefs: &schema.typedefs,
+    };
+    let code = tables.render().unwrap();
+
+    let mut f = std::fs::File::create(out_path.join("schema.rs")).unwrap();
+    f.write_all(code.as_bytes()).unwrap();
 }
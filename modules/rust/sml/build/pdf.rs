@@ -0,0 +1,175 @@
+//! extracts the SML types from the specification PDF
+//!
+//! This needs `pdftotext` from poppler and is only used to regenerate the checked-in schema.
+
+use crate::schema::{Choice, Field, ImplicitChoice, Schema, Sequence, SequenceOf, Type, Variant};
+use lazy_static::lazy_static;
+use std::io::BufRead as _;
+
+fn is_cosem(name: &str) -> bool {
+    lazy_static! {
+        static ref RE: regex::Regex = regex::Regex::new(r"^SML_[a-zA-Z]*Cosem.*$").unwrap();
+    }
+
+    RE.is_match(name)
+}
+
+pub(crate) fn parse(doc_pdf_path: &std::path::Path, out_path: &std::path::Path) -> Schema {
+    lazy_static! {
+        static ref RE_START: regex::Regex = regex::Regex::new(r"([a-zA-Z0-9_\.]+)\s*::=\s*([a-zA-Z0-9_\. ]+)").unwrap();
+        static ref RE_OPEN: regex::Regex = regex::Regex::new(r"^\s*\{\s*$").unwrap();
+        static ref RE_CLOSE: regex::Regex = regex::Regex::new(r"^\s*\}\s*$").unwrap();
+        static ref RE_CLOSE_WITH_COMMENT: regex::Regex = regex::Regex::new(r"^\s*\}\s+.*\)$").unwrap();
+        static ref RE_FIELD: regex::Regex = regex::Regex::new(r"^\s*([a-zA-Z0-9_\-\.]+)\s+(\[0x([0-9a-fA-F]+)\]\s+)?([a-zA-Z0-9_\.\? ]+)\s*,?\s*(\(.*)?$").unwrap();
+    }
+
+    let doc_txt_path = out_path.join("spec-sml2pdf.txt");
+
+    std::process::Command::new("pdftotext")
+        .arg("-layout")
+        .arg(doc_pdf_path)
+        .arg(&doc_txt_path)
+        .spawn()
+        .expect("failed to spawn pdftotext")
+        .wait()
+        .expect("failed to wait for pdftotext")
+        .exit_ok()
+        .expect("pdftotext failed");
+
+    let f = std::fs::File::open(&doc_txt_path).expect("can't open file");
+    let mut lines = std::io::BufReader::new(f).lines().map(|l| l.unwrap());
+
+    let mut schema = Schema::default();
+
+    while let Some(line_start) = lines.next() {
+        let (name, ty) = match RE_START.captures(&line_start) {
+            None => continue,
+            Some(caps) => (
+                caps.get(1).unwrap().as_str().trim(),
+                caps.get(2).unwrap().as_str().trim(),
+            ),
+        };
+
+        let line = lines.next().unwrap();
+        if !RE_OPEN.is_match(&line) {
+            if name != "EndOfSmlMsg" {
+                schema.typedefs.insert(name.to_string(), ty.to_string());
+            }
+            continue;
+        }
+
+        log::trace!("MATCH: {}", line_start);
+
+        let mut parsed_type = match ty {
+            "CHOICE" => Type::Choice(Choice::default()),
+            "IMPLICIT CHOICE" => Type::ImplicitChoice(ImplicitChoice::default()),
+            "SEQUENCE" => Type::Sequence(Sequence::default()),
+            "SEQUENCE OF" => Type::SequenceOf(SequenceOf::default()),
+            other => panic!("unsupported type: {}", other),
+        };
+
+        'mainloop: loop {
+            let line = lines.next().unwrap();
+            if RE_CLOSE.is_match(&line) {
+                break;
+            }
+            if line.is_empty() || line.trim() == "alle Datentyp aus GreenBook Seite 210 übernehmen!"
+            {
+                continue;
+            }
+            log::trace!("LINE: {}", line);
+
+            let caps = RE_FIELD.captures(&line).unwrap();
+
+            let name = caps.get(1).unwrap().as_str().trim();
+            let value = caps.get(3);
+            let (ty, optional) = {
+                let s: Vec<_> = caps.get(4).unwrap().as_str().trim().split(' ').collect();
+
+                if s.len() > 1 && s.last().unwrap() == &"OPTIONAL" {
+                    (s[0..s.len() - 1].join(" ").trim().to_string(), true)
+                } else {
+                    (s.join(" "), false)
+                }
+            };
+            let ty = match ty.as_ref() {
+                "SML_Value10" => "SML_Value".to_string(),
+                "Octet String9" => "Octet String".to_string(),
+                "boolean" => "Boolean".to_string(),
+                _ => ty,
+            };
+            let comment = caps.get(5).map(|v| v.as_str());
+
+            if let Some(comment) = comment {
+                if !comment.ends_with(')') {
+                    loop {
+                        let line = lines.next().unwrap();
+                        if line.ends_with(')') {
+                            if RE_CLOSE_WITH_COMMENT.is_match(&line) {
+                                break 'mainloop;
+                            } else {
+                                break;
+                            }
+                        }
+                    }
+                }
+            }
+
+            match &mut parsed_type {
+                Type::Sequence(seq) => {
+                    if value.is_some() {
+                        panic!("sequence fields can't have values");
+                    }
+                    seq.fields.push(Field {
+                        name: name.to_string(),
+                        ty,
+                        optional,
+                    });
+                }
+                Type::Choice(c) => {
+                    if optional {
+                        panic!("choice variants can't be optional");
+                    }
+
+                    let value: u64 = u64::from_str_radix(value.unwrap().as_str(), 16).unwrap();
+
+                    if name != "SetProcParameterResponse" && !is_cosem(&ty) {
+                        c.variants.insert(name.to_string(), Variant { ty, value });
+                    }
+                }
+                Type::ImplicitChoice(c) => {
+                    if value.is_some() {
+                        panic!("implicit choices can't have values");
+                    }
+                    if optional {
+                        panic!("implicit choice variants can't be optional");
+                    }
+                    c.types.insert(name.to_string(), ty);
+                }
+                Type::SequenceOf(seq) => {
+                    if value.is_some() {
+                        panic!("sequence-ofs can't have values");
+                    }
+                    if optional {
+                        panic!("sequence-ofs can't be optional");
+                    }
+                    seq.types.insert(name.to_string(), ty);
+                }
+            }
+        }
+
+        if name == "..."
+            || name == "Boolean"
+            || name.starts_with("Unsigned")
+            || name.starts_with("Integer")
+            // this one has an incomplete specification
+            || is_cosem(name)
+        {
+            continue;
+        }
+
+        schema.types.insert(name.to_string(), parsed_type);
+    }
+
+    schema
+}
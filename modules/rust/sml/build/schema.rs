@@ -0,0 +1,452 @@
+//! reads and writes the checked-in schema
+//!
+//! The format is a subset of the ASN.1 notation used by the specification:
+//!
+//! ```text
+//! -- comment
+//! SML_Timestamp ::= Unsigned32
+//!
+//! SML_Time ::= CHOICE
+//! {
+//!     secIndex [0x01] Unsigned32,
+//!     timestamp [0x02] SML_Timestamp
+//! }
+//! ```
+//!
+//! Sequence fields may be followed by `OPTIONAL`, choice variants need a tag.
+
+use std::fmt::Write as _;
+
+const HEADER: &str = "\
+-- SML types, extracted from the specification in this directory.
+--
+-- Don't edit this file by hand. To regenerate it, install `pdftotext` from
+-- poppler and run `scripts/sml-regenerate-schema`.
+";
+
+#[derive(Debug, Default, serde::Serialize)]
+pub(crate) struct Variant {
+    pub(crate) ty: String,
+    pub(crate) value: u64,
+}
+
+impl AsRef<str> for Variant {
+    fn as_ref(&self) -> &str {
+        &self.ty
+    }
+}
+
+#[derive(Debug, Default, serde::Serialize)]
+pub(crate) struct Choice {
+    pub(crate) variants: std::collections::BTreeMap<String, Variant>,
+}
+
+#[derive(Debug, Default, serde::Serialize)]
+pub(crate) struct ImplicitChoice {
+    /// name, type
+    pub(crate) types: std::collections::BTreeMap<String, String>,
+}
+
+#[derive(Debug, Default, serde::Serialize)]
+pub(crate) struct Field {
+    pub(crate) name: String,
+    pub(crate) ty: String,
+    pub(crate) optional: bool,
+}
+
+#[derive(Debug, Default, serde::Serialize)]
+pub(crate) struct Sequence {
+    pub(crate) fields: Vec<Field>,
+}
+
+#[derive(Debug, Default, serde::Serialize)]
+pub(crate) struct SequenceOf {
+    /// name, type
+    pub(crate) types: std::collections::BTreeMap<String, String>,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub(crate) enum Type {
+    Choice(Choice),
+    ImplicitChoice(ImplicitChoice),
+    Sequence(Sequence),
+    SequenceOf(SequenceOf),
+}
+
+/// all types of the specification
+#[derive(Debug, Default)]
+pub(crate) struct Schema {
+    pub(crate) types: std::collections::BTreeMap<String, Type>,
+    pub(crate) typedefs: std::collections::BTreeMap<String, String>,
+}
+
+pub(crate) fn validate(schema: &Schema) {
+    for (name, parsed_type) in &schema.types {
+        match parsed_type {
+            Type::Sequence(_) => {}
+            Type::Choice(c) => {
+                for (variantname, variant) in &c.variants {
+                    if let Some((othername, _)) = c.variants.iter().find(|(othername, other)| {
+                        other.value == variant.value && *othername != variantname
+                    }) {
+                        panic!(
+                            "choice {} has variants {} and {} with tag {:#x}",
+                            name, variantname, othername, variant.value
+                        );
+                    }
+                }
+            }
+            Type::ImplicitChoice(c) => {
+                let count = c
+                    .types
+                    .values()
+                    .filter(|&variantty| schema.types.get(variantty).is_some())
+                    .count();
+                if count > 1 {
+                    panic!("choice {} has {} list types", name, count);
+                }
+
+                for variantty in c.types.values() {
+                    let count = c
+                        .types
+                        .values()
+                        .filter(|&variantty2| variantty2 == variantty)
+                        .count();
+                    if count > 1 {
+                        panic!(
+                            "choice {} has {} variants of type {}",
+                            name, count, variantty
+                        );
+                    }
+                }
+            }
+            Type::SequenceOf(seq) => {
+                let count = seq
+                    .types
+                    .values()
+                    .filter(|&variantty| schema.types.get(variantty).is_some())
+                    .count();
+                if count > 1 {
+                    panic!("sequenceof {} has {} list types", name, count);
+                }
+
+                for variantty in seq.types.values() {
+                    let count = seq
+                        .types
+                        .values()
+                        .filter(|&variantty2| variantty2 == variantty)
+                        .count();
+                    if count > 1 {
+                        panic!(
+                            "sequenceof {} has {} variants of type {}",
+                            name, count, variantty
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn kind(ty: &Type) -> &'static str {
+    match ty {
+        Type::Choice(_) => "CHOICE",
+        Type::ImplicitChoice(_) => "IMPLICIT CHOICE",
+        Type::Sequence(_) => "SEQUENCE",
+        Type::SequenceOf(_) => "SEQUENCE OF",
+    }
+}
+
+impl Schema {
+    pub(crate) fn to_text(&self) -> String {
+        let mut out = String::from(HEADER);
+
+        for (name, ty) in &self.typedefs {
+            writeln!(out, "\n{} ::= {}", name, ty).unwrap();
+        }
+
+        for (name, ty) in &self.types {
+            let fields: Vec<String> = match ty {
+                Type::Choice(c) => c
+                    .variants
+                    .iter()
+                    .map(|(name, v)| format!("{} [0x{:02X}] {}", name, v.value, v.ty))
+                    .collect(),
+                Type::ImplicitChoice(ImplicitChoice { types })
+                | Type::SequenceOf(SequenceOf { types }) => types
+                    .iter()
+                    .map(|(name, ty)| format!("{} {}", name, ty))
+                    .collect(),
+                Type::Sequence(seq) => seq
+                    .fields
+                    .iter()
+                    .map(|f| {
+                        let optional = if f.optional { " OPTIONAL" } else { "" };
+                        format!("{} {}{}", f.name, f.ty, optional)
+                    })
+                    .collect(),
+            };
+
+            writeln!(out, "\n{} ::= {}\n{{", name, kind(ty)).unwrap();
+            writeln!(out, "    {}", fields.join(",\n    ")).unwrap();
+            writeln!(out, "}}").unwrap();
+        }
+
+        out
+    }
+
+    /// parse a schema, `source` is only used for error messages
+    pub(crate) fn parse(text: &str, source: &str) -> Self {
+        let mut schema = Schema::default();
+        let mut lines = text
+            .lines()
+            .enumerate()
+            .map(|(i, line)| (i + 1, line.split("--").next().unwrap().trim()))
+            .filter(|(_, line)| !line.is_empty());
+
+        while let Some((lineno, line)) = lines.next() {
+            let (name, ty) = line
+                .split_once("::=")
+                .unwrap_or_else(|| panic!("{}:{}: expected `::=`", source, lineno));
+            let (name, ty) = (name.trim().to_string(), ty.trim());
+
+            let mut parsed_type = match ty {
+                "CHOICE" => Type::Choice(Choice::default()),
+                "IMPLICIT CHOICE" => Type::ImplicitChoice(ImplicitChoice::default()),
+                "SEQUENCE" => Type::Sequence(Sequence::default()),
+                "SEQUENCE OF" => Type::SequenceOf(SequenceOf::default()),
+                other => {
+                    schema.typedefs.insert(name, other.to_string());
+                    continue;
+                }
+            };
+
+            match lines.next() {
+                Some((_, "{")) => (),
+                Some((lineno, _)) => panic!("{}:{}: expected `{{`", source, lineno),
+                None => panic!("{}: unexpected end of file", source),
+            }
+
+            loop {
+                let (lineno, line) = lines
+                    .next()
+                    .unwrap_or_else(|| panic!("{}: unexpected end of file", source));
+                if line == "}" {
+                    break;
+                }
+
+                let err = |msg: &str| -> ! { panic!("{}:{}: {}", source, lineno, msg) };
+
+                let mut words = line.trim_end_matches(',').split_whitespace();
+                let fieldname = words.next().unwrap().to_string();
+                let mut words = words.peekable();
+                let value = match words.peek() {
+                    Some(w) if w.starts_with('[') => {
+                        let w = words.next().unwrap();
+                        let hex = w
+                            .strip_prefix("[0x")
+                            .and_then(|w| w.strip_suffix(']'))
+                            .unwrap_or_else(|| err("invalid tag"));
+                        Some(u64::from_str_radix(hex, 16).unwrap_or_else(|_| err("invalid tag")))
+                    }
+                    _ => None,
+                };
+                let mut words: Vec<_> = words.collect();
+                let optional = words.last() == Some(&"OPTIONAL");
+                if optional {
+                    words.pop();
+                }
+                if words.is_empty() {
+                    err("missing type");
+                }
+                let fieldty = words.join(" ");
+
+                match &mut parsed_type {
+                    Type::Choice(c) => {
+                        let value = value.unwrap_or_else(|| err("choice variants need a tag"));
+                        if optional {
+                            err("choice variants can't be optional");
+                        }
+                        c.variants.insert(fieldname, Variant { ty: fieldty, value });
+                    }
+                    Type::Sequence(seq) => {
+                        if value.is_some() {
+                            err("sequence fields can't have tags");
+                        }
+                        seq.fields.push(Field {
+                            name: fieldname,
+                            ty: fieldty,
+                            optional,
+                        });
+                    }
+                    Type::ImplicitChoice(ImplicitChoice { types })
+                    | Type::SequenceOf(SequenceOf { types }) => {
+                        if value.is_some() || optional {
+                            err("only plain types are allowed here");
+                        }
+                        types.insert(fieldname, fieldty);
+                    }
+                }
+            }
+
+            schema.types.insert(name, parsed_type);
+        }
+
+        schema
+    }
+
+    /// add the types of `other`, e.g. vendor extensions
+    ///
+    /// Variants of choices are added to the existing choice, replacing variants with the same
+    /// name. All other types and typedefs replace the existing definition.
+    pub(crate) fn merge(&mut self, other: Schema) {
+        self.typedefs.extend(other.typedefs);
+
+        for (name, ty) in other.types {
+            match (self.types.get_mut(&name), ty) {
+                (Some(Type::Choice(existing)), Type::Choice(c)) => {
+                    existing.variants.extend(c.variants);
+                }
+                (_, ty) => {
+                    self.types.insert(name, ty);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Schema, Type};
+
+    const BASE: &str = "
+        SML_Timestamp ::= Unsigned32
+
+        SML_Time ::= CHOICE
+        {
+            secIndex [0x01] Unsigned32,
+            timestamp [0x02] SML_Timestamp
+        }
+
+        SML_Signature ::= SEQUENCE
+        {
+            sig Octet String
+        }
+    ";
+
+    fn merged(extra: &str) -> Schema {
+        let mut schema = Schema::parse(BASE, "base");
+        schema.merge(Schema::parse(extra, "extra"));
+        schema
+    }
+
+    fn variants(schema: &Schema, name: &str) -> Vec<(String, u64)> {
+        match &schema.types[name] {
+            Type::Choice(c) => c
+                .variants
+                .iter()
+                .map(|(name, v)| (name.clone(), v.value))
+                .collect(),
+            other => panic!("{} isn't a choice: {:?}", name, other),
+        }
+    }
+
+    #[test]
+    fn roundtrip() {
+        let schema = Schema::parse(BASE, "base");
+        let text = schema.to_text();
+        assert_eq!(Schema::parse(&text, "text").to_text(), text);
+    }
+
+    #[test]
+    fn merge_choice() {
+        let schema = merged(
+            "
+            SML_Time ::= CHOICE
+            {
+                acmeTime [0x10] Unsigned64
+            }
+            ",
+        );
+        super::validate(&schema);
+
+        assert_eq!(
+            variants(&schema, "SML_Time"),
+            [
+                ("acmeTime".to_string(), 0x10),
+                ("secIndex".to_string(), 0x01),
+                ("timestamp".to_string(), 0x02),
+            ]
+        );
+    }
+
+    #[test]
+    fn merge_replaces() {
+        let schema = merged(
+            "
+            SML_Timestamp ::= Unsigned64
+
+            SML_Time ::= CHOICE
+            {
+                secIndex [0x03] Unsigned32
+            }
+
+            SML_Signature ::= SEQUENCE
+            {
+                sig Octet String,
+                keyId Unsigned8 OPTIONAL
+            }
+            ",
+        );
+        super::validate(&schema);
+
+        assert_eq!(schema.typedefs["SML_Timestamp"], "Unsigned64");
+        assert_eq!(
+            variants(&schema, "SML_Time"),
+            [
+                ("secIndex".to_string(), 0x03),
+                ("timestamp".to_string(), 0x02)
+            ]
+        );
+        match &schema.types["SML_Signature"] {
+            Type::Sequence(seq) => {
+                let fields: Vec<_> = seq
+                    .fields
+                    .iter()
+                    .map(|f| (f.name.as_str(), f.optional))
+                    .collect();
+                assert_eq!(fields, [("sig", false), ("keyId", true)]);
+            }
+            other => panic!("not a sequence: {:?}", other),
+        }
+    }
+
+    /// a vendor variant mustn't reuse the tag of an existing one
+    #[test]
+    #[should_panic(expected = "choice SML_Time has variants")]
+    fn merge_conflicting_tag() {
+        let schema = merged(
+            "
+            SML_Time ::= CHOICE
+            {
+                acmeTime [0x02] Unsigned64
+            }
+            ",
+        );
+        super::validate(&schema);
+    }
+
+    /// a choice can't be extended by something else, it gets replaced
+    #[test]
+    fn merge_different_kind() {
+        let schema = merged(
+            "
+            SML_Time ::= SEQUENCE
+            {
+                acmeTime Unsigned64
+            }
+            ",
+        );
+        assert!(matches!(schema.types["SML_Time"], Type::Sequence(_)));
+    }
+}
@@ -19,6 +19,10 @@ pub enum Error {
     },
     /// TLV lengths field doesn't fit into our datatype for it
     TlvLengthTooBig,
+    /// the data left to skip doesn't fit into our datatype for it, so the input is corrupted
+    ///
+    /// The reader lost its position and returns this for all further reads.
+    SkipOverflow,
     /// received end marker
     EndOfSmlMessage,
     /// received an unsupported TLV type
@@ -35,11 +39,26 @@ pub enum Error {
     },
     /// tried to read a value of a certain type but the end of the list was reached
     EndOfList,
-    /// the frame or message checksum doesn't match
+    /// the message checksum doesn't match
     ChecksumMismatch {
         rec: u16,
         calc: u16,
     },
+    /// the frame checksum doesn't match
+    CrcMismatch {
+        /// the checksum at the end of the frame
+        expected: u16,
+        /// the checksum of the received data
+        actual: u16,
+    },
+    /// the end of the frame has more than 3 fill bytes, or more than the last block has
+    InvalidFillBytes {
+        num: u8,
+    },
+    /// an escape sequence other than the start and end of a frame or an escaped `1b1b1b1b`
+    UnknownEscape {
+        code: u8,
+    },
     /// a SML choice has an unsupported tag
     UnsupportedTag {
         tag: u32,
@@ -53,6 +72,15 @@ pub enum Error {
     /// Some APIs can't restrict usage at compile-time. Those return `CantParseTwice` on the second
     /// attempt
     CantParseTwice,
+    /// the output buffer is too small for the encoded data
+    BufferFull,
+    /// lists are nested deeper than supported, see [crate::tlv::DUMP_MAX_DEPTH]
+    NestingTooDeep,
+    /// writing to a [core::fmt::Write] failed
+    Fmt,
+    /// the schema uses something the generated code doesn't support, e.g. a `SEQUENCE OF` with
+    /// several element types
+    Unimplemented,
 
     Io(io::Error),
     TryFromIntError,
@@ -69,3 +97,145 @@ impl From<core::num::TryFromIntError> for Error {
         Self::TryFromIntError
     }
 }
+
+impl From<core::fmt::Error> for Error {
+    fn from(_source: core::fmt::Error) -> Self {
+        Self::Fmt
+    }
+}
+
+/// generates [core::fmt::Display] and, for deferred logs, [dlog::Format] from one table
+macro_rules! messages {
+    ($($pattern:pat => ($fmt:literal $(, $arg:expr)*),)*) => {
+        impl core::fmt::Display for Error {
+            fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+                match self {
+                    $($pattern => write!(f, $fmt $(, $arg)*),)*
+                }
+            }
+        }
+
+        dlog::deferred! {
+            impl dlog::Format for Error {
+                fn format(&self, e: &mut dlog::Encoder) {
+                    match self {
+                        $($pattern => dlog::write!(e, $fmt $(, $arg)*),)*
+                    }
+                }
+            }
+        }
+    };
+}
+
+messages! {
+    Self::UnexpectedTlv { ty, len } => ("unexpected TLV {:?} with length {}", ty, len),
+    Self::ShortTlvLength { len } => ("TLV length {} is too short", len),
+    Self::MidMessageEndMarker { remaining } => (
+        "end of message with {} items of the list remaining",
+        remaining
+    ),
+    Self::MultibyteTlvReservedType { ty } => ("multibyte TLV header with reserved type {:#x}", ty),
+    Self::TlvLengthTooBig => ("TLV length is too big"),
+    Self::SkipOverflow => ("too much data to skip"),
+    Self::EndOfSmlMessage => ("end of message"),
+    Self::UnsupportedTlvType { ty } => ("unsupported TLV type {:#x}", ty),
+    Self::UnexpectedValue => ("unexpected value"),
+    Self::UnsupportedLen { len } => ("unsupported length {}", len),
+    Self::EndOfList => ("end of list"),
+    Self::ChecksumMismatch { rec, calc } => (
+        "checksum mismatch: received {:#06x}, calculated {:#06x}",
+        rec,
+        calc
+    ),
+    Self::CrcMismatch { expected, actual } => (
+        "frame checksum mismatch: expected {:#06x}, calculated {:#06x}",
+        expected,
+        actual
+    ),
+    Self::InvalidFillBytes { num } => ("invalid number of fill bytes {}", num),
+    Self::UnknownEscape { code } => ("unknown escape sequence {:#04x}", code),
+    Self::UnsupportedTag { tag } => ("unsupported tag {:#x}", tag),
+    Self::WrongBufferSize => ("wrong buffer size"),
+    Self::NoneTlv => ("unexpected None"),
+    Self::CantParseTwice => ("can't parse twice"),
+    Self::BufferFull => ("buffer full"),
+    Self::NestingTooDeep => ("lists are nested too deep"),
+    Self::Fmt => ("formatting failed"),
+    Self::Unimplemented => ("not implemented"),
+    Self::Io(io) => ("io error: {}", IoError(io)),
+    Self::TryFromIntError => ("integer out of range"),
+}
+
+/// an [io::Error] in a message
+///
+/// `io` doesn't know about deferred logs, so they only contain which error it is instead of
+/// formatting it on the device.
+struct IoError<'a>(&'a io::Error);
+
+impl core::fmt::Display for IoError<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+dlog::deferred! {
+    impl dlog::Format for IoError<'_> {
+        fn format(&self, e: &mut dlog::Encoder) {
+            match self.0 {
+                io::Error::NativeUnsigned { ret } => dlog::write!(e, "NativeUnsigned({})", ret),
+                io::Error::UnexpectedEof => dlog::write!(e, "UnexpectedEof"),
+                io::Error::WriteZero => dlog::write!(e, "WriteZero"),
+                io::Error::TimedOut => dlog::write!(e, "TimedOut"),
+                io::Error::Overrun => dlog::write!(e, "Overrun"),
+                io::Error::Framing => dlog::write!(e, "Framing"),
+                io::Error::WouldBlock => dlog::write!(e, "WouldBlock"),
+                io::Error::Unimplemented => dlog::write!(e, "Unimplemented"),
+                io::Error::Unknown => dlog::write!(e, "Unknown"),
+                // the variants of the host and of `embedded-io-async` depend on the features of `io`
+                #[allow(unreachable_patterns)]
+                _ => dlog::write!(e, "Other"),
+            }
+        }
+    }
+}
+
+impl core::error::Error for Error {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// an [Error] while reading a frame, with the position where it happened
+#[derive(Debug)]
+pub struct FrameError {
+    /// the number of bytes of the frame which were read, including the start sequence
+    ///
+    /// The frame is read in blocks of 4 bytes and one block is buffered for detecting the end of
+    /// the frame, so the data causing the error can be up to 8 bytes earlier. It's 0 if the
+    /// error happened before the start of a frame.
+    pub offset: usize,
+    pub error: Error,
+}
+
+impl core::fmt::Display for FrameError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{} at byte {} of the frame", self.error, self.offset)
+    }
+}
+
+dlog::deferred! {
+    impl dlog::Format for FrameError {
+        fn format(&self, e: &mut dlog::Encoder) {
+            dlog::write!(e, "{} at byte {} of the frame", self.error, self.offset)
+        }
+    }
+}
+
+impl core::error::Error for FrameError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
@@ -1,9 +1,8 @@
 //! handle framing and call [crate::message] module when one was received
 
 use crate::types::FromTlvList as _;
-use crate::Error;
 use crate::ReaderEnded as _;
-use io::AsyncReadExt as _;
+use crate::{Error, FrameError};
 
 /// a buffer of 4 bytes
 ///
@@ -106,6 +105,13 @@ pub struct CheckingReader<'a, R> {
     in_esc: bool,
     bufferlist: BufferList,
     digest: crc::Digest<'static, u16>,
+    /// the number of bytes read, including the start sequence
+    offset: usize,
+    /// why the frame is invalid
+    ///
+    /// Reads can only fail with an [io::Error], so they return [io::Error::Unknown] and
+    /// [CheckingReader::frame_error] replaces it with this.
+    invalid: Option<Error>,
 }
 
 impl<'a, R> CheckingReader<'a, R> {
@@ -122,6 +128,16 @@ impl<'a, R> CheckingReader<'a, R> {
                 buffers_swapped: false,
             },
             digest,
+            offset: 8,
+            invalid: None,
+        }
+    }
+
+    /// adds the position to `error`, or replaces it with the reason why the frame is invalid
+    fn frame_error(&mut self, error: Error) -> FrameError {
+        FrameError {
+            offset: self.offset,
+            error: self.invalid.take().unwrap_or(error),
         }
     }
 }
@@ -150,7 +166,12 @@ impl<'a, R: io::AsyncRead + Unpin> io::AsyncRead for CheckingReader<'a, R> {
                         .as_mut()
                         .poll_read(cx, buffer.data_free_mut()))
                     .inspect_err(|_| *me.state = ReaderState::End)?;
+                    if num == 0 {
+                        *me.state = ReaderState::End;
+                        return core::task::Poll::Ready(Err(io::Error::UnexpectedEof));
+                    }
                     buffer.add_read(num);
+                    *me.offset += num;
 
                     if buffer.read() < buffer.len() {
                         continue;
@@ -185,12 +206,16 @@ impl<'a, R: io::AsyncRead + Unpin> io::AsyncRead for CheckingReader<'a, R> {
                                 let crc_calc = digest.finalize();
 
                                 if crc_rec != crc_calc {
-                                    log::error!(
+                                    dlog::error!(
                                         "frame CRC mismatch: calc={:X} rec={:X}",
                                         crc_calc,
                                         crc_rec
                                     );
                                     *me.state = ReaderState::End;
+                                    *me.invalid = Some(Error::CrcMismatch {
+                                        expected: crc_rec,
+                                        actual: crc_calc,
+                                    });
                                     return Err(io::Error::Unknown).into();
                                 }
 
@@ -198,21 +223,25 @@ impl<'a, R: io::AsyncRead + Unpin> io::AsyncRead for CheckingReader<'a, R> {
                                 let buffer = me.bufferlist.get_mut(0);
 
                                 if num_fillbytes > 3 {
-                                    log::error!(
+                                    dlog::error!(
                                         "unsupported number of fillbytes: {}",
                                         num_fillbytes
                                     );
                                     *me.state = ReaderState::End;
+                                    *me.invalid =
+                                        Some(Error::InvalidFillBytes { num: num_fillbytes });
                                     return Err(io::Error::Unknown).into();
                                 }
 
                                 if buffer.read() < num_fillbytes.into() {
-                                    log::error!(
+                                    dlog::error!(
                                         "data-length={} can't be less than given fillbytes={}",
                                         buffer.read(),
                                         num_fillbytes
                                     );
                                     *me.state = ReaderState::End;
+                                    *me.invalid =
+                                        Some(Error::InvalidFillBytes { num: num_fillbytes });
                                     return Err(io::Error::Unknown).into();
                                 }
 
@@ -224,9 +253,10 @@ impl<'a, R: io::AsyncRead + Unpin> io::AsyncRead for CheckingReader<'a, R> {
                                 };
                                 continue;
                             }
-                            _ => {
+                            other => {
                                 *me.state = ReaderState::End;
-                                return core::task::Poll::Ready(Err(io::Error::Unimplemented));
+                                *me.invalid = Some(Error::UnknownEscape { code: other[0] });
+                                return core::task::Poll::Ready(Err(io::Error::Unknown));
                             }
                         }
                     }
@@ -286,7 +316,7 @@ impl<'a, R: io::AsyncRead + Unpin> io::AsyncRead for CheckingReader<'a, R> {
 pub(crate) async fn read_frame<F, R: io::AsyncRead + Unpin>(
     reader: &mut R,
     callback: &mut F,
-) -> Result<(), Error>
+) -> Result<(), FrameError>
 where
     F: for<'r> crate::Callback<crate::message::CheckingReader<'r, CheckingReader<'r, R>>>,
 {
@@ -296,6 +326,17 @@ where
     let message_crc = crate::message::CheckingReader::new(&mut frame);
     let mut tlv_reader = crate::tlv::Reader::new(message_crc);
 
+    let res = read_messages(&mut tlv_reader, callback).await;
+    res.map_err(|error| tlv_reader.reader().inner_mut().frame_error(error))
+}
+
+async fn read_messages<'r, F, R: io::AsyncRead + Unpin>(
+    tlv_reader: &mut crate::tlv::Reader<crate::message::CheckingReader<'r, CheckingReader<'r, R>>>,
+    callback: &mut F,
+) -> Result<(), Error>
+where
+    F: for<'x> crate::Callback<crate::message::CheckingReader<'x, CheckingReader<'x, R>>>,
+{
     while !tlv_reader.reader().has_ended() {
         log::debug!("read message");
 
@@ -339,21 +380,137 @@ where
     Ok(())
 }
 
+/// reads a single frame and writes a [crate::tlv::dump] of every message to `out`
+///
+/// The message checksums aren't verified, so messages with unknown types can be dumped as well.
+pub(crate) async fn dump_frame<R, W>(reader: &mut R, out: &mut W) -> Result<(), FrameError>
+where
+    R: io::AsyncRead + Unpin,
+    W: core::fmt::Write + ?Sized,
+{
+    let mut tlv_reader = crate::tlv::Reader::new(CheckingReader::new(reader));
+
+    let res = dump_messages(&mut tlv_reader, out).await;
+    res.map_err(|error| tlv_reader.reader().frame_error(error))
+}
+
+async fn dump_messages<R, W>(
+    tlv_reader: &mut crate::tlv::Reader<CheckingReader<'_, R>>,
+    out: &mut W,
+) -> Result<(), Error>
+where
+    R: io::AsyncRead + Unpin,
+    W: core::fmt::Write + ?Sized,
+{
+    while !tlv_reader.reader().has_ended() {
+        let mut message = tlv_reader.read_list().await?;
+        writeln!(out, "list[{}] (SML_Message)", message.len())?;
+
+        let mut annotator = crate::schema::Annotator::new("SML_Message");
+        crate::tlv::dump_indented(&mut message, out, &mut annotator, 1).await?;
+    }
+
+    Ok(())
+}
+
+/// copy `data` to `out` at `pos` and add it to the checksum
+fn write_checked(
+    out: &mut [u8],
+    pos: &mut usize,
+    digest: &mut crc::Digest<'static, u16>,
+    data: &[u8],
+) -> Result<(), Error> {
+    let end = *pos + data.len();
+    out.get_mut(*pos..end)
+        .ok_or(Error::BufferFull)?
+        .copy_from_slice(data);
+    digest.update(data);
+    *pos = end;
+
+    Ok(())
+}
+
+/// frames data into `out`, returns the number of bytes written
+///
+/// Escapes `1b1b1b1b` sequences, adds the fill bytes and the frame checksum. Like the decoder,
+/// only escape sequences at 4 byte boundaries are considered.
+pub fn write_frame(payload: &[u8], out: &mut [u8]) -> Result<usize, Error> {
+    const ESCAPE: [u8; 4] = [0x1B, 0x1B, 0x1B, 0x1B];
+
+    let mut pos = 0;
+    let mut digest = crate::CRC_INSTANCE.digest();
+
+    write_checked(out, &mut pos, &mut digest, &ESCAPE)?;
+    write_checked(out, &mut pos, &mut digest, &[0x01, 0x01, 0x01, 0x01])?;
+
+    let num_fillbytes = (4 - payload.len() % 4) % 4;
+    for chunk in payload.chunks(4) {
+        let mut block = [0x00; 4];
+        block[..chunk.len()].copy_from_slice(chunk);
+
+        if block == ESCAPE {
+            write_checked(out, &mut pos, &mut digest, &ESCAPE)?;
+        }
+        write_checked(out, &mut pos, &mut digest, &block)?;
+    }
+
+    write_checked(out, &mut pos, &mut digest, &ESCAPE)?;
+    write_checked(out, &mut pos, &mut digest, &[0x1A, num_fillbytes as u8])?;
+
+    // the checksum is little-endian
+    let crc = digest.finalize().to_le_bytes();
+    out.get_mut(pos..pos + 2)
+        .ok_or(Error::BufferFull)?
+        .copy_from_slice(&crc);
+
+    Ok(pos + 2)
+}
+
 /// deterministic finite automata waiting for the start marker
-pub(crate) async fn wait_for_start_sequence<R: io::AsyncRead + Unpin>(
-    reader: &mut R,
+///
+/// Scans the buffer of `reader` for the first escape byte, so data between frames is discarded
+/// in bulk. Only the start sequence is consumed.
+pub(crate) async fn wait_for_start_sequence<R: io::AsyncRead + Unpin, const N: usize>(
+    reader: &mut io::BufReader<R, N>,
 ) -> Result<(), Error> {
     let mut count = 0;
 
     while count < 8 {
-        let byte = reader.read_u8().await?;
-        if (count < 4 && byte == 0x1b) || (count >= 4 && byte == 0x01) {
-            count += 1;
-        } else if count == 4 && byte == 0x1b {
-            // stay in the current state
-        } else {
-            count = 0;
+        let data = reader.fill_buf().await?;
+        if data.is_empty() {
+            return Err(io::Error::UnexpectedEof.into());
+        }
+
+        let mut used = 0;
+        if count == 0 {
+            match data.iter().position(|b| *b == 0x1b) {
+                Some(index) => used = index,
+                None => {
+                    let len = data.len();
+                    reader.consume(len);
+                    continue;
+                }
+            }
         }
+
+        for byte in &data[used..] {
+            used += 1;
+
+            if (count < 4 && *byte == 0x1b) || (count >= 4 && *byte == 0x01) {
+                count += 1;
+            } else if count == 4 && *byte == 0x1b {
+                // stay in the current state
+            } else {
+                count = 0;
+            }
+
+            // search the next escape byte in bulk again
+            if count == 0 || count == 8 {
+                break;
+            }
+        }
+
+        reader.consume(used);
     }
 
     Ok(())
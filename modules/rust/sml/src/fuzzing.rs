@@ -0,0 +1,122 @@
+//! entry points for the fuzz targets in `fuzz/`
+//!
+//! This is not a stable API. It only exists so the fuzzers can reach the
+//! crate-private parsing layers.
+
+type SliceReader<'a> = io::FuturesUtilReader<futures_util::io::Cursor<&'a [u8]>>;
+
+fn slice_reader(data: &[u8]) -> SliceReader<'_> {
+    io::FuturesUtilReader(futures_util::io::Cursor::new(data))
+}
+
+/// run a future to completion
+///
+/// All readers used here are backed by memory and never return
+/// [core::task::Poll::Pending], so there's no need for a real executor.
+fn block_on<F: core::future::Future>(f: F) -> F::Output {
+    let mut f = core::pin::pin!(f);
+    let mut cx = core::task::Context::from_waker(futures_util::task::noop_waker_ref());
+
+    loop {
+        if let core::task::Poll::Ready(v) = f.as_mut().poll(&mut cx) {
+            return v;
+        }
+    }
+}
+
+/// decode every item of `list` recursively
+///
+/// Long lists and strings are only read partially, so the drop-based skipping
+/// of [crate::tlv::List] and [crate::tlv::String] gets exercised as well.
+fn walk_list<'a, 'b, R: io::AsyncRead + Unpin>(
+    list: &'a mut crate::tlv::List<'b, R>,
+) -> core::pin::Pin<Box<dyn core::future::Future<Output = Result<(), crate::Error>> + 'a>> {
+    Box::pin(async move {
+        let partial = list.len() > 4;
+        let mut index = 0;
+
+        loop {
+            match list.next().await? {
+                None => break,
+                Some(crate::tlv::Item::String(mut s)) => {
+                    let mut buf = [0u8; 32];
+                    if s.len() <= buf.len() {
+                        let len = s.len();
+                        s.read(&mut buf[..len]).await?;
+                    }
+                }
+                Some(crate::tlv::Item::Boolean(v)) => {
+                    v.into_bool().await?;
+                }
+                Some(crate::tlv::Item::Integer(v)) => {
+                    v.into_i64_relaxed().await?;
+                }
+                Some(crate::tlv::Item::Unsigned(v)) => {
+                    v.into_u64_relaxed().await?;
+                }
+                Some(crate::tlv::Item::List(mut l)) => {
+                    walk_list(&mut l).await?;
+                }
+                Some(crate::tlv::Item::None) => {}
+            }
+
+            index += 1;
+            if partial && index == 2 {
+                list.skip(1).await?;
+                break;
+            }
+        }
+
+        Ok(())
+    })
+}
+
+/// a callback which reads as much of each message body as possible
+struct FuzzCallback;
+
+impl<R: io::AsyncRead + Unpin> crate::Callback<R> for FuzzCallback {
+    fn frame_start(&mut self) {}
+
+    async fn message_received<'a>(
+        &'a mut self,
+        body: crate::types::MessageBody<'a, R>,
+    ) -> Result<(), crate::Error> {
+        let mut body = body;
+        walk_list(&mut body.list).await
+    }
+
+    fn frame_finished(&mut self, _valid: bool) {}
+}
+
+/// parse `data` as a single TLV list
+pub fn tlv(data: &[u8]) {
+    let mut reader = crate::tlv::Reader::new(slice_reader(data));
+
+    let _ = block_on(async {
+        let mut list = reader.read_list().await?;
+        walk_list(&mut list).await?;
+        drop(list);
+        reader.skip_now().await
+    });
+}
+
+/// parse `data` as a single frame, starting with the start sequence
+pub fn frame(data: &[u8]) {
+    let mut reader = io::BufReader::<_, 64>::new(slice_reader(data));
+    let mut callback = FuzzCallback;
+
+    let _ = block_on(async {
+        crate::frame::wait_for_start_sequence(&mut reader).await?;
+        crate::frame::read_frame(&mut reader, &mut callback)
+            .await
+            .map_err(|e| e.error)
+    });
+}
+
+/// run the whole SML task until `data` is exhausted
+pub fn task(data: &[u8]) {
+    let mut reader = io::BufReader::<_, 64>::new(slice_reader(data));
+    let mut callback = FuzzCallback;
+
+    let _ = block_on(crate::task(&mut reader, &mut callback));
+}
@@ -5,14 +5,23 @@
 #![feature(impl_trait_in_assoc_type)]
 #![feature(async_fn_in_trait)]
 #![feature(impl_trait_projections)]
+#![feature(error_in_core)]
 
 mod error;
 mod frame;
+#[cfg(feature = "fuzzing")]
+pub mod fuzzing;
 mod macros;
 mod message;
-mod tlv;
+pub mod reading;
+pub mod schema;
+#[cfg(test)]
+mod test_support;
+pub mod tlv;
+pub mod visit;
 
-pub use error::Error;
+pub use error::{Error, FrameError};
+pub use frame::write_frame;
 
 const CRC_16_SML: crc::Algorithm<u16> = crc::Algorithm {
     width: 16,
@@ -101,6 +110,13 @@ pub mod types {
 
     include!(concat!(env!("OUT_DIR"), "/messages.rs"));
 
+    impl<'a, R: io::AsyncRead + Unpin> GetListRes<'a, R> {
+        /// read the entries of `valList`, skipping all other fields
+        pub async fn entries(self) -> Result<crate::reading::Entries<'a, R>, crate::Error> {
+            crate::reading::Entries::new(self.list).await
+        }
+    }
+
     impl<'a, R> ValueEnum<'a, R> {
         pub fn into_i128_relaxed(self) -> Result<i128, crate::Error> {
             Ok(match self {
@@ -150,12 +166,52 @@ pub trait Callback<R> {
         val: types::MessageBody<'a, R>,
     ) -> Result<(), Error>;
     fn frame_finished(&mut self, valid: bool);
+    /// called by [task_with_timeouts], before [Callback::frame_finished] for aborted frames
+    fn timeout(&mut self, _timeout: Timeout) {}
+}
+
+/// the timeouts of [task_with_timeouts], in milliseconds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timeouts {
+    /// report [Timeout::NoFrame] if no frame started for this long
+    ///
+    /// Meters send a frame every 1 to 4 seconds.
+    pub frame_ms: u64,
+    /// abort a frame if no data was received for this long
+    pub byte_ms: u64,
+}
+
+impl Default for Timeouts {
+    fn default() -> Self {
+        Self {
+            frame_ms: 10_000,
+            byte_ms: 500,
+        }
+    }
 }
 
-pub async fn task<R, C>(reader: &mut R, callback: &mut C) -> Result<(), Error>
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Timeout {
+    /// no frame started for [Timeouts::frame_ms], reported again after every period
+    NoFrame,
+    /// no data was received for [Timeouts::byte_ms] in the middle of a frame
+    FrameAborted,
+}
+
+/// read frames from `reader` forever and report them to `callback`
+///
+/// The start sequence is searched in the buffer of `reader` and skipped data is discarded in
+/// bulk, so the wrapped reader is called once per `N` bytes instead of once per byte. Keep the
+/// [io::BufReader] when calling this again, it may already hold data of the next frame.
+pub async fn task<R, C, const N: usize>(
+    reader: &mut io::BufReader<R, N>,
+    callback: &mut C,
+) -> Result<(), Error>
 where
     R: io::AsyncRead + Unpin,
-    C: for<'r> Callback<message::CheckingReader<'r, frame::CheckingReader<'r, R>>>,
+    C: for<'r> Callback<
+        message::CheckingReader<'r, frame::CheckingReader<'r, io::BufReader<R, N>>>,
+    >,
 {
     loop {
         crate::frame::wait_for_start_sequence(reader).await?;
@@ -165,93 +221,588 @@ where
         match crate::frame::read_frame(reader, callback).await {
             Ok(()) => callback.frame_finished(true),
             Err(e) => {
-                log::error!("failed to read frame: {:#?}", e);
+                dlog::error!("failed to read frame: {}", e);
                 callback.frame_finished(false);
             }
         }
     }
 }
 
+/// like [task], but reports stalled links to [Callback::timeout]
+///
+/// See [io::time::TimeoutReader] for how the timeouts are detected.
+pub async fn task_with_timeouts<R, T, C, const N: usize>(
+    reader: &mut io::BufReader<io::time::TimeoutReader<R, T>, N>,
+    timeouts: Timeouts,
+    callback: &mut C,
+) -> Result<(), Error>
+where
+    R: io::AsyncRead + Unpin,
+    T: io::time::TimeSource + Unpin,
+    C: for<'r> Callback<
+        message::CheckingReader<
+            'r,
+            frame::CheckingReader<'r, io::BufReader<io::time::TimeoutReader<R, T>, N>>,
+        >,
+    >,
+{
+    let mut last_frame = reader.get_ref().time().now_ms();
+
+    loop {
+        reader
+            .get_mut()
+            .set_deadline(Some(last_frame.saturating_add(timeouts.frame_ms)));
+        match crate::frame::wait_for_start_sequence(reader).await {
+            Ok(()) => (),
+            Err(Error::Io(io::Error::TimedOut)) => {
+                dlog::warn!("no frame for {} ms", timeouts.frame_ms);
+                callback.timeout(Timeout::NoFrame);
+                last_frame = reader.get_ref().time().now_ms();
+                continue;
+            }
+            Err(e) => return Err(e),
+        }
+
+        last_frame = reader.get_ref().time().now_ms();
+        reader.get_mut().set_idle_timeout(timeouts.byte_ms);
+        callback.frame_start();
+
+        match crate::frame::read_frame(reader, callback).await {
+            Ok(()) => callback.frame_finished(true),
+            Err(e) => {
+                dlog::error!("failed to read frame: {}", e);
+                if let Error::Io(io::Error::TimedOut) = e.error {
+                    callback.timeout(Timeout::FrameAborted);
+                }
+                callback.frame_finished(false);
+            }
+        }
+    }
+}
+
+/// wait for the next frame and write a [tlv::dump] of its messages to `out`
+///
+/// The values are named using [schema::Annotator]. The frame checksum is verified, but the
+/// dump may already have been written partially when it doesn't match.
+///
+/// Like with [task], `reader` may hold data of the next frame afterwards.
+pub async fn dump_frame<R, W, const N: usize>(
+    reader: &mut io::BufReader<R, N>,
+    out: &mut W,
+) -> Result<(), FrameError>
+where
+    R: io::AsyncRead + Unpin,
+    W: core::fmt::Write + ?Sized,
+{
+    crate::frame::wait_for_start_sequence(reader)
+        .await
+        .map_err(|error| FrameError { offset: 0, error })?;
+    crate::frame::dump_frame(reader, out).await
+}
+
 #[cfg(test)]
 mod tests {
-    struct TestCallback;
+    use crate::test_support::{
+        captures, check_samples, decode, format_hex, format_obis, format_opt, parse_expected,
+        Collect,
+    };
+
+    /// collects all list entries in the same format as the `.expected` files
+    #[derive(Default)]
+    struct ListCallback {
+        frames: Vec<Vec<String>>,
+        current: Vec<String>,
+    }
 
-    const OBIS_ACTIVE_ENERGY: &[u8] = &[0x01, 0x00, 0x01, 0x08, 0x00, 0xFF];
-    const OBIS_ACTIVE_POWER: &[u8] = &[0x01, 0x00, 0x10, 0x07, 0x00, 0xFF];
+    impl Collect for ListCallback {
+        fn frames(&self) -> &[Vec<String>] {
+            &self.frames
+        }
+    }
+
+    impl<R: io::AsyncRead + Unpin> crate::Callback<R> for ListCallback {
+        fn frame_start(&mut self) {
+            self.current.clear();
+        }
 
-    impl<R: io::AsyncRead + Unpin> crate::Callback<R> for TestCallback {
         async fn message_received<'a>(
             &'a mut self,
             mut body: crate::types::MessageBody<'a, R>,
         ) -> Result<(), crate::Error> {
-            log::info!("got message body");
-
-            match body.read().await? {
-                crate::types::MessageBodyEnum::GetListResponse(r) => {
-                    log::info!("got list response");
-
-                    let mut field = r.val_list().await?;
-                    let mut list = field.parse().await?;
-
-                    let mut buf = [0; 7];
-                    while let Some(entry) = list.next().await? {
-                        let mut field = entry.obj_name().await?;
-                        let mut obj_name = field.parse().await?;
-
-                        if obj_name.len() > buf.len() {
-                            continue;
-                        }
-
-                        let buf = &mut buf[..obj_name.len()];
-                        obj_name.read(buf).await?;
-                        drop(obj_name);
-                        let entry = field.finish().await?;
-
-                        let (entry, scaler) = entry.scaler().await?;
-                        let scaler = scaler.unwrap_or(0);
-                        log::debug!("scaler={}", scaler);
-
-                        let mut field = entry.value().await?;
-                        let value = field.parse().await?.read().await?;
-                        match &*buf {
-                            OBIS_ACTIVE_POWER => {
-                                log::debug!("active_power={}", value.into_i128_relaxed()?);
-                            }
-
-                            OBIS_ACTIVE_ENERGY => {
-                                log::debug!("active_energy={}", value.into_i128_relaxed()?);
-                            }
-                            _ => continue,
-                        }
+            let r = match body.read().await? {
+                crate::types::MessageBodyEnum::GetListResponse(r) => r,
+                _ => return Ok(()),
+            };
+
+            let mut field = r.val_list().await?;
+            let mut list = field.parse().await?;
+
+            while let Some(entry) = list.next().await? {
+                let mut field = entry.obj_name().await?;
+                let mut obj_name = field.parse().await?;
+                let mut obis = vec![0; obj_name.len()];
+                obj_name.read(&mut obis).await?;
+                drop(obj_name);
+                let entry = field.finish().await?;
+
+                let mut field = entry.status().await?;
+                let status = {
+                    let status = field.parse().await?;
+                    if status.is_none() {
+                        None
+                    } else {
+                        Some(match status.read().await? {
+                            crate::types::StatusEnum::Status8(v) => u64::from(v),
+                            crate::types::StatusEnum::Status16(v) => v.into(),
+                            crate::types::StatusEnum::Status32(v) => v.into(),
+                            crate::types::StatusEnum::Status64(v) => v,
+                        })
+                    }
+                };
+                let entry = field.finish().await?;
+
+                let (entry, unit) = entry.unit().await?;
+                let (entry, scaler) = entry.scaler().await?;
+
+                let mut field = entry.value().await?;
+                let value = match field.parse().await?.read().await? {
+                    crate::types::ValueEnum::BooleanValue(v) => v.to_string(),
+                    crate::types::ValueEnum::ByteList(mut s) => {
+                        let mut buf = vec![0; s.len()];
+                        s.read(&mut buf).await?;
+                        format!("hex:{}", format_hex(&buf))
                     }
+                    crate::types::ValueEnum::SmlList(_) => "list".to_string(),
+                    other => other.into_i128_relaxed()?.to_string(),
+                };
+
+                self.current.push(format!(
+                    "{} status={} unit={} scaler={} value={}",
+                    format_obis(&obis),
+                    format_opt(status),
+                    format_opt(unit),
+                    format_opt(scaler),
+                    value
+                ));
+            }
+
+            Ok(())
+        }
+
+        fn frame_finished(&mut self, valid: bool) {
+            assert!(valid, "received invalid frame");
+            self.frames.push(core::mem::take(&mut self.current));
+        }
+    }
+
+    /// decode every sample in `testdata/` and compare with its `.expected` file
+    #[test_log::test]
+    fn samples() {
+        check_samples::<ListCallback>();
+    }
+
+    /// the decoder mustn't depend on how the data is split into reads
+    #[test_log::test(tokio::test)]
+    async fn replay_chunks() {
+        let captures = captures();
+        for capture in captures {
+            let sampledata = std::fs::read(&capture).unwrap();
+            let expected = std::fs::read_to_string(capture.with_extension("expected")).unwrap();
+
+            // like a UART ring buffer returning whatever was received so far
+            let mut recorder = io::replay::Recorder::new(Vec::new()).unwrap();
+            let mut rest = &sampledata[..];
+            for len in [1, 3, 7, 2, 5, 64, 4].into_iter().cycle() {
+                let (chunk, next) = rest.split_at(len.min(rest.len()));
+                recorder
+                    .record_with_delay(std::time::Duration::ZERO, chunk)
+                    .unwrap();
+                rest = next;
+                if rest.is_empty() {
+                    break;
                 }
+            }
+            let recording = recorder.into_inner();
+
+            let mut reader =
+                io::BufReader::<_, 16>::new(io::replay::Replay::new(&recording).unwrap());
+            let mut callback = ListCallback::default();
+            let res = crate::task(&mut reader, &mut callback).await;
+            assert!(matches!(
+                res,
+                Err(crate::Error::Io(io::Error::UnexpectedEof))
+            ));
+
+            assert_eq!(
+                callback.frames,
+                parse_expected(&expected),
+                "{}",
+                capture.display()
+            );
+        }
+    }
+
+    /// encode a frame with an escape sequence in the payload and decode it again
+    #[test_log::test(tokio::test)]
+    async fn write_frame() {
+        let mut payload = [0u8; 256];
+        let mut writer = crate::tlv::Writer::new(&mut payload);
+        writer.write_list(6).unwrap();
+        writer.write_string(&[0x01]).unwrap();
+        writer.write_unsigned(0, 1).unwrap();
+        writer.write_unsigned(0, 1).unwrap();
+        writer.write_list(2).unwrap();
+        writer.write_unsigned(0x0701, 4).unwrap();
+        writer.write_list(7).unwrap();
+        writer.write_none().unwrap();
+        writer.write_string(&[0x0a, 0x01, 0x02]).unwrap();
+        writer.write_none().unwrap();
+        writer.write_none().unwrap();
+        writer.write_list(1).unwrap();
+        writer.write_list(7).unwrap();
+        writer
+            .write_string(&[0x01, 0x00, 0x01, 0x08, 0x00, 0xff])
+            .unwrap();
+        writer.write_none().unwrap();
+        writer.write_none().unwrap();
+        writer.write_unsigned(30, 1).unwrap();
+        writer.write_integer(-1, 1).unwrap();
+        // contains an escape sequence no matter how it's aligned
+        writer.write_string(&[0x1b; 7]).unwrap();
+        writer.write_none().unwrap();
+        writer.write_none().unwrap();
+        writer.write_none().unwrap();
+        writer.write_crc16(0).unwrap();
+        writer.write_end_of_message().unwrap();
+        let payload = writer.written().to_vec();
+
+        let mut frame = [0u8; 512];
+        let len = crate::write_frame(&payload, &mut frame).unwrap();
+        let frame = &frame[..len];
+        assert!(frame[8..].windows(8).any(|w| w == [0x1b; 8]));
+
+        let mut callback = ListCallback::default();
+        decode(frame, &mut callback).await;
+        assert_eq!(
+            callback.frames,
+            [["1-0:1.8.0*255 status=- unit=30 scaler=-1 value=hex:1b1b1b1b1b1b1b"]]
+        );
+    }
+
+    /// only reads the units, the fields in front of them are finished without parsing them
+    #[derive(Default)]
+    struct FinishCallback {
+        units: Vec<Option<u8>>,
+    }
+
+    impl<R: io::AsyncRead + Unpin> crate::Callback<R> for FinishCallback {
+        fn frame_start(&mut self) {}
+
+        async fn message_received<'a>(
+            &'a mut self,
+            mut body: crate::types::MessageBody<'a, R>,
+        ) -> Result<(), crate::Error> {
+            let r = match body.read().await? {
+                crate::types::MessageBodyEnum::GetListResponse(r) => r,
                 _ => return Ok(()),
+            };
+
+            let mut field = r.val_list().await?;
+            let mut list = field.parse().await?;
+
+            while let Some(entry) = list.next().await? {
+                let entry = entry.obj_name().await?.finish().await?;
+                let entry = entry.status().await?.finish().await?;
+                let entry = entry.val_time().await?.finish().await?;
+                let (_, unit) = entry.unit().await?;
+                self.units.push(unit);
             }
 
             Ok(())
         }
 
-        fn frame_start(&mut self) {}
+        fn frame_finished(&mut self, valid: bool) {
+            assert!(valid, "received invalid frame");
+        }
+    }
+
+    /// `finish` skips optional fields whether they're set or not
+    #[test_log::test(tokio::test)]
+    async fn finish_optional() {
+        let mut payload = [0u8; 256];
+        let mut writer = crate::tlv::Writer::new(&mut payload);
+        writer.write_list(6).unwrap();
+        writer.write_string(&[0x01]).unwrap();
+        writer.write_unsigned(0, 1).unwrap();
+        writer.write_unsigned(0, 1).unwrap();
+        writer.write_list(2).unwrap();
+        writer.write_unsigned(0x0701, 4).unwrap();
+        writer.write_list(7).unwrap();
+        writer.write_none().unwrap();
+        writer.write_string(&[0x0a, 0x01, 0x02]).unwrap();
+        writer.write_none().unwrap();
+        writer.write_none().unwrap();
+        writer.write_list(2).unwrap();
+        // status and time are set
+        writer.write_list(7).unwrap();
+        writer
+            .write_string(&[0x01, 0x00, 0x01, 0x08, 0x00, 0xff])
+            .unwrap();
+        writer.write_unsigned(0x0182, 2).unwrap();
+        writer.write_list(2).unwrap();
+        writer.write_unsigned(0x01, 1).unwrap();
+        writer.write_unsigned(0x12345678, 4).unwrap();
+        writer.write_unsigned(30, 1).unwrap();
+        writer.write_integer(-1, 1).unwrap();
+        writer.write_integer(1000, 4).unwrap();
+        writer.write_none().unwrap();
+        // status and time aren't set
+        writer.write_list(7).unwrap();
+        writer
+            .write_string(&[0x01, 0x00, 0x10, 0x07, 0x00, 0xff])
+            .unwrap();
+        writer.write_none().unwrap();
+        writer.write_none().unwrap();
+        writer.write_unsigned(27, 1).unwrap();
+        writer.write_integer(-1, 1).unwrap();
+        writer.write_integer(500, 4).unwrap();
+        writer.write_none().unwrap();
+        writer.write_none().unwrap();
+        writer.write_none().unwrap();
+        writer.write_crc16(0).unwrap();
+        writer.write_end_of_message().unwrap();
+        let payload = writer.written().to_vec();
+
+        let mut frame = [0u8; 512];
+        let len = crate::write_frame(&payload, &mut frame).unwrap();
+
+        let mut callback = FinishCallback::default();
+        decode(&frame[..len], &mut callback).await;
+        assert_eq!(callback.units, [Some(30), Some(27)]);
+    }
+
+    /// records the calls, the messages are skipped
+    #[derive(Default)]
+    struct EventCallback {
+        events: Vec<String>,
+    }
+
+    impl<R: io::AsyncRead + Unpin> crate::Callback<R> for EventCallback {
+        fn frame_start(&mut self) {
+            self.events.push("start".to_string());
+        }
 
-        fn frame_finished(&mut self, _valid: bool) {}
+        async fn message_received<'a>(
+            &'a mut self,
+            _body: crate::types::MessageBody<'a, R>,
+        ) -> Result<(), crate::Error> {
+            Ok(())
+        }
+
+        fn frame_finished(&mut self, valid: bool) {
+            self.events.push(format!("finished valid={}", valid));
+        }
+
+        fn timeout(&mut self, timeout: crate::Timeout) {
+            self.events.push(format!("{:?}", timeout));
+        }
     }
 
+    /// returns the chunks which were pushed so far, pending otherwise
+    struct ChunkReader<'a>(&'a std::cell::RefCell<std::collections::VecDeque<Vec<u8>>>);
+
+    impl io::AsyncRead for ChunkReader<'_> {
+        fn poll_read(
+            self: core::pin::Pin<&mut Self>,
+            _cx: &mut core::task::Context<'_>,
+            buf: &mut [u8],
+        ) -> core::task::Poll<Result<usize, io::Error>> {
+            let mut chunks = self.0.borrow_mut();
+            let chunk = match chunks.front_mut() {
+                None => return core::task::Poll::Pending,
+                Some(chunk) => chunk,
+            };
+
+            let len = buf.len().min(chunk.len());
+            buf[..len].copy_from_slice(&chunk[..len]);
+            chunk.drain(..len);
+            if chunk.is_empty() {
+                chunks.pop_front();
+            }
+            core::task::Poll::Ready(Ok(len))
+        }
+    }
+
+    #[test]
+    fn timeouts() {
+        let capture = std::fs::read(&captures()[0]).unwrap();
+        let chunks = std::cell::RefCell::new(std::collections::VecDeque::new());
+        let time = io::time::ManualTime::default();
+        let mut reader =
+            io::BufReader::<_, 64>::new(io::time::TimeoutReader::new(ChunkReader(&chunks), &time));
+        let mut callback = EventCallback::default();
+
+        let timeouts = crate::Timeouts {
+            frame_ms: 5000,
+            byte_ms: 100,
+        };
+        let mut task = Box::pin(crate::task_with_timeouts(
+            &mut reader,
+            timeouts,
+            &mut callback,
+        ));
+        let waker = futures_util::task::noop_waker();
+        let mut cx = core::task::Context::from_waker(&waker);
+        let mut poll = || assert!(core::future::Future::poll(task.as_mut(), &mut cx).is_pending());
+
+        // half a frame, then nothing
+        chunks.borrow_mut().push_back(capture[..40].to_vec());
+        poll();
+        time.set(99);
+        poll();
+        time.set(100);
+        poll();
+
+        // counted from the start of the last frame, then again after every report
+        for now in [4999, 5000, 9999, 10000] {
+            time.set(now);
+            poll();
+        }
+
+        chunks.borrow_mut().push_back(capture);
+        poll();
+
+        drop(task);
+        assert_eq!(
+            callback.events[..6],
+            [
+                "start",
+                "FrameAborted",
+                "finished valid=false",
+                "NoFrame",
+                "NoFrame",
+                "start"
+            ]
+        );
+        assert_eq!(callback.events[6], "finished valid=true");
+    }
+
+    /// partial start sequences before a frame are skipped, also across the buffer boundary
     #[test_log::test(tokio::test)]
-    async fn basic() {
-        let mut callback = TestCallback {};
+    async fn start_sequence_after_noise() {
+        let mut data = vec![
+            0x00, 0x1b, 0x1b, 0x1b, 0x42, 0x1b, 0x1b, 0x1b, 0x1b, 0x1b, 0x01,
+        ];
+        data.extend([0xaa; 40]);
+        data.extend([0x1b, 0x1b, 0x1b, 0x1b, 0x01, 0x01, 0x01, 0x02]);
+        data.extend(std::fs::read(&captures()[0]).unwrap());
+
+        let mut reader = io::BufReader::<_, 16>::new(io::FuturesUtilReader(
+            futures_util::io::Cursor::new(&data[..]),
+        ));
+        let mut out = String::new();
+        crate::dump_frame(&mut reader, &mut out).await.unwrap();
+
+        assert!(out.starts_with("list[6] (SML_Message)\n"), "{}", out);
+    }
 
-        let sampledata = include_bytes!("/home/m1cha/nbu/west_smartmeter_lora/sampledata.bin");
-        let mut reader = io::FuturesUtilReader(futures_util::io::Cursor::new(&sampledata[..]));
+    /// errors say where in the frame they happened
+    #[test_log::test(tokio::test)]
+    async fn frame_error_offset() {
+        // a list with a string and a TLV of an unsupported type at byte 15 of the payload
+        let mut payload = [0u8; 20];
+        payload[0] = 0x72;
+        payload[1] = 0x0e;
+        payload[15] = 0x31;
+
+        let mut frame = [0u8; 64];
+        let len = crate::write_frame(&payload, &mut frame).unwrap();
+
+        let mut reader = io::BufReader::<_, 64>::new(io::FuturesUtilReader(
+            futures_util::io::Cursor::new(&frame[..len]),
+        ));
+        let mut out = String::new();
+        let e = crate::dump_frame(&mut reader, &mut out).await.unwrap_err();
+
+        // at most 8 bytes after the TLV at byte 8 + 15
+        assert!(matches!(e.error, crate::Error::UnsupportedTlvType { .. }));
+        assert_eq!(e.offset, 28);
+        assert_eq!(
+            e.to_string(),
+            "unsupported TLV type 0x3 at byte 28 of the frame"
+        );
+    }
 
-        loop {
-            crate::frame::wait_for_start_sequence(&mut reader)
-                .await
-                .unwrap();
-            crate::frame::read_frame(&mut reader, &mut callback)
+    /// broken frame endings are reported with their own errors instead of an io error
+    #[test_log::test(tokio::test)]
+    async fn invalid_frame_end() {
+        // a list of two `None`s
+        let payload = [0x72, 0x01, 0x01];
+        let mut valid = [0u8; 32];
+        let len = crate::write_frame(&payload, &mut valid).unwrap();
+        let valid = &valid[..len];
+
+        let dump = |frame: Vec<u8>| async move {
+            let mut reader = io::BufReader::<_, 64>::new(io::FuturesUtilReader(
+                futures_util::io::Cursor::new(frame),
+            ));
+            let mut out = String::new();
+            crate::dump_frame(&mut reader, &mut out)
                 .await
-                .unwrap();
+                .map_err(|e| e.error)
+        };
 
-            break;
+        assert!(dump(valid.to_vec()).await.is_ok());
+
+        let mut frame = valid.to_vec();
+        frame[len - 1] ^= 0xff;
+        let crc = u16::from_le_bytes([valid[len - 2], valid[len - 1]]);
+        let e = dump(frame).await.unwrap_err();
+        assert!(
+            matches!(e, crate::Error::CrcMismatch { expected, actual } if expected == crc ^ 0xff00 && actual == crc),
+            "{:?}",
+            e
+        );
+
+        let mut frame = valid.to_vec();
+        frame[len - 3] = 4;
+        let crc = crate::CRC_INSTANCE.checksum(&frame[..len - 2]);
+        frame[len - 2..].copy_from_slice(&crc.to_le_bytes());
+        let e = dump(frame).await.unwrap_err();
+        assert!(
+            matches!(e, crate::Error::InvalidFillBytes { num: 4 }),
+            "{:?}",
+            e
+        );
+
+        let mut frame = valid.to_vec();
+        frame[len - 4] = 0x02;
+        let e = dump(frame).await.unwrap_err();
+        assert!(
+            matches!(e, crate::Error::UnknownEscape { code: 0x02 }),
+            "{:?}",
+            e
+        );
+    }
+
+    /// dump the first frame of every capture, the values have to be named using the schema
+    #[test_log::test(tokio::test)]
+    async fn dump_frame() {
+        let captures = captures();
+        for capture in captures {
+            let sampledata = std::fs::read(&capture).unwrap();
+            let mut reader = io::BufReader::<_, 64>::new(io::FuturesUtilReader(
+                futures_util::io::Cursor::new(&sampledata[..]),
+            ));
+
+            let mut out = String::new();
+            crate::dump_frame(&mut reader, &mut out).await.unwrap();
+            log::info!("{}:\n{}", capture.display(), out);
+
+            assert!(out.starts_with("list[6] (SML_Message)\n"), "{}", out);
+            // meters encode the tags with different lengths
+            assert!(out.contains("] 1793 (GetListResponse)\n"), "{}", out);
+            assert!(out.contains("objName: string[6] 0100010800ff\n"), "{}", out);
         }
     }
 }
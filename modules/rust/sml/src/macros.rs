@@ -30,6 +30,17 @@ macro_rules! bitvalues {
                 }
             }
         }
+
+        dlog::deferred! {
+            impl dlog::Format for $name {
+                fn format(&self, e: &mut dlog::Encoder) {
+                    match self {
+                        $( Self::$variant => dlog::write!(e, stringify!($variant)), )*
+                        Self::Other(other) => dlog::write!(e, "Other({})", other),
+                    }
+                }
+            }
+        }
     };
 }
 
@@ -23,6 +23,14 @@ impl<'a, R> CheckingReader<'a, R> {
     pub fn reset(&mut self) {
         self.digest = crate::CRC_INSTANCE.digest();
     }
+
+    pub fn inner(&self) -> &R {
+        self.inner
+    }
+
+    pub fn inner_mut(&mut self) -> &mut R {
+        self.inner
+    }
 }
 
 impl<'a, R: crate::ReaderEnded> crate::ReaderEnded for CheckingReader<'a, R> {
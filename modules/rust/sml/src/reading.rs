@@ -0,0 +1,314 @@
+//! decoded entries of a `SML_GetList.Res`
+//!
+//! Most meters send their values as a list response. [Entries] reads it entry by entry, without
+//! having to go through the fields of every [types::ListEntry] manually.
+
+use crate::types::{self, ParseField as _};
+use crate::Error;
+
+/// an OBIS code identifying a value, e.g. `1-0:1.8.0*255` for the imported active energy
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Obis(pub [u8; 6]);
+
+impl core::fmt::Display for Obis {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let [a, b, c, d, e, g] = self.0;
+        write!(f, "{}-{}:{}.{}.{}*{}", a, b, c, d, e, g)
+    }
+}
+
+impl Obis {
+    /// a short description of well-known codes
+    ///
+    /// The last group is ignored, meters use both `255` and `1` for current values.
+    pub fn description(&self) -> Option<&'static str> {
+        let [a, b, c, d, e, _] = self.0;
+
+        Some(match (a, b, c, d, e) {
+            (1, _, 0, 0, 0) => "meter id",
+            (1, _, 0, 0, 9) => "device id",
+            (1, _, 96, 1, 0) => "serial number",
+            (1, _, 96, 50, 1) => "manufacturer",
+            (1, _, 1, 8, 0) => "energy import",
+            (1, _, 1, 8, 1) => "energy import tariff 1",
+            (1, _, 1, 8, 2) => "energy import tariff 2",
+            (1, _, 2, 8, 0) => "energy export",
+            (1, _, 2, 8, 1) => "energy export tariff 1",
+            (1, _, 2, 8, 2) => "energy export tariff 2",
+            (1, _, 16, 7, 0) => "active power",
+            (1, _, 36, 7, 0) => "active power L1",
+            (1, _, 56, 7, 0) => "active power L2",
+            (1, _, 76, 7, 0) => "active power L3",
+            (1, _, 31, 7, 0) => "current L1",
+            (1, _, 51, 7, 0) => "current L2",
+            (1, _, 71, 7, 0) => "current L3",
+            (1, _, 32, 7, 0) => "voltage L1",
+            (1, _, 52, 7, 0) => "voltage L2",
+            (1, _, 72, 7, 0) => "voltage L3",
+            (1, _, 14, 7, 0) => "frequency",
+            (129, 129, 199, 130, 3) => "manufacturer",
+            (129, 129, 199, 130, 5) => "public key",
+            _ => return None,
+        })
+    }
+}
+
+/// the symbol of a DLMS unit code, e.g. `W` for `27`
+pub fn unit_symbol(unit: u8) -> Option<&'static str> {
+    Some(match unit {
+        1 => "a",
+        2 => "mo",
+        3 => "wk",
+        4 => "d",
+        5 => "h",
+        6 => "min",
+        7 => "s",
+        8 => "°",
+        9 => "°C",
+        27 => "W",
+        28 => "VA",
+        29 => "var",
+        30 => "Wh",
+        31 => "VAh",
+        32 => "varh",
+        33 => "A",
+        34 => "C",
+        35 => "V",
+        44 => "Hz",
+        _ => return None,
+    })
+}
+
+/// an octet string, truncated to [Bytes::CAPACITY] bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Bytes {
+    buf: [u8; Bytes::CAPACITY],
+    len: usize,
+}
+
+impl Bytes {
+    /// fits the 48 byte public keys some meters send
+    pub const CAPACITY: usize = 48;
+
+    async fn read<R: io::AsyncRead + Unpin>(
+        s: &mut crate::tlv::String<'_, R>,
+    ) -> Result<Self, Error> {
+        let len = s.len();
+        let mut buf = [0; Self::CAPACITY];
+        s.read_truncated(&mut buf).await?;
+
+        Ok(Self { buf, len })
+    }
+
+    /// the received data, up to [Bytes::CAPACITY] bytes
+    pub fn as_slice(&self) -> &[u8] {
+        &self.buf[..self.len.min(Self::CAPACITY)]
+    }
+
+    /// the length of the string as received
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn is_truncated(&self) -> bool {
+        self.len > Self::CAPACITY
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Value {
+    Boolean(bool),
+    Integer(i64),
+    Unsigned(u64),
+    Bytes(Bytes),
+    /// a `SML_ListType`, which is skipped
+    List,
+}
+
+impl Value {
+    async fn read<R: io::AsyncRead + Unpin>(value: types::Value<'_, R>) -> Result<Self, Error> {
+        Ok(match value.read().await? {
+            types::ValueEnum::BooleanValue(v) => Self::Boolean(v),
+            types::ValueEnum::ByteList(mut s) => Self::Bytes(Bytes::read(&mut s).await?),
+            types::ValueEnum::SmlList(_) => Self::List,
+            types::ValueEnum::N8BitInteger(v) => Self::Integer(v.into()),
+            types::ValueEnum::N16BitInteger(v) => Self::Integer(v.into()),
+            types::ValueEnum::N32BitInteger(v) => Self::Integer(v.into()),
+            types::ValueEnum::N64BitInteger(v) => Self::Integer(v),
+            types::ValueEnum::N8BitUnsigned(v) => Self::Unsigned(v.into()),
+            types::ValueEnum::N16BitUnsigned(v) => Self::Unsigned(v.into()),
+            types::ValueEnum::N32BitUnsigned(v) => Self::Unsigned(v.into()),
+            types::ValueEnum::N64BitUnsigned(v) => Self::Unsigned(v),
+        })
+    }
+
+    /// returns the value of integers of any type
+    pub fn to_i128(self) -> Option<i128> {
+        match self {
+            Self::Integer(v) => Some(v.into()),
+            Self::Unsigned(v) => Some(v.into()),
+            _ => None,
+        }
+    }
+}
+
+/// a single `SML_ListEntry`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Reading {
+    pub obis: Obis,
+    pub status: Option<u64>,
+    pub unit: Option<u8>,
+    pub scaler: Option<i8>,
+    pub value: Value,
+}
+
+impl Reading {
+    /// returns [None] for entries which aren't named by an OBIS code
+    async fn read<R: io::AsyncRead + Unpin>(
+        entry: types::ListEntry<'_, R>,
+    ) -> Result<Option<Self>, Error> {
+        let mut field = entry.obj_name().await?;
+        let mut obj_name = field.parse().await?;
+        let mut obis = [0; 6];
+        if obj_name.len() != obis.len() {
+            log::debug!("skip entry with a name of {} bytes", obj_name.len());
+            return Ok(None);
+        }
+        obj_name.read(&mut obis).await?;
+        drop(obj_name);
+        let entry = field.finish().await?;
+
+        let mut field = entry.status().await?;
+        let status = {
+            let status = field.parse().await?;
+            if status.is_none() {
+                None
+            } else {
+                Some(match status.read().await? {
+                    types::StatusEnum::Status8(v) => v.into(),
+                    types::StatusEnum::Status16(v) => v.into(),
+                    types::StatusEnum::Status32(v) => v.into(),
+                    types::StatusEnum::Status64(v) => v,
+                })
+            }
+        };
+        let entry = field.finish().await?;
+
+        let (entry, unit) = entry.unit().await?;
+        let (entry, scaler) = entry.scaler().await?;
+
+        let mut field = entry.value().await?;
+        let value = Value::read(field.parse().await?).await?;
+
+        Ok(Some(Self {
+            obis: Obis(obis),
+            status,
+            unit,
+            scaler,
+            value,
+        }))
+    }
+}
+
+/// iterates over the `valList` of a `SML_GetList.Res`
+///
+/// Created by [types::GetListRes::entries]. Entries which weren't read are skipped on drop.
+pub struct Entries<'a, R> {
+    /// the entries of `valList`, the remaining fields of the response get skipped after them
+    list: crate::tlv::List<'a, R>,
+}
+
+impl<'a, R: io::AsyncRead + Unpin> Entries<'a, R> {
+    pub(crate) async fn new(mut list: crate::tlv::List<'a, R>) -> Result<Self, Error> {
+        // clientId, serverId, listName, actSensorTime
+        list.skip(4).await?;
+        list.enter_list().await?;
+
+        Ok(Self { list })
+    }
+
+    /// returns the number of entries that haven't been read yet
+    pub fn remaining(&self) -> usize {
+        self.list.len()
+    }
+
+    pub async fn next(&mut self) -> Result<Option<Reading>, Error> {
+        while !self.list.is_empty() {
+            let entry = types::ListEntry::parse_field(&mut self.list).await?;
+            if let Some(reading) = Reading::read(entry).await? {
+                return Ok(Some(reading));
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::{check_samples, format_hex, format_opt, Collect};
+
+    #[derive(Default)]
+    struct EntriesCallback {
+        frames: Vec<Vec<String>>,
+        current: Vec<String>,
+    }
+
+    impl Collect for EntriesCallback {
+        fn frames(&self) -> &[Vec<String>] {
+            &self.frames
+        }
+    }
+
+    impl<R: io::AsyncRead + Unpin> crate::Callback<R> for EntriesCallback {
+        fn frame_start(&mut self) {
+            self.current.clear();
+        }
+
+        async fn message_received<'a>(
+            &'a mut self,
+            mut body: crate::types::MessageBody<'a, R>,
+        ) -> Result<(), crate::Error> {
+            let r = match body.read().await? {
+                crate::types::MessageBodyEnum::GetListResponse(r) => r,
+                _ => return Ok(()),
+            };
+
+            let mut entries = r.entries().await?;
+            while let Some(reading) = entries.next().await? {
+                let value = match reading.value {
+                    super::Value::Boolean(v) => v.to_string(),
+                    super::Value::Integer(v) => v.to_string(),
+                    super::Value::Unsigned(v) => v.to_string(),
+                    super::Value::Bytes(b) => format!("hex:{}", format_hex(b.as_slice())),
+                    super::Value::List => "list".to_string(),
+                };
+
+                self.current.push(format!(
+                    "{} status={} unit={} scaler={} value={}",
+                    reading.obis,
+                    format_opt(reading.status),
+                    format_opt(reading.unit),
+                    format_opt(reading.scaler),
+                    value
+                ));
+            }
+
+            Ok(())
+        }
+
+        fn frame_finished(&mut self, valid: bool) {
+            assert!(valid, "received invalid frame");
+            self.frames.push(core::mem::take(&mut self.current));
+        }
+    }
+
+    #[test_log::test]
+    fn entries() {
+        check_samples::<EntriesCallback>();
+    }
+}
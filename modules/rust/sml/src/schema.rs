@@ -0,0 +1,171 @@
+//! the types of the SML specification as data
+//!
+//! This is used to name the values of a [crate::tlv::dump], see [Annotator]. For decoding, use
+//! the generated [crate::types] instead.
+
+/// a type of the schema
+#[derive(Debug)]
+pub struct TypeInfo {
+    /// the name in the specification, e.g. `SML_ListEntry`
+    pub name: &'static str,
+    pub kind: Kind,
+}
+
+#[derive(Debug)]
+pub enum Kind {
+    Sequence(&'static [Field]),
+    SequenceOf(&'static [Member]),
+    Choice(&'static [Variant]),
+    ImplicitChoice(&'static [Member]),
+}
+
+/// a field of a sequence
+#[derive(Debug)]
+pub struct Field {
+    pub name: &'static str,
+    pub ty: &'static str,
+    pub optional: bool,
+}
+
+/// a possible type of the items of a sequence-of or of an implicit choice
+#[derive(Debug)]
+pub struct Member {
+    pub name: &'static str,
+    pub ty: &'static str,
+}
+
+/// a variant of a choice, selected by its tag
+#[derive(Debug)]
+pub struct Variant {
+    pub name: &'static str,
+    pub tag: u64,
+    pub ty: &'static str,
+}
+
+include!(concat!(env!("OUT_DIR"), "/schema.rs"));
+
+/// resolve aliases of primitive types, e.g. `SML_Timestamp` to `Unsigned32`
+pub fn resolve(name: &str) -> &str {
+    match TYPEDEFS.binary_search_by(|(other, _)| (*other).cmp(name)) {
+        Ok(index) => TYPEDEFS[index].1,
+        Err(_) => name,
+    }
+}
+
+/// look up a type by its name, returns [None] for primitive types
+pub fn lookup(name: &str) -> Option<&'static TypeInfo> {
+    let name = resolve(name);
+    TYPES
+        .binary_search_by(|ty| ty.name.cmp(name))
+        .ok()
+        .map(|index| &TYPES[index])
+}
+
+/// types worth mentioning in a dump, the TLV type is written anyway
+fn is_named(name: &str) -> bool {
+    lookup(name).is_some() || resolve(name) != name
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+struct Level {
+    ty: Option<&'static TypeInfo>,
+    /// the index of the next item
+    index: usize,
+    /// the variant of a choice, after its tag was read
+    variant: Option<&'static Variant>,
+}
+
+/// names the items of a [crate::tlv::dump_annotated] with the field names of the schema
+///
+/// Items of unknown types, e.g. vendor specific choice variants, are written without names.
+#[derive(Debug)]
+pub struct Annotator {
+    levels: [Level; crate::tlv::DUMP_MAX_DEPTH],
+    depth: usize,
+    /// the type of the last item, in case it's a list
+    last: Option<&'static str>,
+}
+
+impl Annotator {
+    /// `root` is the type of the dumped list, e.g. `SML_Message`
+    pub fn new(root: &str) -> Self {
+        let mut levels = [Level::default(); crate::tlv::DUMP_MAX_DEPTH];
+        levels[0].ty = lookup(root);
+
+        Self {
+            levels,
+            depth: 0,
+            last: None,
+        }
+    }
+
+    fn level(&mut self) -> Option<&mut Level> {
+        self.levels.get_mut(self.depth)
+    }
+}
+
+impl crate::tlv::Annotate for Annotator {
+    fn item(&mut self) -> crate::tlv::Label {
+        let (name, ty) = match self.level() {
+            None => (None, None),
+            Some(level) => {
+                let index = level.index;
+                level.index += 1;
+
+                match level.ty.map(|ty| &ty.kind) {
+                    Some(Kind::Sequence(fields)) => {
+                        fields.get(index).map(|f| (f.name, f.ty)).unzip()
+                    }
+                    Some(Kind::SequenceOf([member])) => (Some(member.name), Some(member.ty)),
+                    Some(Kind::Choice(_)) if index == 0 => (Some("tag"), None),
+                    Some(Kind::Choice(_)) => level.variant.map(|v| (v.name, v.ty)).unzip(),
+                    _ => (None, None),
+                }
+            }
+        };
+
+        self.last = ty;
+        crate::tlv::Label {
+            name,
+            note: ty.filter(|ty| is_named(ty)),
+        }
+    }
+
+    fn unsigned(&mut self, value: u64) -> Option<&'static str> {
+        let level = self.level()?;
+
+        match level.ty.map(|ty| &ty.kind) {
+            Some(Kind::Choice(variants)) if level.index == 1 => {
+                level.variant = variants.iter().find(|v| v.tag == value);
+                level.variant.map(|v| v.name)
+            }
+            _ => None,
+        }
+    }
+
+    fn enter_list(&mut self) {
+        let mut ty = self.last.and_then(lookup);
+
+        // the list variant of an implicit choice, e.g. `smlList` of `SML_Value`
+        if let Some(TypeInfo {
+            kind: Kind::ImplicitChoice(members),
+            ..
+        }) = ty
+        {
+            ty = members.iter().find_map(|member| lookup(member.ty));
+        }
+
+        self.depth += 1;
+        self.last = None;
+        if let Some(level) = self.level() {
+            *level = Level {
+                ty,
+                ..Level::default()
+            };
+        }
+    }
+
+    fn leave_list(&mut self) {
+        self.depth = self.depth.saturating_sub(1);
+    }
+}
@@ -0,0 +1,105 @@
+//! helpers shared by the tests which decode the samples in `testdata/`
+
+/// the reader the callbacks get when decoding a sample with [decode]
+pub(crate) type SampleReader<'r, 'd> = crate::message::CheckingReader<
+    'r,
+    crate::frame::CheckingReader<
+        'r,
+        io::BufReader<io::FuturesUtilReader<futures_util::io::Cursor<&'d [u8]>>, 64>,
+    >,
+>;
+
+/// a [crate::Callback] which collects the list entries of every frame
+pub(crate) trait Collect {
+    /// the entries of the frames received so far, in the format of the `.expected` files
+    fn frames(&self) -> &[Vec<String>];
+}
+
+pub(crate) fn format_obis(obis: &[u8]) -> String {
+    match obis {
+        [a, b, c, d, e, f] => format!("{}-{}:{}.{}.{}*{}", a, b, c, d, e, f),
+        other => format!("hex:{}", format_hex(other)),
+    }
+}
+
+pub(crate) fn format_hex(data: &[u8]) -> String {
+    data.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub(crate) fn format_opt<T: ToString>(v: Option<T>) -> String {
+    v.map(|v| v.to_string()).unwrap_or_else(|| "-".to_string())
+}
+
+pub(crate) fn parse_expected(expected: &str) -> Vec<Vec<String>> {
+    let mut frames = Vec::new();
+
+    for line in expected.lines() {
+        if line == "[frame]" {
+            frames.push(Vec::new());
+        } else if !line.is_empty() {
+            frames
+                .last_mut()
+                .expect("entry before the first `[frame]`")
+                .push(line.to_string());
+        }
+    }
+
+    frames
+}
+
+/// all samples in `testdata/`
+pub(crate) fn captures() -> Vec<std::path::PathBuf> {
+    let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("testdata");
+    let mut captures: Vec<_> = std::fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().map_or(false, |ext| ext == "bin"))
+        .collect();
+    captures.sort();
+    assert!(!captures.is_empty());
+
+    captures
+}
+
+/// run the task until all of `data` was consumed
+pub(crate) async fn decode<'d, C>(data: &'d [u8], callback: &mut C)
+where
+    C: for<'r> crate::Callback<SampleReader<'r, 'd>>,
+{
+    let mut reader = io::BufReader::new(io::FuturesUtilReader(futures_util::io::Cursor::new(data)));
+
+    // the task only ends when the data runs out
+    let res = crate::task(&mut reader, callback).await;
+    assert!(
+        matches!(res, Err(crate::Error::Io(io::Error::UnexpectedEof))),
+        "{:?}",
+        res
+    );
+}
+
+/// decode every sample in `testdata/` with a new `C` and compare with its `.expected` file
+pub(crate) fn check_samples<C>()
+where
+    C: Default + Collect + for<'r, 'd> crate::Callback<SampleReader<'r, 'd>>,
+{
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+
+    for capture in captures() {
+        log::info!("decode {}", capture.display());
+
+        let sampledata = std::fs::read(&capture).unwrap();
+        let expected = std::fs::read_to_string(capture.with_extension("expected")).unwrap();
+
+        let mut callback = C::default();
+        runtime.block_on(decode(&sampledata, &mut callback));
+
+        assert_eq!(
+            callback.frames(),
+            parse_expected(&expected),
+            "{}",
+            capture.display()
+        );
+    }
+}
@@ -38,6 +38,10 @@ impl<'a, R: io::AsyncRead + Unpin> String<'a, R> {
         self.len
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
     /// read the whole string to `buf`
     ///
     /// `buf` must be exactly the size of the string.
@@ -52,12 +56,24 @@ impl<'a, R: io::AsyncRead + Unpin> String<'a, R> {
 
         Ok(())
     }
+
+    /// read up to `buf.len()` bytes of the string, the rest gets skipped
+    ///
+    /// Returns the number of bytes read.
+    pub async fn read_truncated(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        let len = self.len.min(buf.len());
+
+        self.reader.reader().read_exact(&mut buf[..len]).await?;
+        self.len -= len;
+
+        Ok(len)
+    }
 }
 
 impl<'a, R> Drop for String<'a, R> {
     fn drop(&mut self) {
         log::trace!("drop string of length {}", self.len);
-        self.reader.remaining_bytes += self.len;
+        self.reader.defer_bytes(self.len);
     }
 }
 
@@ -68,7 +84,7 @@ pub struct Boolean<'a, R> {
 
 impl<'a, R> Drop for Boolean<'a, R> {
     fn drop(&mut self) {
-        self.reader.remaining_bytes += self.len;
+        self.reader.defer_bytes(self.len);
     }
 }
 
@@ -115,7 +131,7 @@ pub struct Integer<'a, R> {
 
 impl<'a, R> Drop for Integer<'a, R> {
     fn drop(&mut self) {
-        self.reader.remaining_bytes += self.len;
+        self.reader.defer_bytes(self.len);
     }
 }
 
@@ -152,7 +168,7 @@ pub struct Unsigned<'a, R> {
 
 impl<'a, R> Drop for Unsigned<'a, R> {
     fn drop(&mut self) {
-        self.reader.remaining_bytes += self.len;
+        self.reader.defer_bytes(self.len);
     }
 }
 
@@ -185,11 +201,14 @@ impl<'a, R: io::AsyncRead + Unpin> Unsigned<'a, R> {
 pub struct List<'a, R> {
     pub(crate) reader: &'a mut Reader<R>,
     pub(crate) len: usize,
+    /// items of enclosing lists which follow this one, see [List::enter_list]
+    trailing: usize,
 }
 impl<'a, R> Drop for List<'a, R> {
     fn drop(&mut self) {
         log::trace!("drop list of length {}", self.len);
-        self.reader.remaining_tlvs += self.len;
+        self.reader.defer_tlvs(self.len);
+        self.reader.defer_tlvs(self.trailing);
     }
 }
 
@@ -226,6 +245,10 @@ impl<'a, R: io::AsyncRead + Unpin> List<'a, R> {
         self.len
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
     pub async fn next(&mut self) -> Result<Option<Item<'_, R>>, Error> {
         if self.len == 0 {
             Ok(None)
@@ -254,6 +277,7 @@ impl<'a, R: io::AsyncRead + Unpin> List<'a, R> {
                 TlvType::List => Item::List(List {
                     reader: self.reader,
                     len,
+                    trailing: 0,
                 }),
                 TlvType::Other(ty) => return Err(Error::UnsupportedTlvType { ty }),
             }))
@@ -278,12 +302,41 @@ impl<'a, R: io::AsyncRead + Unpin> List<'a, R> {
         Ok(())
     }
 
+    /// continue with the items of the next item, which has to be a list
+    ///
+    /// Afterwards this list behaves like the nested one, so that one doesn't have to borrow from
+    /// this list. The items of this list after the nested one get skipped when it's dropped.
+    pub async fn enter_list(&mut self) -> Result<(), Error> {
+        if self.len == 0 {
+            return Err(Error::EndOfList);
+        }
+
+        let (ty, len) = self.reader.read_tlv().await?;
+        self.len -= 1;
+
+        match ty {
+            TlvType::List => {
+                self.trailing = self
+                    .trailing
+                    .checked_add(self.len)
+                    .ok_or(Error::SkipOverflow)?;
+                self.len = len;
+                Ok(())
+            }
+            TlvType::String if len == 0 => Err(Error::NoneTlv),
+            ty => {
+                self.reader.defer_bytes(len);
+                Err(Error::UnexpectedTlv { ty, len })
+            }
+        }
+    }
+
     pub async fn skip(&mut self, num: usize) -> Result<(), Error> {
         if num > self.len {
             return Err(Error::EndOfList);
         }
 
-        self.reader.remaining_tlvs += num;
+        self.reader.defer_tlvs(num);
         self.len -= num;
 
         Ok(())
@@ -315,6 +368,26 @@ pub struct Reader<R> {
     reader: R,
     remaining_bytes: usize,
     remaining_tlvs: usize,
+    /// the things left to skip didn't fit into the counters, so the position is lost
+    skip_overflow: bool,
+}
+
+impl<R> Reader<R> {
+    /// skip `num` more bytes before reading the next TLV
+    fn defer_bytes(&mut self, num: usize) {
+        match self.remaining_bytes.checked_add(num) {
+            Some(v) => self.remaining_bytes = v,
+            None => self.skip_overflow = true,
+        }
+    }
+
+    /// skip `num` more TLVs before reading the next one
+    fn defer_tlvs(&mut self, num: usize) {
+        match self.remaining_tlvs.checked_add(num) {
+            Some(v) => self.remaining_tlvs = v,
+            None => self.skip_overflow = true,
+        }
+    }
 }
 
 impl<R: io::AsyncRead + Unpin> Reader<R> {
@@ -323,6 +396,7 @@ impl<R: io::AsyncRead + Unpin> Reader<R> {
             reader,
             remaining_bytes: 0,
             remaining_tlvs: 0,
+            skip_overflow: false,
         }
     }
 
@@ -345,7 +419,11 @@ impl<R: io::AsyncRead + Unpin> Reader<R> {
                 });
             }
 
-            len = len.checked_shl(4).ok_or(Error::TlvLengthTooBig)?;
+            // `checked_shl` only checks the shift amount, not if bits get lost
+            if len > usize::MAX >> 4 {
+                return Err(Error::TlvLengthTooBig);
+            }
+            len <<= 4;
             len = len
                 .checked_add(header.len().into())
                 .ok_or(Error::TlvLengthTooBig)?;
@@ -353,7 +431,8 @@ impl<R: io::AsyncRead + Unpin> Reader<R> {
             header_len += 1;
         }
 
-        match header.ty() {
+        // the type is only stored in the first header byte
+        match &ty {
             // for lists the length doesn't include the header
             TlvType::List => (),
             TlvType::String if len == 0 => return Err(Error::EndOfSmlMessage),
@@ -373,6 +452,10 @@ impl<R: io::AsyncRead + Unpin> Reader<R> {
     }
 
     pub async fn skip_now(&mut self) -> Result<(), Error> {
+        if self.skip_overflow {
+            return Err(Error::SkipOverflow);
+        }
+
         if self.remaining_bytes > 0 {
             self.skip_bytes(self.remaining_bytes).await?;
             self.remaining_bytes = 0;
@@ -395,7 +478,11 @@ impl<R: io::AsyncRead + Unpin> Reader<R> {
     pub async fn read_list(&mut self) -> Result<List<'_, R>, Error> {
         let (ty, len) = self.read_tlv().await?;
         match ty {
-            TlvType::List => Ok(List { reader: self, len }),
+            TlvType::List => Ok(List {
+                reader: self,
+                len,
+                trailing: 0,
+            }),
             _ => Err(Error::UnexpectedTlv { ty, len }),
         }
     }
@@ -403,10 +490,11 @@ impl<R: io::AsyncRead + Unpin> Reader<R> {
     async fn skip_bytes(&mut self, mut num: usize) -> Result<(), Error> {
         log::trace!("skip {} bytes", num);
 
-        let mut buf = [0u8; 4];
+        // every byte has to go through the checksums, so this can't skip the data of the reader
+        let mut buf = [0u8; 16];
 
         while num > 0 {
-            let readlen = num.min(4);
+            let readlen = num.min(buf.len());
 
             self.reader.read_exact(&mut buf[0..readlen]).await?;
 
@@ -416,6 +504,28 @@ impl<R: io::AsyncRead + Unpin> Reader<R> {
         Ok(())
     }
 
+    /// read `num` bytes and write them as hex
+    async fn dump_hex<W: core::fmt::Write + ?Sized>(
+        &mut self,
+        mut num: usize,
+        out: &mut W,
+    ) -> Result<(), Error> {
+        let mut buf = [0u8; 16];
+
+        while num > 0 {
+            let readlen = num.min(buf.len());
+            self.reader.read_exact(&mut buf[0..readlen]).await?;
+
+            for b in &buf[0..readlen] {
+                write!(out, "{:02x}", b)?;
+            }
+
+            num -= readlen;
+        }
+
+        Ok(())
+    }
+
     /// skip the given number of TLVs recursively
     ///
     /// for list TLVs, all the list items are skipped as well
@@ -441,7 +551,7 @@ impl<R: io::AsyncRead + Unpin> Reader<R> {
                 TlvType::List => {
                     // For skipping the length doesn't matter.
                     // Pretend we're processing a longer list
-                    num += len;
+                    num = num.checked_add(len).ok_or(Error::TlvLengthTooBig)?;
                 }
                 _ => {
                     self.skip_bytes(len).await?;
@@ -454,7 +564,309 @@ impl<R: io::AsyncRead + Unpin> Reader<R> {
     }
 }
 
+/// how deeply [dump] descends into nested lists
+pub const DUMP_MAX_DEPTH: usize = 16;
+
+/// what an [Annotate] knows about the next item of a dump
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Label {
+    /// written in front of the item, e.g. the field name
+    pub name: Option<&'static str>,
+    /// written after the item, e.g. its type
+    pub note: Option<&'static str>,
+}
+
+/// names the items written by [dump_annotated]
+///
+/// The functions are called in the order the items are received, so implementations can track
+/// where in a message the dump currently is.
+pub trait Annotate {
+    /// called before each item
+    fn item(&mut self) -> Label {
+        Label::default()
+    }
+
+    /// the current item is an unsigned number, returns a note which replaces the one of its
+    /// [Label], e.g. to name the variant of a choice
+    fn unsigned(&mut self, _value: u64) -> Option<&'static str> {
+        None
+    }
+
+    /// the current item is a list, the next items are its children
+    fn enter_list(&mut self) {}
+
+    /// all children of the innermost list were written
+    fn leave_list(&mut self) {}
+}
+
+impl Annotate for () {}
+
+/// read all items of `list` recursively and write them as an indented tree
+///
+/// Every item is written on its own line with its type, its length and its value. Strings are
+/// written as hex, lists as the number of items followed by the items on the next lines.
+pub async fn dump<R, W>(list: &mut List<'_, R>, out: &mut W) -> Result<(), Error>
+where
+    R: io::AsyncRead + Unpin,
+    W: core::fmt::Write + ?Sized,
+{
+    dump_annotated(list, out, &mut ()).await
+}
+
+/// like [dump], but with names and notes from `annotate`, see [crate::schema::Annotator]
+pub async fn dump_annotated<R, W, A>(
+    list: &mut List<'_, R>,
+    out: &mut W,
+    annotate: &mut A,
+) -> Result<(), Error>
+where
+    R: io::AsyncRead + Unpin,
+    W: core::fmt::Write + ?Sized,
+    A: Annotate + ?Sized,
+{
+    dump_indented(list, out, annotate, 0).await
+}
+
+/// the nested lists are tracked using a stack, so this doesn't need boxed recursive futures
+pub(crate) async fn dump_indented<R, W, A>(
+    list: &mut List<'_, R>,
+    out: &mut W,
+    annotate: &mut A,
+    indent: usize,
+) -> Result<(), Error>
+where
+    R: io::AsyncRead + Unpin,
+    W: core::fmt::Write + ?Sized,
+    A: Annotate + ?Sized,
+{
+    let reader = &mut *list.reader;
+    let mut remaining = [0usize; DUMP_MAX_DEPTH];
+    let mut depth = 0;
+    remaining[0] = core::mem::take(&mut list.len);
+
+    loop {
+        if remaining[depth] == 0 {
+            if depth == 0 {
+                break;
+            }
+
+            depth -= 1;
+            annotate.leave_list();
+            continue;
+        }
+        remaining[depth] -= 1;
+
+        let label = annotate.item();
+        for _ in 0..indent + depth {
+            out.write_str("  ")?;
+        }
+        if let Some(name) = label.name {
+            write!(out, "{}: ", name)?;
+        }
+
+        let (ty, len) = match reader.read_tlv().await {
+            Err(Error::EndOfSmlMessage) => {
+                writeln!(out, "end of message")?;
+                continue;
+            }
+            other => other?,
+        };
+
+        let mut note = label.note;
+        match ty {
+            TlvType::String if len == 0 => out.write_str("none")?,
+            TlvType::String => {
+                write!(out, "string[{}] ", len)?;
+                reader.dump_hex(len, out).await?;
+            }
+            TlvType::Boolean => {
+                let value = Boolean { reader, len }.into_bool().await?;
+                write!(out, "boolean[{}] {}", len, value)?;
+            }
+            TlvType::Integer => {
+                write!(out, "integer[{}] ", len)?;
+                match len {
+                    1..=8 => write!(out, "{}", Integer { reader, len }.into_i64_relaxed().await?)?,
+                    _ => reader.dump_hex(len, out).await?,
+                }
+            }
+            TlvType::Unsigned => {
+                write!(out, "unsigned[{}] ", len)?;
+                match len {
+                    1..=8 => {
+                        let value = Unsigned { reader, len }.into_u64_relaxed().await?;
+                        write!(out, "{}", value)?;
+                        note = annotate.unsigned(value).or(note);
+                    }
+                    _ => reader.dump_hex(len, out).await?,
+                }
+            }
+            TlvType::List => write!(out, "list[{}]", len)?,
+            TlvType::Other(ty) => return Err(Error::UnsupportedTlvType { ty }),
+        }
+
+        if let Some(note) = note {
+            write!(out, " ({})", note)?;
+        }
+        writeln!(out)?;
+
+        if matches!(ty, TlvType::List) {
+            if depth + 1 == DUMP_MAX_DEPTH {
+                return Err(Error::NestingTooDeep);
+            }
+
+            depth += 1;
+            remaining[depth] = len;
+            annotate.enter_list();
+        }
+    }
+
+    Ok(())
+}
+
+/// encodes TLVs into a buffer
+///
+/// The counterpart to [Reader]. Lengths are passed the same way [Reader] reports them: the
+/// number of payload bytes for values and the number of items for lists.
+pub struct Writer<'a> {
+    buf: &'a mut [u8],
+    pos: usize,
+}
+
+impl<'a> Writer<'a> {
+    pub fn new(buf: &'a mut [u8]) -> Self {
+        Self { buf, pos: 0 }
+    }
+
+    /// the bytes written so far
+    pub fn written(&self) -> &[u8] {
+        &self.buf[..self.pos]
+    }
+
+    fn write_bytes(&mut self, data: &[u8]) -> Result<(), Error> {
+        let end = self
+            .pos
+            .checked_add(data.len())
+            .filter(|end| *end <= self.buf.len())
+            .ok_or(Error::BufferFull)?;
+
+        self.buf[self.pos..end].copy_from_slice(data);
+        self.pos = end;
+
+        Ok(())
+    }
+
+    /// write a TLV header, using as many header bytes as `len` requires
+    pub fn write_header(&mut self, ty: TlvType, len: usize) -> Result<(), Error> {
+        let is_list = matches!(ty, TlvType::List);
+
+        // for everything but lists, the length includes the header itself
+        let mut header_len = 1;
+        let total = loop {
+            let total = if is_list {
+                len
+            } else {
+                len.checked_add(header_len).ok_or(Error::TlvLengthTooBig)?
+            };
+            let bits = 4 * header_len;
+            if bits >= usize::BITS as usize || total >> bits == 0 {
+                break total;
+            }
+            header_len += 1;
+        };
+
+        let ty: u8 = ty.into();
+        for i in (0..header_len).rev() {
+            let mut byte = ((total >> (4 * i)) & 0x0f) as u8;
+            if i == header_len - 1 {
+                byte |= ty << 4;
+            }
+            if i != 0 {
+                byte |= 0x80;
+            }
+            self.write_bytes(&[byte])?;
+        }
+
+        Ok(())
+    }
+
+    pub fn write_string(&mut self, data: &[u8]) -> Result<(), Error> {
+        self.write_header(TlvType::String, data.len())?;
+        self.write_bytes(data)
+    }
+
+    /// write an unset optional value
+    pub fn write_none(&mut self) -> Result<(), Error> {
+        self.write_header(TlvType::String, 0)
+    }
+
+    /// write the end marker of a SML message
+    pub fn write_end_of_message(&mut self) -> Result<(), Error> {
+        self.write_bytes(&[0x00])
+    }
+
+    /// write the `crc16` field of a message which starts at offset `start`
+    ///
+    /// The checksum covers everything written since then, so this has to be called right after
+    /// the message body.
+    pub fn write_crc16(&mut self, start: usize) -> Result<(), Error> {
+        let data = self
+            .buf
+            .get(start..self.pos)
+            .ok_or(Error::UnexpectedValue)?;
+        let crc = crate::CRC_INSTANCE.checksum(data);
+
+        // like when reading, the byte order is swapped
+        self.write_unsigned(crc.swap_bytes().into(), 2)
+    }
+
+    pub fn write_bool(&mut self, v: bool) -> Result<(), Error> {
+        self.write_header(TlvType::Boolean, 1)?;
+        self.write_bytes(&[if v { 0xff } else { 0x00 }])
+    }
+
+    /// write `v` using the lowest `width` bytes
+    ///
+    /// Returns [Error::UnsupportedLen] if `v` can't be represented in `width` bytes.
+    pub fn write_integer(&mut self, v: i64, width: usize) -> Result<(), Error> {
+        if !(1..=8).contains(&width) {
+            return Err(Error::UnsupportedLen { len: width });
+        }
+        let shift = 64 - 8 * width as u32;
+        if (v << shift) >> shift != v {
+            return Err(Error::UnsupportedLen { len: width });
+        }
+
+        self.write_header(TlvType::Integer, width)?;
+        self.write_bytes(&v.to_be_bytes()[8 - width..])
+    }
+
+    /// write `v` using the lowest `width` bytes
+    ///
+    /// Returns [Error::UnsupportedLen] if `v` can't be represented in `width` bytes.
+    pub fn write_unsigned(&mut self, v: u64, width: usize) -> Result<(), Error> {
+        if !(1..=8).contains(&width) {
+            return Err(Error::UnsupportedLen { len: width });
+        }
+        if width < 8 && v >> (8 * width) != 0 {
+            return Err(Error::UnsupportedLen { len: width });
+        }
+
+        self.write_header(TlvType::Unsigned, width)?;
+        self.write_bytes(&v.to_be_bytes()[8 - width..])
+    }
+
+    /// write the header of a list with `len` items
+    ///
+    /// The items have to be written afterwards.
+    pub fn write_list(&mut self, len: usize) -> Result<(), Error> {
+        self.write_header(TlvType::List, len)
+    }
+}
+
 #[cfg(test)]
+// the integer tables are plain tuples and `tokio::test` trips `needless_return` on the loops
+#[allow(clippy::type_complexity, clippy::needless_return)]
 mod tests {
     use core::assert_matches::debug_assert_matches;
 
@@ -680,4 +1092,262 @@ mod tests {
             }
         }
     }
+
+    /// truncate `v` to `width` bytes and sign-extend it again
+    fn truncate_signed(v: i64, width: usize) -> i64 {
+        let shift = 64 - 8 * width as u32;
+        (v << shift) >> shift
+    }
+
+    fn truncate_unsigned(v: u64, width: usize) -> u64 {
+        if width == 8 {
+            v
+        } else {
+            v & ((1 << (8 * width)) - 1)
+        }
+    }
+
+    /// wrap a single value into a list, so it can be decoded using the public `Reader` API
+    fn encode_in_list(f: impl FnOnce(&mut super::Writer) -> Result<(), crate::Error>) -> Vec<u8> {
+        let mut buf = vec![0u8; 1024];
+        let mut writer = super::Writer::new(&mut buf);
+        writer.write_list(1).unwrap();
+        f(&mut writer).unwrap();
+        writer.written().to_vec()
+    }
+
+    fn block_on<F: core::future::Future>(f: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(f)
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn dump() {
+        let mut buf = [0u8; 64];
+        let mut writer = super::Writer::new(&mut buf);
+        writer.write_list(5).unwrap();
+        writer
+            .write_string(&[0x01, 0x00, 0x10, 0x07, 0x00, 0xff])
+            .unwrap();
+        writer.write_none().unwrap();
+        writer.write_list(2).unwrap();
+        writer.write_integer(-1, 1).unwrap();
+        writer.write_bool(true).unwrap();
+        writer.write_unsigned(0x0701, 4).unwrap();
+        writer.write_end_of_message().unwrap();
+        let encoded = writer.written().to_vec();
+
+        let mut reader = make_reader(&encoded);
+        let mut list = reader.read_list().await.unwrap();
+        let mut out = String::new();
+        super::dump(&mut list, &mut out).await.unwrap();
+
+        assert_eq!(
+            out,
+            "string[6] 0100100700ff\n\
+             none\n\
+             list[2]\n\
+             \x20 integer[1] -1\n\
+             \x20 boolean[1] true\n\
+             unsigned[4] 1793\n\
+             end of message\n"
+        );
+    }
+
+    /// the items after an entered list are skipped after the items of the entered list
+    #[test_log::test(tokio::test)]
+    async fn enter_list() {
+        let mut buf = [0u8; 64];
+        let mut writer = super::Writer::new(&mut buf);
+        writer.write_list(3).unwrap();
+        writer.write_none().unwrap();
+        writer.write_list(2).unwrap();
+        writer.write_unsigned(1, 1).unwrap();
+        writer.write_unsigned(2, 1).unwrap();
+        writer.write_unsigned(3, 1).unwrap();
+        writer.write_list(1).unwrap();
+        writer.write_unsigned(4, 1).unwrap();
+        let encoded = writer.written().to_vec();
+
+        let mut reader = make_reader(&encoded);
+        let mut list = reader.read_list().await.unwrap();
+        assert!(matches!(
+            list.enter_list().await,
+            Err(crate::Error::NoneTlv)
+        ));
+        list.enter_list().await.unwrap();
+        assert_eq!(list.len(), 2);
+        let v = list.next_unsigned().await.unwrap().into_u8().await.unwrap();
+        assert_eq!(v, 1);
+        drop(list);
+
+        let mut list = reader.read_list().await.unwrap();
+        let v = list.next_unsigned().await.unwrap().into_u8().await.unwrap();
+        assert_eq!(v, 4);
+    }
+
+    /// skipping more than the counters can hold is an error instead of losing the position
+    #[test_log::test(tokio::test)]
+    async fn skip_overflow() {
+        let mut buf = [0u8; 64];
+        let mut writer = super::Writer::new(&mut buf);
+        writer.write_list(2).unwrap();
+        writer.write_list(usize::MAX).unwrap();
+        writer.write_list(0).unwrap();
+        let encoded = writer.written().to_vec();
+
+        let mut reader = make_reader(&encoded);
+        let mut list = reader.read_list().await.unwrap();
+        let mut inner = list.next_list().await.unwrap();
+        inner.skip(usize::MAX).await.unwrap();
+        drop(inner);
+        list.skip(1).await.unwrap();
+        drop(list);
+
+        for _ in 0..2 {
+            let res = reader.read_list().await;
+            assert!(matches!(res, Err(crate::Error::SkipOverflow)));
+        }
+    }
+
+    proptest::proptest! {
+        #[test]
+        fn integer_roundtrip(v in proptest::num::i64::ANY, width in 1usize..=8) {
+            let v = truncate_signed(v, width);
+            let buf = encode_in_list(|w| w.write_integer(v, width));
+            proptest::prop_assert_eq!(buf.len(), 2 + width);
+
+            let (exact, relaxed32, relaxed64) = block_on(async {
+                let mut reader = make_reader(&buf);
+                let mut list = reader.read_list().await.unwrap();
+                let exact = match width {
+                    1 => list.next_integer().await.unwrap().into_i8().await.map(i64::from),
+                    2 => list.next_integer().await.unwrap().into_i16().await.map(i64::from),
+                    3..=4 => list.next_integer().await.unwrap().into_i32().await.map(i64::from),
+                    _ => list.next_integer().await.unwrap().into_i64().await,
+                };
+                drop(list);
+
+                let mut reader = make_reader(&buf);
+                let mut list = reader.read_list().await.unwrap();
+                let relaxed32 = list.next_integer().await.unwrap().into_i32_relaxed().await;
+                drop(list);
+
+                let mut reader = make_reader(&buf);
+                let mut list = reader.read_list().await.unwrap();
+                let relaxed64 = list.next_integer().await.unwrap().into_i64_relaxed().await;
+
+                (exact, relaxed32, relaxed64)
+            });
+
+            proptest::prop_assert_eq!(exact.unwrap(), v);
+            proptest::prop_assert_eq!(relaxed64.unwrap(), v);
+            if width <= 4 {
+                proptest::prop_assert_eq!(i64::from(relaxed32.unwrap()), v);
+            } else {
+                proptest::prop_assert!(
+                    matches!(relaxed32, Err(crate::Error::UnsupportedLen { len }) if len == width),
+                    "{:?}",
+                    relaxed32
+                );
+            }
+        }
+
+        #[test]
+        fn unsigned_roundtrip(v in proptest::num::u64::ANY, width in 1usize..=8) {
+            let v = truncate_unsigned(v, width);
+            let buf = encode_in_list(|w| w.write_unsigned(v, width));
+            proptest::prop_assert_eq!(buf.len(), 2 + width);
+
+            let (exact, relaxed32, relaxed64) = block_on(async {
+                let mut reader = make_reader(&buf);
+                let mut list = reader.read_list().await.unwrap();
+                let exact = match width {
+                    1 => list.next_unsigned().await.unwrap().into_u8().await.map(u64::from),
+                    2 => list.next_unsigned().await.unwrap().into_u16().await.map(u64::from),
+                    3..=4 => list.next_unsigned().await.unwrap().into_u32().await.map(u64::from),
+                    _ => list.next_unsigned().await.unwrap().into_u64().await,
+                };
+                drop(list);
+
+                let mut reader = make_reader(&buf);
+                let mut list = reader.read_list().await.unwrap();
+                let relaxed32 = list.next_unsigned().await.unwrap().into_u32_relaxed().await;
+                drop(list);
+
+                let mut reader = make_reader(&buf);
+                let mut list = reader.read_list().await.unwrap();
+                let relaxed64 = list.next_unsigned().await.unwrap().into_u64_relaxed().await;
+
+                (exact, relaxed32, relaxed64)
+            });
+
+            proptest::prop_assert_eq!(exact.unwrap(), v);
+            proptest::prop_assert_eq!(relaxed64.unwrap(), v);
+            if width <= 4 {
+                proptest::prop_assert_eq!(u64::from(relaxed32.unwrap()), v);
+            } else {
+                proptest::prop_assert!(
+                    matches!(relaxed32, Err(crate::Error::UnsupportedLen { len }) if len == width),
+                    "{:?}",
+                    relaxed32
+                );
+            }
+        }
+
+        #[test]
+        fn integer_out_of_range(v in proptest::num::i64::ANY, width in 1usize..=7) {
+            let mut buf = [0u8; 16];
+            let res = super::Writer::new(&mut buf).write_integer(v, width);
+            if truncate_signed(v, width) == v {
+                proptest::prop_assert!(res.is_ok());
+            } else {
+                proptest::prop_assert!(
+                    matches!(res, Err(crate::Error::UnsupportedLen { len }) if len == width),
+                    "{:?}",
+                    res
+                );
+            }
+        }
+
+        /// strings and lists long enough to need `has_more` header chains, skipped by dropping
+        /// them, followed by a value which has to be decoded from the right offset
+        #[test]
+        fn multibyte_headers(
+            strings in proptest::collection::vec(proptest::collection::vec(proptest::num::u8::ANY, 1..600), 1..4),
+            list_len in 0usize..2000,
+            trailer in proptest::num::u32::ANY,
+        ) {
+            let mut buf = vec![0u8; 8192];
+            let mut writer = super::Writer::new(&mut buf);
+            writer.write_list(strings.len() + 2).unwrap();
+            for s in &strings {
+                writer.write_string(s).unwrap();
+            }
+            writer.write_list(list_len).unwrap();
+            for _ in 0..list_len {
+                writer.write_none().unwrap();
+            }
+            writer.write_unsigned(trailer.into(), 4).unwrap();
+            let encoded = writer.written().to_vec();
+
+            let (lens, decoded_list_len, decoded) = block_on(async {
+                let mut reader = make_reader(&encoded);
+                let mut list = reader.read_list().await.unwrap();
+                let mut lens = Vec::new();
+                for _ in &strings {
+                    lens.push(list.next_string().await.unwrap().len());
+                }
+                let decoded_list_len = list.next_list().await.unwrap().len();
+                let decoded = list.next_unsigned().await.unwrap().into_u32().await.unwrap();
+                (lens, decoded_list_len, decoded)
+            });
+
+            proptest::prop_assert_eq!(lens, strings.iter().map(Vec::len).collect::<Vec<_>>());
+            proptest::prop_assert_eq!(decoded_list_len, list_len);
+            proptest::prop_assert_eq!(decoded, trailer);
+        }
+    }
 }
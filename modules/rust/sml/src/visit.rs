@@ -0,0 +1,141 @@
+//! walks through SML messages and calls a [Visitor] for every value
+//!
+//! This is an alternative to reading the [crate::types] field by field, for consumers which are
+//! only interested in a few types or want to process everything the same way.
+
+use crate::types;
+
+/// where a value is located within its parent type
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Field {
+    /// the name of the parent type in the specification, e.g. `SML_ListEntry`
+    pub ty: &'static str,
+    /// the name of the field or choice variant in the specification, e.g. `objName`
+    pub name: &'static str,
+}
+
+include!(concat!(env!("OUT_DIR"), "/visitor.rs"));
+
+/// walk through a message body, e.g. from [crate::Callback::message_received]
+pub async fn walk<R, V>(
+    visitor: &mut V,
+    body: types::MessageBody<'_, R>,
+) -> Result<(), crate::Error>
+where
+    R: io::AsyncRead + Unpin,
+    V: Visitor<R> + ?Sized,
+{
+    let field = Field {
+        ty: "SML_Message",
+        name: "messageBody",
+    };
+
+    visitor.visit_message_body(field, body).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Field;
+    use crate::test_support::{check_samples, format_hex, format_obis, format_opt, Collect};
+
+    /// builds the same lines as the `.expected` files, only from primitive values
+    #[derive(Default)]
+    struct EntryVisitor {
+        lines: Vec<String>,
+        obis: String,
+        status: Option<u64>,
+        unit: Option<u64>,
+        scaler: Option<i64>,
+    }
+
+    impl EntryVisitor {
+        fn push(&mut self, value: String) {
+            self.lines.push(format!(
+                "{} status={} unit={} scaler={} value={}",
+                self.obis,
+                format_opt(self.status.take()),
+                format_opt(self.unit.take()),
+                format_opt(self.scaler.take()),
+                value
+            ));
+        }
+    }
+
+    impl<R: io::AsyncRead + Unpin> super::Visitor<R> for EntryVisitor {
+        async fn visit_unsigned(&mut self, field: Field, value: u64) -> Result<(), crate::Error> {
+            match (field.ty, field.name) {
+                ("SML_Status", _) => self.status = Some(value),
+                ("SML_ListEntry", "unit") => self.unit = Some(value),
+                ("SML_Value", _) => self.push(value.to_string()),
+                _ => (),
+            }
+            Ok(())
+        }
+
+        async fn visit_integer(&mut self, field: Field, value: i64) -> Result<(), crate::Error> {
+            match (field.ty, field.name) {
+                ("SML_ListEntry", "scaler") => self.scaler = Some(value),
+                ("SML_Value", _) => self.push(value.to_string()),
+                _ => (),
+            }
+            Ok(())
+        }
+
+        async fn visit_boolean(&mut self, field: Field, value: bool) -> Result<(), crate::Error> {
+            if field.ty == "SML_Value" {
+                self.push(value.to_string());
+            }
+            Ok(())
+        }
+
+        async fn visit_octet_string(
+            &mut self,
+            field: Field,
+            mut value: crate::tlv::String<'_, R>,
+        ) -> Result<(), crate::Error> {
+            let mut buf = vec![0; value.len()];
+            value.read(&mut buf).await?;
+
+            match (field.ty, field.name) {
+                ("SML_ListEntry", "objName") => self.obis = format_obis(&buf),
+                ("SML_Value", _) => self.push(format!("hex:{}", format_hex(&buf))),
+                _ => (),
+            }
+            Ok(())
+        }
+    }
+
+    #[derive(Default)]
+    struct WalkCallback {
+        visitor: EntryVisitor,
+        frames: Vec<Vec<String>>,
+    }
+
+    impl Collect for WalkCallback {
+        fn frames(&self) -> &[Vec<String>] {
+            &self.frames
+        }
+    }
+
+    impl<R: io::AsyncRead + Unpin> crate::Callback<R> for WalkCallback {
+        fn frame_start(&mut self) {}
+
+        async fn message_received<'a>(
+            &'a mut self,
+            body: crate::types::MessageBody<'a, R>,
+        ) -> Result<(), crate::Error> {
+            super::walk(&mut self.visitor, body).await
+        }
+
+        fn frame_finished(&mut self, valid: bool) {
+            assert!(valid, "received invalid frame");
+            self.frames.push(core::mem::take(&mut self.visitor.lines));
+        }
+    }
+
+    /// walking the whole message has to produce the same values as reading it manually
+    #[test_log::test]
+    fn walk_captures() {
+        check_samples::<WalkCallback>();
+    }
+}
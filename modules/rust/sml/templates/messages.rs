@@ -127,9 +127,35 @@
                     {{fieldty}}::parse_field(&mut self.list).await
                 }
 
+                {% if field.optional && !self.is_implicit_choice(field.ty) %}
+                    /// like `parse`, but returns [None] if the optional field wasn't set
+                    pub async fn parse_opt<'s>(&'s mut self) -> Result<Option<{{fieldty}}<'s, Reader>>, crate::Error>
+                    {
+                        if self.parsed {
+                            return Err(crate::Error::CantParseTwice);
+                        }
+                        self.parsed = true;
+
+                        match self.list.next_any().await? {
+                            crate::tlv::Item::None => Ok(None),
+                            item => Ok(Some({{fieldty}}::from_tlv_item(item).await?)),
+                        }
+                    }
+                {% endif %}
+
+                /// continue with the next field
+                ///
+                /// If the field wasn't parsed, a mandatory field is parsed to check its type
+                /// and then skipped. An optional field is skipped without any checks, because it
+                /// may not be set.
                 pub async fn finish(mut self) -> Result<NextTy, crate::Error> {
                     if !self.parsed {
-                        {{fieldty}}::parse_field(&mut self.list).await?;
+                        {% if field.optional %}
+                            // the field might not be set, so it can't be parsed
+                            self.list.skip(1).await?;
+                        {% else %}
+                            {{fieldty}}::parse_field(&mut self.list).await?;
+                        {% endif %}
                     }
 
                     Ok(NextTy::from_tlv_list(self.list))
@@ -163,9 +189,18 @@
         }
     }
 
+    impl<'a, R: 'a> FromTlvItem<'a, R> for {{structname}}<'a, R> {
+        async fn from_tlv_item(item: crate::tlv::Item<'a, R>) -> Result<Self, crate::Error> {
+            match item {
+                crate::tlv::Item::List(list) => Ok(Self {list}),
+                _ => Err(crate::Error::UnexpectedValue),
+            }
+        }
+    }
+
     impl<'a, R: io::AsyncRead + Unpin> {{structname}} <'a, R> {
         pub async fn next<'s>(&'s mut self) -> Result<Option<{{valuetype}}<'s, R>>, crate::Error> {
-            if self.list.len() == 0 {
+            if self.list.is_empty() {
                 return Ok(None);
             }
 
@@ -190,7 +225,7 @@
     impl<'a: 'b, 'b, R: io::AsyncRead + Unpin + 'a> ParseField<'a, 'b, R> for {{structname}}<'a, R> {
         async fn parse_field<'l: 'b>(list: &'l mut crate::tlv::List<'a, R>) -> Result<Self, crate::Error> {
             log::error!("not implemented: {{structname}}");
-            Err(crate::Error::UnexpectedValue)
+            Err(crate::Error::Unimplemented)
         }
     }
 {% endmacro %}
@@ -219,7 +254,7 @@
     {% call render_variant_enum(structname, choice.variants.borrow()) %}
 
     pub struct {{structname}} <'r, R> {
-        list: crate::tlv::List<'r, R>,
+        pub(crate) list: crate::tlv::List<'r, R>,
         parsed: bool,
     }
 
@@ -297,6 +332,11 @@
     }
 
     impl<'a, R: io::AsyncRead + Unpin + 'a> {{structname}} <'a, R> {
+        /// returns `true` if an optional field wasn't set
+        pub fn is_none(&self) -> bool {
+            matches!(self.item, crate::tlv::Item::None)
+        }
+
         pub async fn read(self) -> Result<{{structname}}Enum{{enum_generics}}, crate::Error> {
             match &self.item {
                 {% for (variantname, variant) in choice.types.borrow() %}
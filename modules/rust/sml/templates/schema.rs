@@ -0,0 +1,49 @@
+/// all types of the schema, sorted by name
+pub static TYPES: &[TypeInfo] = &[
+    {% for (typename, ty) in types %}
+        TypeInfo {
+            name: {{ "{:?}"|format(typename) }},
+            {% match ty %}
+            {% when Type::Sequence with (seq) %}
+                kind: Kind::Sequence(&[
+                    {% for field in seq.fields %}
+                        Field {
+                            name: {{ "{:?}"|format(field.name) }},
+                            ty: {{ "{:?}"|format(field.ty) }},
+                            optional: {{field.optional}},
+                        },
+                    {% endfor %}
+                ]),
+            {% when Type::SequenceOf with (seq) %}
+                kind: Kind::SequenceOf(&[
+                    {% for (name, ty) in seq.types %}
+                        Member { name: {{ "{:?}"|format(name) }}, ty: {{ "{:?}"|format(ty) }} },
+                    {% endfor %}
+                ]),
+            {% when Type::Choice with (choice) %}
+                kind: Kind::Choice(&[
+                    {% for (name, variant) in choice.variants %}
+                        Variant {
+                            name: {{ "{:?}"|format(name) }},
+                            tag: {{variant.value}},
+                            ty: {{ "{:?}"|format(variant.ty) }},
+                        },
+                    {% endfor %}
+                ]),
+            {% when Type::ImplicitChoice with (choice) %}
+                kind: Kind::ImplicitChoice(&[
+                    {% for (name, ty) in choice.types %}
+                        Member { name: {{ "{:?}"|format(name) }}, ty: {{ "{:?}"|format(ty) }} },
+                    {% endfor %}
+                ]),
+            {% endmatch %}
+        },
+    {% endfor %}
+];
+
+/// aliases of primitive types, e.g. `SML_Timestamp` for `Unsigned32`, sorted by name
+pub static TYPEDEFS: &[(&str, &str)] = &[
+    {% for (name, ty) in typedefs %}
+        ({{ "{:?}"|format(name) }}, {{ "{:?}"|format(ty) }}),
+    {% endfor %}
+];
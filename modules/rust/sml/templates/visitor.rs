@@ -0,0 +1,176 @@
+{% macro walk_sequence(rawname, seq) %}
+    let next = value;
+
+    {% for (id, field) in seq.fields.iter().enumerate() %}
+        {% let fieldty = self.base.type2rust(field.ty) %}
+        {% let fieldname = field.name() %}
+        {% let is_last = seq.fields.get(id.clone() + 1).is_none() %}
+
+        // {{field.name}}
+        {% if crate::is_primitive_rust(fieldty) %}
+            {% if is_last %}
+                let v = next.{{fieldname}}().await?;
+            {% else %}
+                let (next, v) = next.{{fieldname}}().await?;
+            {% endif %}
+
+            {% if field.optional %}
+                match v {
+                    Some(v) => {{self.visit_call(field.ty, rawname, field.name, "v")}},
+                    None => {{self.visit_none_call(rawname, field.name)}},
+                }
+            {% else %}
+                {{self.visit_call(field.ty, rawname, field.name, "v")}};
+            {% endif %}
+        {% else if fieldty == "EndOfSmlMsg" %}
+            next.{{fieldname}}().await?;
+        {% else %}
+            let mut field = next.{{fieldname}}().await?;
+
+            {% if field.optional && self.base.is_implicit_choice(field.ty) %}
+                {
+                    let v = field.parse().await?;
+                    if v.is_none() {
+                        {{self.visit_none_call(rawname, field.name)}};
+                    } else {
+                        {{self.visit_call(field.ty, rawname, field.name, "v")}};
+                    }
+                }
+            {% else if field.optional %}
+                match field.parse_opt().await? {
+                    Some(v) => {{self.visit_call(field.ty, rawname, field.name, "v")}},
+                    None => {{self.visit_none_call(rawname, field.name)}},
+                }
+            {% else %}
+                {{self.visit_call(field.ty, rawname, field.name, "field.parse().await?")}};
+            {% endif %}
+
+            {% if is_last %}
+                field.finish().await?;
+            {% else %}
+                let next = field.finish().await?;
+            {% endif %}
+        {% endif %}
+    {% endfor %}
+
+    Ok(())
+{% endmacro %}
+
+{% macro walk_choice(rawname, structname, variants, read_by_ref) %}
+    {% if read_by_ref %}
+        let mut value = value;
+    {% endif %}
+
+    match value.read().await? {
+        {% for (variantname, variant) in variants %}
+            {% let variantident = crate::str2ident(variantname, Case::Pascal) %}
+
+            types::{{structname}}Enum::{{variantident}}(v) => {
+                {{self.visit_call(variant.as_ref(), rawname, variantname, "v")}};
+            }
+        {% endfor %}
+    }
+
+    Ok(())
+{% endmacro %}
+
+/// callbacks for walking through SML messages
+///
+/// There's one function per SML type. The default implementations walk into the value and call
+/// the functions of the contained types, down to the `visit_unsigned`, `visit_integer`,
+/// `visit_boolean`, `visit_octet_string` and `visit_none` functions for primitive values.
+///
+/// To handle a type manually, override its function. The corresponding `walk_*` function can be
+/// used to continue walking into it afterwards.
+pub trait Visitor<R: io::AsyncRead + Unpin> {
+    async fn visit_unsigned(&mut self, _field: Field, _value: u64) -> Result<(), crate::Error> {
+        Ok(())
+    }
+
+    async fn visit_integer(&mut self, _field: Field, _value: i64) -> Result<(), crate::Error> {
+        Ok(())
+    }
+
+    async fn visit_boolean(&mut self, _field: Field, _value: bool) -> Result<(), crate::Error> {
+        Ok(())
+    }
+
+    /// strings that aren't read are skipped
+    async fn visit_octet_string(
+        &mut self,
+        _field: Field,
+        _value: crate::tlv::String<'_, R>,
+    ) -> Result<(), crate::Error> {
+        Ok(())
+    }
+
+    /// an optional field which wasn't set
+    async fn visit_none(&mut self, _field: Field) -> Result<(), crate::Error> {
+        Ok(())
+    }
+
+    {% for (typename, ty) in base.types %}
+        {% let structname = self.base.type2rust(typename) %}
+
+        {% if self.recursive.contains(typename.as_str()) %}
+            /// `{{typename}}`
+            ///
+            /// This type can contain itself, so it's not walked into by default. Walking into it
+            /// requires boxing the future returned by [{{self.walk_fn(typename)}}].
+            async fn {{self.visit_fn(typename)}}(
+                &mut self,
+                _field: Field,
+                _value: types::{{structname}}<'_, R>,
+            ) -> Result<(), crate::Error> {
+                Ok(())
+            }
+        {% else %}
+            /// `{{typename}}`
+            async fn {{self.visit_fn(typename)}}(
+                &mut self,
+                _field: Field,
+                value: types::{{structname}}<'_, R>,
+            ) -> Result<(), crate::Error> {
+                {{self.walk_fn(typename)}}(self, value).await
+            }
+        {% endif %}
+    {% endfor %}
+}
+
+{% for (typename, ty) in base.types %}
+    {% let structname = self.base.type2rust(typename) %}
+
+    /// call the visitor functions for everything contained in a `{{typename}}`
+    pub async fn {{self.walk_fn(typename)}}<R, V>(
+        visitor: &mut V,
+        value: types::{{structname}}<'_, R>,
+    ) -> Result<(), crate::Error>
+    where
+        R: io::AsyncRead + Unpin,
+        V: Visitor<R> + ?Sized,
+    {
+        {% match ty %}
+        {% when Type::Sequence with (seq) %}
+            {% call walk_sequence(typename, seq) %}
+        {% when Type::SequenceOf with (seq) %}
+            {% if seq.types.len() == 1 %}
+                {% let (itemname, itemty) = seq.types.iter().next().unwrap() %}
+                let mut value = value;
+
+                while let Some(v) = value.next().await? {
+                    {{self.visit_call(itemty, typename, itemname, "v")}};
+                }
+
+                Ok(())
+            {% else %}
+                // sequence-ofs with multiple value-types can't be parsed either
+                let _ = (visitor, value);
+                Err(crate::Error::Unimplemented)
+            {% endif %}
+        {% when Type::Choice with (choice) %}
+            {% call walk_choice(typename, structname, choice.variants.borrow(), true) %}
+        {% when Type::ImplicitChoice with (choice) %}
+            {% call walk_choice(typename, structname, choice.types.borrow(), false) %}
+        {% endmatch %}
+    }
+{% endfor %}
@@ -0,0 +1,6 @@
+//! runs the tests of the schema handling in the build script
+
+// the build script uses more of the module than the tests do
+#[allow(dead_code)]
+#[path = "../build/schema.rs"]
+mod schema;
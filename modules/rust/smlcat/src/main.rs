@@ -0,0 +1,227 @@
+//! prints the readings of SML meters from a capture file, stdin or a serial device
+//!
+//! Serial devices have to be configured before, e.g. using `stty -F /dev/ttyUSB0 9600 raw`.
+//! Readings are only printed once the checksum of their frame was verified.
+
+#![feature(async_fn_in_trait)]
+
+use clap::Parser as _;
+
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+enum Format {
+    /// one line per reading, frames are separated by empty lines
+    Text,
+    /// one JSON object per reading
+    Json,
+}
+
+#[derive(clap::Parser)]
+#[command(about)]
+struct Args {
+    /// capture file or serial device, `-` for stdin
+    #[arg(default_value = "-")]
+    path: std::path::PathBuf,
+
+    #[arg(long, value_enum, default_value_t = Format::Text)]
+    format: Format,
+}
+
+fn open(path: &std::path::Path) -> std::io::Result<Box<dyn std::io::Read>> {
+    if path == std::path::Path::new("-") {
+        Ok(Box::new(std::io::stdin()))
+    } else {
+        Ok(Box::new(std::fs::File::open(path)?))
+    }
+}
+
+/// format `value * 10^scaler` without rounding errors
+fn scale(value: i128, scaler: i8) -> String {
+    if scaler >= 0 {
+        return match 10i128
+            .checked_pow(scaler.unsigned_abs().into())
+            .and_then(|factor| value.checked_mul(factor))
+        {
+            Some(value) => value.to_string(),
+            None => format!("{}e{}", value, scaler),
+        };
+    }
+
+    let decimals = usize::from(scaler.unsigned_abs());
+    let digits = format!("{:0>width$}", value.unsigned_abs(), width = decimals + 1);
+    let (integer, fraction) = digits.split_at(digits.len() - decimals);
+    let fraction = fraction.trim_end_matches('0');
+
+    let sign = if value < 0 { "-" } else { "" };
+    if fraction.is_empty() {
+        format!("{}{}", sign, integer)
+    } else {
+        format!("{}{}.{}", sign, integer, fraction)
+    }
+}
+
+/// octet strings are mostly ids, which are either readable text or binary
+fn format_bytes(bytes: &sml::reading::Bytes) -> String {
+    let data = bytes.as_slice();
+    let ellipsis = if bytes.is_truncated() { "..." } else { "" };
+
+    if !data.is_empty() && data.iter().all(|b| b.is_ascii_graphic() || *b == b' ') {
+        format!("{}{}", String::from_utf8_lossy(data), ellipsis)
+    } else {
+        let hex: String = data.iter().map(|b| format!("{:02x}", b)).collect();
+        format!("hex:{}{}", hex, ellipsis)
+    }
+}
+
+fn format_value(reading: &sml::reading::Reading) -> String {
+    match reading.value {
+        sml::reading::Value::Boolean(v) => v.to_string(),
+        sml::reading::Value::Bytes(bytes) => format_bytes(&bytes),
+        sml::reading::Value::List => "list".to_string(),
+        value => scale(value.to_i128().unwrap(), reading.scaler.unwrap_or(0)),
+    }
+}
+
+fn format_json(frame: usize, reading: &sml::reading::Reading) -> serde_json::Value {
+    let value = match reading.value {
+        sml::reading::Value::Boolean(v) => v.into(),
+        sml::reading::Value::List => serde_json::Value::Null,
+        sml::reading::Value::Bytes(_) => format_value(reading).into(),
+        // parsing the exact decimal gives the closest float
+        _ => format_value(reading)
+            .parse::<f64>()
+            .ok()
+            .and_then(serde_json::Number::from_f64)
+            .map_or(serde_json::Value::Null, serde_json::Value::Number),
+    };
+
+    serde_json::json!({
+        "frame": frame,
+        "obis": reading.obis.to_string(),
+        "name": reading.obis.description(),
+        "value": value,
+        "unit": reading.unit.and_then(sml::reading::unit_symbol),
+        "status": reading.status,
+    })
+}
+
+fn format_text(reading: &sml::reading::Reading) -> String {
+    let mut line = reading.obis.to_string();
+    if let Some(name) = reading.obis.description() {
+        line.push(' ');
+        line.push_str(name);
+    }
+
+    line.push_str(": ");
+    line.push_str(&format_value(reading));
+
+    match reading.unit {
+        None => (),
+        Some(unit) => match sml::reading::unit_symbol(unit) {
+            Some(symbol) => line.push_str(&format!(" {}", symbol)),
+            None => line.push_str(&format!(" (unit {})", unit)),
+        },
+    }
+
+    line
+}
+
+/// collects the readings of a frame and prints them when it was valid
+struct Printer {
+    format: Format,
+    frame: usize,
+    readings: Vec<sml::reading::Reading>,
+}
+
+impl<R: io::AsyncRead + Unpin> sml::Callback<R> for Printer {
+    fn frame_start(&mut self) {
+        self.readings.clear();
+    }
+
+    async fn message_received<'a>(
+        &'a mut self,
+        mut body: sml::types::MessageBody<'a, R>,
+    ) -> Result<(), sml::Error> {
+        let r = match body.read().await {
+            Ok(sml::types::MessageBodyEnum::GetListResponse(r)) => r,
+            // vendor specific messages
+            Ok(_) | Err(sml::Error::UnsupportedTag { .. }) => return Ok(()),
+            Err(e) => return Err(e),
+        };
+
+        let mut entries = r.entries().await?;
+        while let Some(reading) = entries.next().await? {
+            self.readings.push(reading);
+        }
+
+        Ok(())
+    }
+
+    fn frame_finished(&mut self, valid: bool) {
+        let frame = self.frame;
+        self.frame += 1;
+
+        if !valid {
+            eprintln!("frame {}: invalid, skipped", frame);
+            return;
+        }
+
+        for reading in &self.readings {
+            match self.format {
+                Format::Text => println!("{}", format_text(reading)),
+                Format::Json => println!("{}", format_json(frame, reading)),
+            }
+        }
+
+        if let Format::Text = self.format {
+            println!();
+        }
+    }
+}
+
+fn main() -> std::process::ExitCode {
+    io::block_on(run())
+}
+
+async fn run() -> std::process::ExitCode {
+    env_logger::init();
+    let args = Args::parse();
+
+    let file = match open(&args.path) {
+        Ok(file) => file,
+        Err(e) => {
+            eprintln!("can't open {}: {}", args.path.display(), e);
+            return std::process::ExitCode::FAILURE;
+        }
+    };
+    let mut reader = io::BufReader::<_, 4096>::new(io::StdReader(file));
+
+    let mut printer = Printer {
+        format: args.format,
+        frame: 0,
+        readings: Vec::new(),
+    };
+
+    // the task only returns when reading fails, which is how the end of a capture is reported
+    match sml::task(&mut reader, &mut printer).await {
+        Ok(()) | Err(sml::Error::Io(io::Error::UnexpectedEof)) => std::process::ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("failed to read {}: {}", args.path.display(), e);
+            std::process::ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn scale() {
+        assert_eq!(super::scale(2456781234, -4), "245678.1234");
+        assert_eq!(super::scale(98765, -2), "987.65");
+        assert_eq!(super::scale(-5, -2), "-0.05");
+        assert_eq!(super::scale(1200, -2), "12");
+        assert_eq!(super::scale(0, -1), "0");
+        assert_eq!(super::scale(42, 0), "42");
+        assert_eq!(super::scale(42, 3), "42000");
+        assert_eq!(super::scale(42, 100), "42e100");
+    }
+}
@@ -0,0 +1,64 @@
+//! prints the TLV tree of every SML frame read from a capture file or serial device
+//!
+//! Serial devices have to be configured before, e.g. using `stty -F /dev/ttyUSB0 9600 raw`.
+
+use clap::Parser as _;
+
+#[derive(clap::Parser)]
+#[command(about)]
+struct Args {
+    /// capture file or serial device, `-` for stdin
+    path: std::path::PathBuf,
+}
+
+fn open(path: &std::path::Path) -> std::io::Result<Box<dyn std::io::Read>> {
+    if path == std::path::Path::new("-") {
+        Ok(Box::new(std::io::stdin()))
+    } else {
+        Ok(Box::new(std::fs::File::open(path)?))
+    }
+}
+
+fn main() -> std::process::ExitCode {
+    io::block_on(run())
+}
+
+async fn run() -> std::process::ExitCode {
+    env_logger::init();
+    let args = Args::parse();
+
+    let file = match open(&args.path) {
+        Ok(file) => file,
+        Err(e) => {
+            eprintln!("can't open {}: {}", args.path.display(), e);
+            return std::process::ExitCode::FAILURE;
+        }
+    };
+    let mut reader = io::BufReader::<_, 4096>::new(io::StdReader(file));
+
+    for index in 0.. {
+        let mut out = String::new();
+        let res = sml::dump_frame(&mut reader, &mut out).await;
+
+        if !out.is_empty() {
+            println!("frame {}:", index);
+            print!("{}", out);
+        }
+
+        match res {
+            Ok(()) => (),
+            Err(sml::FrameError {
+                error: sml::Error::Io(io::Error::UnexpectedEof),
+                ..
+            }) => {
+                if !out.is_empty() {
+                    println!("incomplete frame");
+                }
+                break;
+            }
+            Err(e) => println!("invalid frame: {}", e),
+        }
+    }
+
+    std::process::ExitCode::SUCCESS
+}
@@ -0,0 +1,108 @@
+//! decodes the deferred log records of the firmware
+//!
+//! The firmware logs every record as a line with `smrlog:` followed by the record in hex. Those
+//! are replaced by the decoded message, all other lines are printed as they are.
+
+use clap::Parser as _;
+use std::io::{BufRead as _, Write as _};
+
+const MARKER: &str = "smrlog:";
+
+#[derive(clap::Parser)]
+#[command(about)]
+struct Args {
+    /// the firmware ELF file, e.g. `build/zephyr/zephyr.elf`
+    elf: std::path::PathBuf,
+    /// log file or serial device, `-` for stdin
+    #[arg(default_value = "-")]
+    path: std::path::PathBuf,
+}
+
+fn open(path: &std::path::Path) -> std::io::Result<Box<dyn std::io::Read>> {
+    if path == std::path::Path::new("-") {
+        Ok(Box::new(std::io::stdin()))
+    } else {
+        Ok(Box::new(std::fs::File::open(path)?))
+    }
+}
+
+fn parse_hex(hex: &str) -> Option<Vec<u8>> {
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// replaces the record in the line, if there's one
+fn decode_line(table: &dlog::decoder::Table, line: &str) -> String {
+    let start = match line.find(MARKER) {
+        Some(start) => start,
+        None => return line.to_owned(),
+    };
+    let hex_start = start + MARKER.len();
+    let hex_len = line[hex_start..]
+        .find(|c: char| !c.is_ascii_hexdigit())
+        .unwrap_or(line.len() - hex_start);
+    let hex = &line[hex_start..hex_start + hex_len];
+
+    let decoded = match parse_hex(hex)
+        .ok_or_else(|| "invalid hex".to_owned())
+        .and_then(|record| table.decode(&record).map_err(|e| e.to_string()))
+    {
+        Ok(record) if record.truncated => {
+            format!("{}: {} (truncated)", record.target, record.message)
+        }
+        Ok(record) => format!("{}: {}", record.target, record.message),
+        Err(e) => format!("<{}: {}>", e, hex),
+    };
+    format!(
+        "{}{}{}",
+        &line[..start],
+        decoded,
+        &line[hex_start + hex_len..]
+    )
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::parse();
+
+    let table = dlog::decoder::Table::from_elf(&std::fs::read(&args.elf)?)?;
+    let input = std::io::BufReader::new(open(&args.path)?);
+    let mut stdout = std::io::stdout().lock();
+    for line in input.lines() {
+        writeln!(stdout, "{}", decode_line(&table, &line?))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    #[test]
+    fn decode_line() {
+        let exe = std::fs::read(std::env::current_exe().unwrap()).unwrap();
+        let table = dlog::decoder::Table::from_elf(&exe).unwrap();
+
+        let mut encoder = dlog::Encoder::new(dlog::intern!("sml::frame", "value={}"));
+        dlog::Format::format(&300u16, &mut encoder);
+        let hex: String = encoder
+            .as_bytes()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+
+        assert_eq!(
+            super::decode_line(&table, &format!("<err> powermeter: smrlog:{}\r", hex)),
+            "<err> powermeter: sml::frame: value=300\r"
+        );
+        assert_eq!(
+            super::decode_line(&table, "<err> powermeter: smrlog:abc"),
+            "<err> powermeter: <invalid hex: abc>"
+        );
+        assert_eq!(
+            super::decode_line(&table, "<err> powermeter: smrlog:"),
+            "<err> powermeter: <empty record: >"
+        );
+        assert_eq!(super::decode_line(&table, "other"), "other");
+    }
+}
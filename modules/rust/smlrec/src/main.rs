@@ -0,0 +1,109 @@
+//! records the bytes of a serial device with their timing, and replays such recordings
+//!
+//! Serial devices have to be configured before, e.g. using `stty -F /dev/ttyUSB0 9600 raw`.
+//! For replaying the chunks exactly as they were read in tests, use [io::replay::Replay].
+
+use clap::Parser as _;
+use std::io::{Read as _, Write as _};
+
+#[derive(clap::Parser)]
+#[command(about)]
+struct Args {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(clap::Subcommand)]
+enum Command {
+    /// record until the device is closed or interrupted using ctrl-c
+    Record {
+        /// serial device, `-` for stdin
+        device: std::path::PathBuf,
+        /// the recording to write
+        file: std::path::PathBuf,
+    },
+    /// write the data of a recording to stdout, e.g. for piping it into smldump
+    Replay {
+        /// the recording to read
+        file: std::path::PathBuf,
+        /// don't wait for the recorded delays
+        #[arg(long)]
+        fast: bool,
+    },
+}
+
+fn open(path: &std::path::Path) -> std::io::Result<Box<dyn std::io::Read>> {
+    if path == std::path::Path::new("-") {
+        Ok(Box::new(std::io::stdin()))
+    } else {
+        Ok(Box::new(std::fs::File::open(path)?))
+    }
+}
+
+fn record(device: &std::path::Path, file: &std::path::Path) -> Result<(), String> {
+    let mut input = open(device).map_err(|e| format!("can't open {}: {}", device.display(), e))?;
+    let out = std::fs::File::create(file)
+        .map_err(|e| format!("can't create {}: {}", file.display(), e))?;
+    let write_error = |e| format!("can't write {}: {}", file.display(), e);
+    let mut recorder =
+        io::replay::Recorder::new(std::io::BufWriter::new(out)).map_err(write_error)?;
+
+    let mut buf = [0u8; 1024];
+    let mut total = 0;
+    loop {
+        let len = match input.read(&mut buf) {
+            Ok(0) => break,
+            Ok(len) => len,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(format!("can't read {}: {}", device.display(), e)),
+        };
+
+        recorder.record(&buf[..len]).map_err(write_error)?;
+        // so an interrupted recording is still usable
+        recorder.get_mut().flush().map_err(write_error)?;
+
+        total += len;
+        log::debug!("recorded {} bytes, {} total", len, total);
+    }
+
+    Ok(())
+}
+
+fn replay(file: &std::path::Path, fast: bool) -> Result<(), String> {
+    let recording =
+        std::fs::read(file).map_err(|e| format!("can't read {}: {}", file.display(), e))?;
+    let records =
+        io::replay::parse(&recording).map_err(|e| format!("{}: {}", file.display(), e))?;
+
+    let mut stdout = std::io::stdout().lock();
+    for record in records {
+        if !fast {
+            std::thread::sleep(record.delay);
+        }
+
+        stdout
+            .write_all(record.data)
+            .and_then(|()| stdout.flush())
+            .map_err(|e| format!("can't write to stdout: {}", e))?;
+    }
+
+    Ok(())
+}
+
+fn main() -> std::process::ExitCode {
+    env_logger::init();
+    let args = Args::parse();
+
+    let res = match args.command {
+        Command::Record { device, file } => record(&device, &file),
+        Command::Replay { file, fast } => replay(&file, fast),
+    };
+
+    match res {
+        Ok(()) => std::process::ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,168 @@
+//! simulates a SML meter on a pseudo-terminal
+//!
+//! The path of the terminal is printed on startup and can be used like the serial device of a
+//! real meter, e.g. `smlcat /dev/pts/5`. Frames which nobody reads are dropped.
+
+// the tests decode the frames using a `sml::Callback`
+#![cfg_attr(test, feature(async_fn_in_trait))]
+
+mod meter;
+
+use clap::Parser as _;
+use std::io::Write as _;
+
+#[derive(clap::Parser)]
+#[command(about)]
+struct Args {
+    /// the values to send, in this order
+    #[arg(long = "value", value_enum, default_values_t = meter::DEFAULT_VALUES)]
+    values: Vec<meter::Value>,
+
+    /// seconds between two frames
+    #[arg(long, default_value_t = 1.0, value_parser = parse_interval)]
+    interval: f64,
+
+    /// the average power in W, negative values simulate feeding in
+    #[arg(long, default_value_t = 400.0, allow_negative_numbers = true)]
+    power: f64,
+
+    /// the initial reading of the import counter in kWh
+    #[arg(long, default_value_t = 12345.0)]
+    energy: f64,
+
+    /// behave like a meter without entered PIN: only counters, with a resolution of 1 kWh
+    #[arg(long)]
+    locked: bool,
+
+    /// probability for a frame with a wrong checksum
+    #[arg(long, default_value_t = 0.0)]
+    crc_errors: f64,
+
+    /// probability for a frame which is cut off
+    #[arg(long, default_value_t = 0.0)]
+    truncated: f64,
+
+    /// seed for the noise and the injected errors, random by default
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// create a symlink to the terminal, for a stable path in scripts
+    #[arg(long)]
+    link: Option<std::path::PathBuf>,
+}
+
+/// a positive and finite number of seconds, which fits into a [std::time::Duration]
+fn parse_interval(s: &str) -> Result<f64, String> {
+    let interval: f64 = s.parse().map_err(|e| format!("{}", e))?;
+    match std::time::Duration::try_from_secs_f64(interval) {
+        Ok(duration) if !duration.is_zero() => Ok(interval),
+        _ => Err("must be a positive number of seconds".to_owned()),
+    }
+}
+
+/// the terminal has to be raw, or line endings in the binary data get translated
+fn open_pty() -> nix::Result<nix::pty::OpenptyResult> {
+    let pty = nix::pty::openpty(None, None)?;
+
+    let mut termios = nix::sys::termios::tcgetattr(&pty.slave)?;
+    nix::sys::termios::cfmakeraw(&mut termios);
+    nix::sys::termios::tcsetattr(&pty.slave, nix::sys::termios::SetArg::TCSANOW, &termios)?;
+
+    // don't block when nobody reads
+    nix::fcntl::fcntl(
+        std::os::fd::AsRawFd::as_raw_fd(&pty.master),
+        nix::fcntl::FcntlArg::F_SETFL(nix::fcntl::OFlag::O_NONBLOCK),
+    )?;
+
+    Ok(pty)
+}
+
+fn main() -> std::process::ExitCode {
+    env_logger::init();
+    let args = Args::parse();
+
+    let pty = match open_pty() {
+        Ok(pty) => pty,
+        Err(e) => {
+            eprintln!("can't open a pseudo-terminal: {}", e);
+            return std::process::ExitCode::FAILURE;
+        }
+    };
+    let path = nix::unistd::ttyname(std::os::fd::AsRawFd::as_raw_fd(&pty.slave))
+        .expect("pseudo-terminal without a name");
+    println!("{}", path.display());
+
+    if let Some(link) = &args.link {
+        // replace the link of a previous run
+        if link.is_symlink() {
+            let _ = std::fs::remove_file(link);
+        }
+        if let Err(e) = std::os::unix::fs::symlink(&path, link) {
+            eprintln!("can't create {}: {}", link.display(), e);
+            return std::process::ExitCode::FAILURE;
+        }
+    }
+
+    // the slave stays open, so writing doesn't fail while no reader is connected
+    let _slave = pty.slave;
+    let mut master = std::fs::File::from(pty.master);
+
+    let seed = args.seed.unwrap_or_else(|| {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(1, |d| d.as_nanos() as u64)
+    });
+    log::info!("seed: {}", seed);
+    let mut rng = meter::Rng::new(seed);
+
+    let mut meter = meter::Meter::new(args.values, args.locked, args.power, args.energy);
+    let interval = std::time::Duration::from_secs_f64(args.interval);
+    let mut buf = [0u8; 2048];
+
+    loop {
+        std::thread::sleep(interval);
+        meter.step(args.interval, &mut rng);
+
+        let len = match meter.encode(&mut buf) {
+            Ok(len) => len,
+            Err(e) => {
+                eprintln!("can't encode frame: {:?}", e);
+                return std::process::ExitCode::FAILURE;
+            }
+        };
+        let mut frame = &mut buf[..len];
+
+        if rng.chance(args.crc_errors) {
+            log::info!("inject checksum error");
+            meter::corrupt_checksum(frame);
+        }
+        if rng.chance(args.truncated) {
+            let cut = (rng.next_u64() % len as u64) as usize;
+            log::info!("truncate frame to {} of {} bytes", cut, len);
+            frame = &mut frame[..cut];
+        }
+
+        match master.write_all(frame) {
+            Ok(()) => log::debug!("sent {} bytes", frame.len()),
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                log::warn!("nobody is reading, dropped frame");
+            }
+            Err(e) => {
+                eprintln!("can't write to {}: {}", path.display(), e);
+                return std::process::ExitCode::FAILURE;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn parse_interval() {
+        assert_eq!(super::parse_interval("0.5"), Ok(0.5));
+        assert_eq!(super::parse_interval("4"), Ok(4.0));
+        for invalid in ["0", "-1", "nan", "inf", "1e300", "x"] {
+            assert!(super::parse_interval(invalid).is_err(), "{}", invalid);
+        }
+    }
+}
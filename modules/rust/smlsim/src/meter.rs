@@ -0,0 +1,454 @@
+//! the state of the simulated meter and how it's encoded
+
+use sml::tlv::Writer;
+
+/// a small xorshift generator, good enough for noise and reproducible using `--seed`
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        // xorshift gets stuck at 0
+        Self(seed.max(1))
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// a number in `0.0..1.0`
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// returns true with the given probability
+    pub fn chance(&mut self, probability: f64) -> bool {
+        self.next_f64() < probability
+    }
+
+    /// a number in `-amplitude..amplitude`
+    pub fn noise(&mut self, amplitude: f64) -> f64 {
+        (self.next_f64() * 2.0 - 1.0) * amplitude
+    }
+}
+
+/// the values a meter can send
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Value {
+    /// 129-129:199.130.3*255
+    Manufacturer,
+    /// 1-0:0.0.9*255
+    DeviceId,
+    /// 1-0:1.8.0*255
+    EnergyImport,
+    /// 1-0:2.8.0*255
+    EnergyExport,
+    /// 1-0:16.7.0*255
+    Power,
+    /// 1-0:36.7.0*255
+    PowerL1,
+    /// 1-0:56.7.0*255
+    PowerL2,
+    /// 1-0:76.7.0*255
+    PowerL3,
+    /// 1-0:32.7.0*255
+    VoltageL1,
+    /// 1-0:52.7.0*255
+    VoltageL2,
+    /// 1-0:72.7.0*255
+    VoltageL3,
+    /// 1-0:14.7.0*255
+    Frequency,
+}
+
+/// what most household meters send
+pub const DEFAULT_VALUES: [Value; 8] = [
+    Value::Manufacturer,
+    Value::DeviceId,
+    Value::EnergyImport,
+    Value::EnergyExport,
+    Value::Power,
+    Value::PowerL1,
+    Value::PowerL2,
+    Value::PowerL3,
+];
+
+impl Value {
+    fn obis(self) -> [u8; 6] {
+        match self {
+            Self::Manufacturer => [129, 129, 199, 130, 3, 255],
+            Self::DeviceId => [1, 0, 0, 0, 9, 255],
+            Self::EnergyImport => [1, 0, 1, 8, 0, 255],
+            Self::EnergyExport => [1, 0, 2, 8, 0, 255],
+            Self::Power => [1, 0, 16, 7, 0, 255],
+            Self::PowerL1 => [1, 0, 36, 7, 0, 255],
+            Self::PowerL2 => [1, 0, 56, 7, 0, 255],
+            Self::PowerL3 => [1, 0, 76, 7, 0, 255],
+            Self::VoltageL1 => [1, 0, 32, 7, 0, 255],
+            Self::VoltageL2 => [1, 0, 52, 7, 0, 255],
+            Self::VoltageL3 => [1, 0, 72, 7, 0, 255],
+            Self::Frequency => [1, 0, 14, 7, 0, 255],
+        }
+    }
+
+    /// meters without an entered PIN only send the counters
+    fn is_public(self) -> bool {
+        matches!(
+            self,
+            Self::Manufacturer | Self::DeviceId | Self::EnergyImport | Self::EnergyExport
+        )
+    }
+}
+
+/// flip the bits of the last byte of the frame checksum, like `--crc-errors` does
+pub fn corrupt_checksum(frame: &mut [u8]) {
+    if let Some(byte) = frame.last_mut() {
+        *byte ^= 0xff;
+    }
+}
+
+const SERVER_ID: [u8; 10] = [0x0a, 0x01, 0x53, 0x49, 0x4d, 0x00, 0x00, 0x00, 0x00, 0x01];
+
+const TAG_OPEN_RESPONSE: u64 = 0x0101;
+const TAG_CLOSE_RESPONSE: u64 = 0x0201;
+const TAG_GET_LIST_RESPONSE: u64 = 0x0701;
+
+const UNIT_WH: u8 = 30;
+const UNIT_W: u8 = 27;
+const UNIT_V: u8 = 35;
+const UNIT_HZ: u8 = 44;
+
+/// the status word EMH meters send with their counters
+const STATUS: u64 = 0x0001_0182;
+
+pub struct Meter {
+    pub values: Vec<Value>,
+    pub locked: bool,
+    /// the average power in W, negative when feeding in
+    pub base_power: f64,
+
+    seconds: f64,
+    transaction: u32,
+    /// the counters in 0.1 Wh, like real meters send them
+    energy_import: f64,
+    energy_export: f64,
+    power: [f64; 3],
+    voltage: [f64; 3],
+    frequency: f64,
+}
+
+impl Meter {
+    /// `energy` is the initial reading of the import counter in kWh
+    pub fn new(values: Vec<Value>, locked: bool, base_power: f64, energy: f64) -> Self {
+        Self {
+            values,
+            locked,
+            base_power,
+            seconds: 0.0,
+            transaction: 0,
+            energy_import: energy * 10_000.0,
+            energy_export: 0.0,
+            power: [base_power / 3.0; 3],
+            voltage: [230.0; 3],
+            frequency: 50.0,
+        }
+    }
+
+    /// advance the simulation by `seconds`
+    pub fn step(&mut self, seconds: f64, rng: &mut Rng) {
+        self.seconds += seconds;
+
+        for power in &mut self.power {
+            // a random walk which is pulled back to the average
+            *power += (self.base_power / 3.0 - *power) * 0.2
+                + rng.noise(self.base_power.abs() * 0.05 + 5.0);
+        }
+        for voltage in &mut self.voltage {
+            *voltage = 230.0 + rng.noise(3.0);
+        }
+        self.frequency = 50.0 + rng.noise(0.05);
+
+        // the counters are in 0.1 Wh
+        let energy = self.power.iter().sum::<f64>() * seconds / 3600.0 * 10.0;
+        if energy >= 0.0 {
+            self.energy_import += energy;
+        } else {
+            self.energy_export -= energy;
+        }
+    }
+
+    /// encode a frame with an open, a get list and a close response into `out`
+    pub fn encode(&mut self, out: &mut [u8]) -> Result<usize, sml::Error> {
+        let mut payload = [0u8; 1024];
+        let mut writer = Writer::new(&mut payload);
+
+        self.write_message(&mut writer, TAG_OPEN_RESPONSE, |w| {
+            w.write_list(6)?;
+            // codepage, clientId
+            w.write_none()?;
+            w.write_none()?;
+            w.write_string(&self.transaction.to_be_bytes())?;
+            w.write_string(&SERVER_ID)?;
+            // refTime, smlVersion
+            w.write_none()?;
+            w.write_none()
+        })?;
+
+        self.write_message(&mut writer, TAG_GET_LIST_RESPONSE, |w| self.write_list(w))?;
+
+        self.write_message(&mut writer, TAG_CLOSE_RESPONSE, |w| {
+            w.write_list(1)?;
+            // globalSignature
+            w.write_none()
+        })?;
+
+        self.transaction = self.transaction.wrapping_add(1);
+        sml::write_frame(writer.written(), out)
+    }
+
+    fn write_message<F>(&self, w: &mut Writer, tag: u64, body: F) -> Result<(), sml::Error>
+    where
+        F: FnOnce(&mut Writer) -> Result<(), sml::Error>,
+    {
+        let start = w.written().len();
+
+        w.write_list(6)?;
+        w.write_string(&self.transaction.to_be_bytes())?;
+        // groupNo, abortOnError
+        w.write_unsigned(0, 1)?;
+        w.write_unsigned(0, 1)?;
+
+        w.write_list(2)?;
+        w.write_unsigned(tag, 4)?;
+        body(w)?;
+
+        w.write_crc16(start)?;
+        w.write_end_of_message()
+    }
+
+    fn write_list(&self, w: &mut Writer) -> Result<(), sml::Error> {
+        let values: Vec<_> = self
+            .values
+            .iter()
+            .copied()
+            .filter(|v| !self.locked || v.is_public())
+            .collect();
+
+        w.write_list(7)?;
+        // clientId
+        w.write_none()?;
+        w.write_string(&SERVER_ID)?;
+        // listName
+        w.write_none()?;
+
+        // actSensorTime, a secIndex
+        w.write_list(2)?;
+        w.write_unsigned(1, 1)?;
+        w.write_unsigned(self.seconds as u64, 4)?;
+
+        w.write_list(values.len())?;
+        for value in values {
+            self.write_entry(w, value)?;
+        }
+
+        // listSignature, actGatewayTime
+        w.write_none()?;
+        w.write_none()
+    }
+
+    fn write_entry(&self, w: &mut Writer, value: Value) -> Result<(), sml::Error> {
+        // without PIN, the counters only have a resolution of 1 kWh
+        let counter = |v: f64| {
+            let v = v as u64;
+            if self.locked {
+                v / 10_000 * 10_000
+            } else {
+                v
+            }
+        };
+
+        w.write_list(7)?;
+        w.write_string(&value.obis())?;
+
+        let status = match value {
+            Value::EnergyImport | Value::EnergyExport => Some(STATUS),
+            _ => None,
+        };
+        match status {
+            Some(status) => w.write_unsigned(status, 4)?,
+            None => w.write_none()?,
+        }
+
+        // valTime
+        w.write_none()?;
+
+        let (unit, scaler) = match value {
+            Value::Manufacturer | Value::DeviceId => (None, None),
+            Value::EnergyImport | Value::EnergyExport => (Some(UNIT_WH), Some(-1)),
+            Value::Power | Value::PowerL1 | Value::PowerL2 | Value::PowerL3 => {
+                (Some(UNIT_W), Some(0))
+            }
+            Value::VoltageL1 | Value::VoltageL2 | Value::VoltageL3 => (Some(UNIT_V), Some(-1)),
+            Value::Frequency => (Some(UNIT_HZ), Some(-2)),
+        };
+        match unit {
+            Some(unit) => w.write_unsigned(unit.into(), 1)?,
+            None => w.write_none()?,
+        }
+        match scaler {
+            Some(scaler) => w.write_integer(scaler, 1)?,
+            None => w.write_none()?,
+        }
+
+        match value {
+            Value::Manufacturer => w.write_string(b"SIM")?,
+            Value::DeviceId => w.write_string(&SERVER_ID)?,
+            Value::EnergyImport => w.write_unsigned(counter(self.energy_import), 8)?,
+            Value::EnergyExport => w.write_unsigned(counter(self.energy_export), 8)?,
+            Value::Power => w.write_integer(self.power.iter().sum::<f64>().round() as i64, 4)?,
+            Value::PowerL1 => w.write_integer(self.power[0].round() as i64, 4)?,
+            Value::PowerL2 => w.write_integer(self.power[1].round() as i64, 4)?,
+            Value::PowerL3 => w.write_integer(self.power[2].round() as i64, 4)?,
+            Value::VoltageL1 => w.write_unsigned((self.voltage[0] * 10.0) as u64, 2)?,
+            Value::VoltageL2 => w.write_unsigned((self.voltage[1] * 10.0) as u64, 2)?,
+            Value::VoltageL3 => w.write_unsigned((self.voltage[2] * 10.0) as u64, 2)?,
+            Value::Frequency => w.write_unsigned((self.frequency * 100.0) as u64, 2)?,
+        }
+
+        // valueSignature
+        w.write_none()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Meter, Rng, Value};
+    use sml::reading::Reading;
+
+    /// collects the readings of every frame and whether it was valid
+    #[derive(Default)]
+    struct Frames {
+        current: Vec<Reading>,
+        frames: Vec<(bool, Vec<Reading>)>,
+    }
+
+    impl<R: io::AsyncRead + Unpin> sml::Callback<R> for Frames {
+        fn frame_start(&mut self) {
+            self.current.clear();
+        }
+
+        async fn message_received<'a>(
+            &'a mut self,
+            mut body: sml::types::MessageBody<'a, R>,
+        ) -> Result<(), sml::Error> {
+            if let sml::types::MessageBodyEnum::GetListResponse(r) = body.read().await? {
+                let mut entries = r.entries().await?;
+                while let Some(reading) = entries.next().await? {
+                    self.current.push(reading);
+                }
+            }
+            Ok(())
+        }
+
+        fn frame_finished(&mut self, valid: bool) {
+            self.frames
+                .push((valid, core::mem::take(&mut self.current)));
+        }
+    }
+
+    /// runs the simulator for `steps` minutes, passing each frame to `modify`
+    fn simulate(meter: &mut Meter, steps: usize, modify: impl Fn(usize, &mut [u8])) -> Vec<u8> {
+        let mut rng = Rng::new(42);
+        let mut data = Vec::new();
+        let mut buf = [0u8; 2048];
+        for step in 0..steps {
+            meter.step(60.0, &mut rng);
+            let len = meter.encode(&mut buf).unwrap();
+            modify(step, &mut buf[..len]);
+            data.extend_from_slice(&buf[..len]);
+        }
+        data
+    }
+
+    fn decode(data: &[u8]) -> Vec<(bool, Vec<Reading>)> {
+        let mut reader = io::BufReader::<_, 64>::new(io::StdReader(data));
+        let mut frames = Frames::default();
+        let res = io::block_on(sml::task(&mut reader, &mut frames));
+        assert!(matches!(res, Err(sml::Error::Io(io::Error::UnexpectedEof))));
+        frames.frames
+    }
+
+    fn counter(readings: &[Reading], value: Value) -> u64 {
+        let reading = readings
+            .iter()
+            .find(|reading| reading.obis.0 == value.obis())
+            .unwrap();
+        match reading.value {
+            sml::reading::Value::Unsigned(counter) => counter,
+            other => panic!("unexpected counter {:?}", other),
+        }
+    }
+
+    #[test]
+    fn decode_frames() {
+        let values = vec![
+            Value::Manufacturer,
+            Value::EnergyImport,
+            Value::Power,
+            Value::VoltageL1,
+            Value::Frequency,
+        ];
+        let mut meter = Meter::new(values.clone(), false, 400.0, 12345.0);
+        let frames = decode(&simulate(&mut meter, 5, |_, _| ()));
+        assert_eq!(frames.len(), 5);
+
+        let mut counters = Vec::new();
+        for (valid, readings) in &frames {
+            assert!(valid);
+            let obis: Vec<_> = readings.iter().map(|reading| reading.obis.0).collect();
+            let expected: Vec<_> = values.iter().map(|value| value.obis()).collect();
+            assert_eq!(obis, expected);
+            counters.push(counter(readings, Value::EnergyImport));
+        }
+
+        // 400 W for a minute are about 67 units of 0.1 Wh
+        assert!(counters[0] >= 123_450_000);
+        assert!(counters.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+
+    #[test]
+    fn locked() {
+        let mut meter = Meter::new(super::DEFAULT_VALUES.to_vec(), true, 400.0, 12345.0);
+        let frames = decode(&simulate(&mut meter, 3, |_, _| ()));
+        assert_eq!(frames.len(), 3);
+
+        for (valid, readings) in &frames {
+            assert!(valid);
+            // only the public values, with the counters rounded to kWh
+            let obis: Vec<_> = readings.iter().map(|reading| reading.obis.0).collect();
+            let expected: Vec<_> = super::DEFAULT_VALUES
+                .iter()
+                .filter(|value| value.is_public())
+                .map(|value| value.obis())
+                .collect();
+            assert_eq!(obis, expected);
+            assert_eq!(counter(readings, Value::EnergyImport), 123_450_000);
+            assert_eq!(counter(readings, Value::EnergyExport), 0);
+        }
+    }
+
+    #[test]
+    fn crc_errors() {
+        let mut meter = Meter::new(super::DEFAULT_VALUES.to_vec(), false, 400.0, 12345.0);
+        let data = simulate(&mut meter, 3, |step, frame| {
+            if step == 1 {
+                super::corrupt_checksum(frame);
+            }
+        });
+
+        let valid: Vec<_> = decode(&data).iter().map(|(valid, _)| *valid).collect();
+        assert_eq!(valid, [true, false, true]);
+    }
+}